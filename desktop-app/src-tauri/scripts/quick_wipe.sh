@@ -4,11 +4,18 @@
 # 3 passes x 1GB chunks, ~15 minutes
 # Good for most trade-in scenarios
 #
-# Version: 2.2.0
+# Version: 2.3.0
 # Repository: https://github.com/OnlyParams/android-secure-wipe
 #
 # CHANGELOG:
 # ----------
+# v2.3.0 (2026-10-16)
+#   - --help now lists --raw, so the desktop app can detect it
+#   - Added --keep-files flag to leave the last pass's data for inspection
+#   - Each pass is written in 64 MB steps, with progress and free space reported per step
+#   - Reports "Removing temporary files..." while the wipe directory is removed
+#   - --size now accepts 1-65536 MB (was 64-10240)
+#
 # v2.2.0 (2024-12-11)
 #   - Added input validation for --passes (must be 1-20) and --size (64-10240 MB)
 #   - Rejects non-numeric values with clear error messages
@@ -36,7 +43,7 @@
 
 set -euo pipefail
 
-VERSION="2.2.0"
+VERSION="2.3.0"
 
 # Colors for output
 RED='\033[0;31m'
@@ -52,7 +59,9 @@ WIPE_DIR="/sdcard/wipe_temp"
 AUTO_YES=false
 DRY_RUN=false
 RAW_OUTPUT=false    # Raw mode for Tauri - no pipe buffering
+KEEP_FILES=false    # Leave the last pass's data on the device
 MIN_SPACE_MB=100    # Minimum required space in MB
+PROGRESS_STEP_MB=64 # Report progress after each step of this size
 DEVICE=""           # Must be specified via -d flag
 
 # Parse command line arguments
@@ -82,6 +91,10 @@ while [[ $# -gt 0 ]]; do
             RAW_OUTPUT=true
             shift
             ;;
+        --keep-files)
+            KEEP_FILES=true
+            shift
+            ;;
         --version|-v)
             echo "quick_wipe.sh version $VERSION"
             exit 0
@@ -94,9 +107,11 @@ while [[ $# -gt 0 ]]; do
             echo ""
             echo "Options:"
             echo "  -p, --passes N    Number of overwrite passes (default: 3, max: 20)"
-            echo "  -s, --size MB     Size in MB to write per pass (default: 1024, range: 64-10240)"
+            echo "  -s, --size MB     Size in MB to write per pass (default: 1024, range: 1-65536)"
             echo "  --dry-run         Show what would be done without writing any data"
             echo "  --yes, -y         Skip confirmation prompt (for automation)"
+            echo "  --raw             Plain unbuffered output (used by the desktop app)"
+            echo "  --keep-files      Leave the last pass's data in $WIPE_DIR for inspection"
             echo "  --version         Show version number"
             echo "  --help            Show this help message"
             echo ""
@@ -136,15 +151,15 @@ if [ "$PASSES" -lt 1 ] || [ "$PASSES" -gt 20 ]; then
     exit 1
 fi
 
-# Validate CHUNK_SIZE_MB is a number between 64 and 10240 (64MB to 10GB)
+# Validate CHUNK_SIZE_MB is a number between 1 and 65536 (1MB to 64GB)
 if ! [[ "$CHUNK_SIZE_MB" =~ ^[0-9]+$ ]]; then
     echo -e "${RED}Error: --size must be a number (in megabytes)${NC}"
     echo "Example: --size 1024  (for 1GB per pass)"
     exit 1
 fi
 
-if [ "$CHUNK_SIZE_MB" -lt 64 ] || [ "$CHUNK_SIZE_MB" -gt 10240 ]; then
-    echo -e "${RED}Error: --size must be between 64 and 10240 MB${NC}"
+if [ "$CHUNK_SIZE_MB" -lt 1 ] || [ "$CHUNK_SIZE_MB" -gt 65536 ]; then
+    echo -e "${RED}Error: --size must be between 1 and 65536 MB${NC}"
     echo "You specified: ${CHUNK_SIZE_MB}MB"
     echo ""
     echo "Recommended values:"
@@ -304,6 +319,8 @@ REMOTE_SCRIPT="#!/system/bin/sh
 WIPE_DIR=\"$WIPE_DIR\"
 PASSES=$PASSES
 CHUNK_SIZE_MB=$CHUNK_SIZE_MB
+KEEP_FILES=$KEEP_FILES
+STEP_MB=$PROGRESS_STEP_MB
 
 mkdir -p \"\$WIPE_DIR\"
 
@@ -312,19 +329,37 @@ for pass in \$(seq 1 \$PASSES); do
     FILENAME=\"\$WIPE_DIR/wipe_pass_\${pass}.bin\"
 
     echo \"Writing \${CHUNK_SIZE_MB}MB of random data...\"
-    dd if=/dev/urandom of=\"\$FILENAME\" bs=1048576 count=\$CHUNK_SIZE_MB 2>&1 | grep -v records || true
+    # Appended in steps so progress (and the free space left) is reported as it goes
+    CHUNKS=\$(( (CHUNK_SIZE_MB + STEP_MB - 1) / STEP_MB ))
+    WRITTEN_MB=0
+    : > \"\$FILENAME\"
+    for chunk in \$(seq 1 \$CHUNKS); do
+        COUNT=\$STEP_MB
+        [ \$((CHUNK_SIZE_MB - WRITTEN_MB)) -lt \$COUNT ] && COUNT=\$((CHUNK_SIZE_MB - WRITTEN_MB))
+        dd if=/dev/urandom bs=1048576 count=\$COUNT 2>&1 >> \"\$FILENAME\" | grep -v -e records -e copied || true
+        WRITTEN_MB=\$((WRITTEN_MB + COUNT))
+        FREE_KB=\$(df \"\$WIPE_DIR\" 2>/dev/null | tail -1 | awk '{print \$4}')
+        echo \"Pass \$pass: chunk \$chunk of \$CHUNKS, \${WRITTEN_MB}MB written, \${FREE_KB}KB free\"
+    done
 
     echo \"Syncing...\"
     sync
 
-    echo \"Deleting pass \$pass data...\"
-    rm -f \"\$FILENAME\"
-    sync
+    if [ \"\$KEEP_FILES\" = true ] && [ \$pass -eq \$PASSES ]; then
+        echo \"Keeping pass \$pass data in \$WIPE_DIR\"
+    else
+        echo \"Deleting pass \$pass data...\"
+        rm -f \"\$FILENAME\"
+        sync
+    fi
 
     echo \"Pass \$pass complete\"
 done
 
-rm -rf \"\$WIPE_DIR\"
+if [ \"\$KEEP_FILES\" != true ]; then
+    echo \"Removing temporary files...\"
+    rm -rf \"\$WIPE_DIR\"
+fi
 sync
 echo \"Passes completed: \$PASSES\"
 "
@@ -340,7 +375,7 @@ else
                 echo ""
                 echo -e "${BLUE}$line${NC}"
                 ;;
-            "Writing"*|"Syncing"*|"Deleting"*)
+            "Writing"*|"Syncing"*|"Deleting"*|"Removing"*)
                 echo -e "  ${YELLOW}$line${NC}"
                 ;;
             "Pass "*" complete")
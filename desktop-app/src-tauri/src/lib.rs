@@ -8,16 +8,203 @@
 // - Secure wipe execution (quick/full modes) with progress streaming
 // - Factory reset triggering
 // - Device-specific instructions
+// - Persistent audit log with rotation
+// - Wipe session records and signed certificates (JSON and PDF)
 
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::io::{BufRead, BufReader};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex, OnceLock, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tauri::{Emitter, Manager, State};
 
 // Global state for managing the running wipe process
+#[derive(Default)]
 struct WipeState {
     device_id: Mutex<Option<String>>,
+    claimed_as: Mutex<Option<String>>, // Id the wipe was claimed under, before reclaim_as
+    progress: Mutex<HashMap<String, WipeProgress>>, // Latest progress per device
+    snapshots: Mutex<HashMap<String, Vec<StorageSnapshot>>>, // Held for each device's next session
+    data_only: Mutex<HashSet<String>>, // Devices whose running wipe skips the factory reset
+    passes_completed: Mutex<HashMap<String, u32>>, // Highest pass reported complete per device
+    phases: Mutex<HashMap<String, Vec<PhaseTransition>>>, // Each device's wipe so far
+    workflow_serials: Mutex<HashMap<String, String>>, // Serial behind each id when its wipe began
+    launch: Mutex<WipeLaunch>, // Whether the claimed wipe's script has been started
+    script_group: Arc<Mutex<Option<u32>>>, // Process group the running wipe script leads
+    last_finished: Mutex<Option<String>>, // Device of the last wipe to finish, until the next
+}
+
+/// Start of the wipe in `WipeState::device_id`. Between run_wipe claiming the device and its
+/// script starting there is no process to stop, so an abort in that window cancels the start.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum WipeLaunch {
+    #[default]
+    Spawned, // Script started (or no wipe claimed)
+    Pending,
+    Cancelled, // Aborted while pending; the script is never started
+}
+
+/// Error a wipe returns when it was aborted before its script started
+const WIPE_CANCELLED_BEFORE_START: &str = "Wipe cancelled before it started.";
+
+impl WipeState {
+    /// Claim the wipe slot for a device whose wipe is being prepared
+    fn claim(&self, device_id: &str) {
+        *self.device_id.lock().unwrap() = Some(device_id.to_string());
+        *self.claimed_as.lock().unwrap() = Some(device_id.to_string());
+        *self.launch.lock().unwrap() = WipeLaunch::Pending;
+        *self.last_finished.lock().unwrap() = None;
+    }
+
+    /// Keep the claim (and any cancel of it) when the device turns out to have a new id
+    fn reclaim_as(&self, device_id: &str) {
+        let mut claimed = self.device_id.lock().unwrap();
+        if claimed.is_some() {
+            *claimed = Some(device_id.to_string());
+        }
+    }
+
+    /// Whether a wipe is claimed for the device, under its current id or the one it was
+    /// claimed with (a batch still knows a reconnected device by its old id)
+    fn is_claimed_for(&self, device_id: &str) -> bool {
+        let claimed = self.device_id.lock().unwrap();
+        claimed.as_deref() == Some(device_id)
+            || (claimed.is_some() && self.claimed_as.lock().unwrap().as_deref() == Some(device_id))
+    }
+
+    /// Start the claimed wipe's script unless it was aborted first. The launch lock is held
+    /// while spawning, so an abort either cancels the start or finds the script running.
+    fn launch<'p>(
+        &self,
+        spawn: &mut dyn FnMut() -> Result<Box<dyn WipeProcess + 'p>, String>,
+    ) -> Result<Box<dyn WipeProcess + 'p>, String> {
+        let mut launch = self.launch.lock().unwrap();
+        let cancelled = *launch == WipeLaunch::Cancelled;
+        *launch = WipeLaunch::Spawned;
+        if cancelled {
+            return Err(WIPE_CANCELLED_BEFORE_START.to_string());
+        }
+        let process = spawn()?;
+        *self.script_group.lock().unwrap() = process.pid();
+        Ok(process)
+    }
+
+    /// Process group of the running wipe script, for stopping it
+    fn script_group(&self) -> Option<u32> {
+        *self.script_group.lock().unwrap()
+    }
+
+    /// The script has exited; its group id may be reused and must not be killed
+    fn script_exited(&self) {
+        *self.script_group.lock().unwrap() = None;
+    }
+
+    /// Cancel the claimed wipe if its script hasn't been started; false if it has
+    fn cancel_launch(&self) -> bool {
+        let mut launch = self.launch.lock().unwrap();
+        if *launch == WipeLaunch::Spawned {
+            return false;
+        }
+        *launch = WipeLaunch::Cancelled;
+        true
+    }
+
+    /// Whether the claimed wipe was aborted before its script started
+    fn launch_cancelled(&self) -> bool {
+        *self.launch.lock().unwrap() == WipeLaunch::Cancelled
+    }
+
+    /// Free the wipe slot. A wipe that ran to an end (even a failed one) is remembered, so
+    /// a late abort can say it already finished.
+    fn release(&self, device_id: &str, finished: bool) {
+        *self.device_id.lock().unwrap() = None;
+        if finished {
+            *self.last_finished.lock().unwrap() = Some(device_id.to_string());
+        }
+    }
+
+    fn record_progress(&self, device_id: &str, progress: &WipeProgress) {
+        self.note_phase(device_id, &progress.phase, unix_timestamp_ms());
+        if progress.phase == "complete" {
+            let mut completed = self.passes_completed.lock().unwrap();
+            let highest = completed.entry(device_id.to_string()).or_default();
+            *highest = (*highest).max(progress.pass);
+        }
+        let mut latest = self.progress.lock().unwrap();
+        latest.insert(device_id.to_string(), progress.clone());
+    }
+
+    /// Record entering `phase` at `at_ms`, unless the wipe is already in it
+    fn note_phase(&self, device_id: &str, phase: &str, at_ms: u64) {
+        let mut phases = self.phases.lock().unwrap();
+        let transitions = phases.entry(device_id.to_string()).or_default();
+        if transitions.last().is_none_or(|last| last.phase != phase) {
+            transitions.push(PhaseTransition {
+                phase: phase.to_string(),
+                at_ms,
+            });
+        }
+    }
+
+    /// Phase transitions of the device's wipe, clearing them for its next wipe
+    fn take_phases(&self, device_id: &str) -> Vec<PhaseTransition> {
+        self.phases.lock().unwrap().remove(device_id).unwrap_or_default()
+    }
+
+    /// Passes the device's wipe finished, resetting the count for its next wipe
+    fn take_passes_completed(&self, device_id: &str) -> u32 {
+        self.passes_completed.lock().unwrap().remove(device_id).unwrap_or(0)
+    }
+
+    /// Latest progress for the device's active wipe; None once it has finished
+    fn current_progress(&self, device_id: &str) -> Option<WipeProgress> {
+        if self.device_id.lock().unwrap().as_deref() != Some(device_id) {
+            return None;
+        }
+        self.progress.lock().unwrap().get(device_id).cloned()
+    }
+
+    fn clear_progress(&self, device_id: &str) {
+        self.progress.lock().unwrap().remove(device_id);
+    }
+
+    fn add_snapshot(&self, device_id: &str, snapshot: StorageSnapshot) {
+        let mut held = self.snapshots.lock().unwrap();
+        held.entry(device_id.to_string()).or_default().push(snapshot);
+    }
+
+    /// Snapshots taken since the device's last session, for the session being recorded
+    fn take_snapshots(&self, device_id: &str) -> Vec<StorageSnapshot> {
+        self.snapshots.lock().unwrap().remove(device_id).unwrap_or_default()
+    }
+
+    /// Remember which device an id belongs to as its workflow (wipe, then reset) starts
+    fn start_workflow(&self, device_id: &str, serial: &str) {
+        let mut serials = self.workflow_serials.lock().unwrap();
+        serials.insert(device_id.to_string(), serial.to_string());
+    }
+
+    /// Forget the id's workflow, e.g. when the device is detected afresh
+    fn end_workflow(&self, device_id: &str) {
+        self.workflow_serials.lock().unwrap().remove(device_id);
+    }
+
+    /// Before a destructive step, confirm the id still belongs to the device its workflow
+    /// started on. Ids with no workflow in progress pass.
+    fn guard_identity(&self, runner: &dyn CommandRunner, device_id: &str) -> Result<(), String> {
+        let expected = self.workflow_serials.lock().unwrap().get(device_id).cloned();
+        match expected {
+            Some(serial) => check_device_identity_with(runner, device_id, &serial),
+            None => Ok(()),
+        }
+    }
 }
 
 // ============================================================================
@@ -31,6 +218,17 @@ pub struct DeviceInfo {
     pub model: String,
     pub brand: String,
     pub android_version: String,
+    #[serde(default)]
+    pub usb_speed: Option<String>, // Negotiated link speed, e.g. "USB 2.0 High Speed (480 Mbps)"
+    /// A/B (seamless update) partition layout. These devices have no separate recovery
+    /// partition (recovery lives in the boot image), so "reboot recovery" can land on
+    /// the bootloader or the inactive slot on some builds; prefer the Settings reset.
+    #[serde(default)]
+    pub ab_device: bool,
+    #[serde(default)]
+    pub bootloader: Option<String>, // ro.bootloader
+    #[serde(default)]
+    pub baseband: Option<String>, // gsm.version.baseband; None on devices without a modem
 }
 
 /// Storage information from device
@@ -40,6 +238,54 @@ pub struct StorageInfo {
     pub used_mb: u64,
     pub available_mb: u64,
     pub percent_used: u8,
+    #[serde(default)]
+    pub physical_capacity_bytes: Option<u64>, // Whole flash device; None if unreadable
+    #[serde(default)]
+    pub fs_type: Option<String>, // Filesystem holding the data ("ext4", "f2fs", ...)
+    #[serde(default)]
+    pub fs_caveat: Option<String>, // What the filesystem means for overwrite-based wiping
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statfs_check: Option<StorageCrossCheck>, // Only when get_storage_info is asked for it
+}
+
+/// Whether a physical SD card is inserted, as opposed to the emulated internal storage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SdCardInfo {
+    pub present: bool,
+    pub mounted: bool,
+    pub path: Option<String>,      // e.g. "/storage/1234-ABCD"; None unless mounted
+    pub volume_id: Option<String>, // e.g. "public:179,1"; None when found via /storage
+}
+
+/// A wipe entering a progress phase ("starting", "writing", "verifying", "cleanup", ...)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhaseTransition {
+    pub phase: String,
+    pub at_ms: u64, // Unix milliseconds
+}
+
+/// Time a wipe spent in one phase, summed over every time it entered it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhaseDuration {
+    pub phase: String,
+    pub duration_ms: u64,
+}
+
+/// Storage as it was at one point of a wipe, for the before/after comparison in reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageSnapshot {
+    pub label: String, // "before", "during", "after", or the caller's own
+    pub taken_at: u64, // Unix seconds
+    pub storage: StorageInfo,
+}
+
+/// The same filesystem as seen by statfs (`stat -f`), reconciled with df
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageCrossCheck {
+    pub statfs_total_mb: u64,
+    pub statfs_available_mb: u64,
+    pub discrepancy: bool, // df and statfs disagree by more than the tolerance
+    pub detail: String,
 }
 
 /// Progress event emitted during wipe operations
@@ -51,6 +297,10 @@ pub struct WipeProgress {
     pub bytes_written: u64,
     pub message: String,
     pub phase: String, // "writing", "verifying", "cleanup"
+    #[serde(default)]
+    pub rate: Option<f32>, // Overall percent per second while writing, for UI interpolation
+    #[serde(default)]
+    pub estimated: bool, // Interpolated between script updates rather than reported by it
 }
 
 /// Wipe configuration from frontend
@@ -58,8 +308,139 @@ pub struct WipeProgress {
 pub struct WipeConfig {
     pub mode: String,         // "quick" or "full"
     pub passes: u32,          // Number of passes (1-20)
-    pub size_mb: Option<u32>, // Chunk size for quick mode (64-10240)
+    pub size_mb: Option<u32>, // Chunk size for quick mode (see ChunkSizeBounds)
     pub double_reset: bool,   // Enable double factory reset
+    pub expect_serial: Option<String>, // Refuse to wipe unless the device serial matches
+    pub expect_model: Option<String>,  // Refuse to wipe unless the device model matches
+    #[serde(default)]
+    pub max_duration_secs: u64, // Abort the wipe after this long (0 = unlimited)
+    #[serde(default)]
+    pub keep_temp_files: bool, // Leave the last pass's data on the device (not on abort)
+    #[serde(default)]
+    pub pattern: Option<String>, // "random", the only one the scripts write (default)
+    #[serde(default)]
+    pub emulator_test: bool, // Bounded single-pass wipe, only allowed on an emulator
+    #[serde(default)]
+    pub skip_factory_reset: bool, // Data-only wipe: overwrite free space, never reset
+    /// Clear every app's cache and `pm clear` every user-installed app before overwriting.
+    /// The overwrite only reaches free space, so this is what removes allocated app data
+    /// ahead of the reset. Irreversible, and apps that refuse (device admins) keep theirs.
+    #[serde(default)]
+    pub clear_app_data: bool,
+}
+
+/// Chunk written by an emulator test wipe: the smallest quick-mode size, so a CI run
+/// exercises every stage without filling the emulator's data image
+const EMULATOR_TEST_SIZE_MB: u32 = 64;
+
+/// Range quick-mode chunk sizes are clamped to, set in settings. A nearly full device can
+/// need a chunk under 64MB, since a chunk larger than the free space fails its pass;
+/// smaller chunks overwrite less per pass, so they mean more passes for the same coverage.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ChunkSizeBounds {
+    pub min_mb: u32,
+    pub max_mb: u32,
+}
+
+impl Default for ChunkSizeBounds {
+    fn default() -> Self {
+        Self {
+            min_mb: 64,
+            max_mb: 10240,
+        }
+    }
+}
+
+impl ChunkSizeBounds {
+    /// What settings may widen the bounds to: 1MB is the smallest chunk dd writes, and
+    /// 64GB is more free space than the largest phones have
+    const LIMITS: ChunkSizeBounds = ChunkSizeBounds {
+        min_mb: 1,
+        max_mb: 65536,
+    };
+
+    fn validate(self) -> Result<ChunkSizeBounds, String> {
+        let limits = Self::LIMITS;
+        if self.min_mb < limits.min_mb || self.max_mb > limits.max_mb || self.min_mb > self.max_mb
+        {
+            return Err(format!(
+                "Invalid chunk size bounds {}-{}MB. They must satisfy {} <= min <= max <= {}.",
+                self.min_mb, self.max_mb, limits.min_mb, limits.max_mb
+            ));
+        }
+        Ok(self)
+    }
+}
+
+impl WipeConfig {
+    /// Reject incoherent combinations and clamp the rest into the supported ranges
+    fn validate_and_clamp(&self, chunk: ChunkSizeBounds) -> Result<WipeConfig, String> {
+        let mut config = self.clone();
+
+        // Test mode overrides whatever else was asked for: one bounded quick pass
+        if config.emulator_test {
+            config.mode = "quick".to_string();
+            config.passes = 1;
+            config.size_mb = Some(EMULATOR_TEST_SIZE_MB);
+            config.pattern = Some("random".to_string());
+            config.double_reset = false;
+            config.keep_temp_files = false;
+            config.clear_app_data = false;
+            config.skip_factory_reset = true;
+            return Ok(config);
+        }
+
+        if config.mode != "quick" && config.mode != "full" {
+            return Err("Invalid wipe mode. Must be 'quick' or 'full'.".to_string());
+        }
+        if config.skip_factory_reset && config.double_reset {
+            return Err("A data-only wipe skips the factory reset, so it can't also use \
+                        double reset."
+                .to_string());
+        }
+
+        let pattern = config.pattern.as_deref().unwrap_or("random").to_lowercase();
+        if pattern != "random" {
+            return Err(format!("Unknown wipe pattern '{}'. Must be 'random'.", pattern));
+        }
+        config.pattern = Some(pattern);
+        config.passes = config.passes.clamp(1, 20);
+
+        // Quick mode writes a fixed-size chunk; full mode fills free space, so a size
+        // there would be silently ignored
+        config.size_mb = if config.mode == "quick" {
+            Some(config.size_mb.unwrap_or(1024).clamp(chunk.min_mb, chunk.max_mb))
+        } else {
+            None
+        };
+
+        Ok(config)
+    }
+}
+
+/// True for the adb ids the emulator registers as ("emulator-5554", ...)
+fn is_emulator_id(device_id: &str) -> bool {
+    device_id
+        .strip_prefix("emulator-")
+        .is_some_and(|port| !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Refuse an emulator test wipe unless the target is an emulator both by its adb id and
+/// by its own qemu property, so a renamed or forwarded real device is never picked
+fn check_emulator_target(device_id: &str, qemu_prop: &str) -> Result<(), String> {
+    if !is_emulator_id(device_id) {
+        return Err(format!(
+            "Emulator test mode only runs against an emulator (emulator-NNNN), not '{}'",
+            device_id
+        ));
+    }
+    if qemu_prop.trim() != "1" {
+        return Err(format!(
+            "{} does not report itself as an emulator (ro.kernel.qemu); refusing test mode",
+            device_id
+        ));
+    }
+    Ok(())
 }
 
 /// Result of an ADB command check
@@ -68,6 +449,448 @@ pub struct AdbStatus {
     pub installed: bool,
     pub version: Option<String>,
     pub devices_connected: u32,
+    #[serde(default)]
+    pub recovery_devices: Vec<String>, // Connected but booted into recovery (or sideload)
+    #[serde(default)]
+    pub server_conflict: Option<String>, // Another adb build or port in play, with guidance
+    #[serde(default)]
+    pub server_error: Option<String>, // `adb start-server` failed; the rest is still checked
+}
+
+/// Structured result of opening a factory reset screen on the device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FactoryResetResult {
+    pub intent: String,            // Intent action or component that was started
+    pub screen: String,            // Screen that opened, e.g. "Backup & Reset"
+    pub fallback: bool,            // True if only the main Settings screen could be opened
+    pub phase: String,             // "initial" or "final"
+    pub brand: String,             // Device brand/model the instructions were chosen for
+    pub model: String,
+    pub instructions: Vec<String>, // Steps from get_instructions for this device
+    pub summary: String,           // Plain-text summary for simple display
+    pub blocked: Vec<BlockedIntent>, // Intents the device refused before this one opened
+    #[serde(default)]
+    pub dry_run: bool, // Nothing was started; `intent` is what a real run would try first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resolutions: Vec<IntentResolution>, // Dry run: which reset intents resolve
+}
+
+/// Whether a reset intent resolves to an activity on the device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntentResolution {
+    pub intent: String,
+    pub resolvable: bool,
+    pub activity: Option<String>, // e.g. "com.android.settings/.Settings$PrivacySettingsActivity"
+}
+
+/// What poll_reset_started saw after the reset screen was opened
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResetStartCheck {
+    pub confirmed: bool, // Rebooted and came back reset (setup wizard, or adb keys gone)
+    pub rebooted: bool,  // Dropped off adb at some point
+    pub message: String,
+}
+
+/// Round-trip latency of `adb shell echo`, in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PingStats {
+    pub samples: u32, // Successful round trips
+    pub failed: u32,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+}
+
+/// A remembered device that came back under a different adb id or authorization state
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceReassociation {
+    pub serial: String,
+    pub previous_id: String,
+    pub device_id: String, // Id to use from now on
+    pub authorized: bool,  // False while the USB debugging prompt is pending again
+}
+
+/// Where a device is in the reboot/reset lifecycle, as seen from the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeviceLifecycleState {
+    Online,       // Booted and usable over adb
+    Unauthorized, // Connected, waiting for the USB debugging prompt
+    Offline,      // Listed but not responding
+    Rebooting,    // Still booting or reconnecting
+    Absent,       // Not listed at all
+    Recovery,     // Recovery, sideload or rescue mode
+}
+
+/// What abort_wipe does after stopping the wipe
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbortBehavior {
+    #[default]
+    CleanupOnly, // Leave the device as-is
+    CleanupAndReset, // Also open the factory reset screen, so no partial data is left
+}
+
+/// A reset intent the device refused to open
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockedIntent {
+    pub intent: String,
+    pub blocked_reason: String, // e.g. "Permission Denial", "SecurityException"
+}
+
+/// Persisted record of a wipe run, used to generate certificates after the fact
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WipeSession {
+    pub session_id: String,
+    pub device: DeviceInfo,
+    pub serial: String, // ro.serialno, falls back to the adb id
+    pub mode: String,
+    pub passes: u32, // Requested
+    pub size_mb: Option<u32>,
+    pub started_at: u64, // Unix seconds
+    pub finished_at: u64,
+    pub success: bool,
+    #[serde(default)]
+    pub coverage: Option<CoverageEstimate>, // None if storage could not be read
+    #[serde(default)]
+    pub verification: Option<WipeVerification>, // Script-reported; None if the script failed
+    #[serde(default)]
+    pub operator: Option<OperatorInfo>, // Who ran the wipe, from settings at the time
+    #[serde(default)]
+    pub free_space_coverage: Option<FreeSpaceCoverage>, // None if storage could not be read
+    #[serde(default)]
+    pub warnings: Vec<Warning>,
+    #[serde(default)]
+    pub storage_snapshots: Vec<StorageSnapshot>, // In the order they were taken
+    #[serde(default)]
+    pub factory_reset_skipped: bool, // Data-only wipe; the device was left as it was
+    #[serde(default)]
+    pub passes_completed: Option<u32>, // Reported complete by the script; None in old records
+    #[serde(default)]
+    pub app_version: Option<String>, // None in old records
+    #[serde(default)]
+    pub script_version: Option<String>, // None if the script had no VERSION line
+    #[serde(default)]
+    pub phase_durations: Vec<PhaseDuration>, // Empty in old records
+    #[serde(default)]
+    pub batch_id: Option<String>, // Set when the wipe ran as part of a batch
+}
+
+/// Who performs wipes, for attributing audit records and certificates
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperatorInfo {
+    pub name: String,                   // Name or staff ID
+    pub case_reference: Option<String>, // Case, ticket or asset-disposal batch number
+}
+
+/// What to do when a finished wipe can't be verified
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyFailurePolicy {
+    #[default]
+    Warn, // Report it, keep the wipe as completed
+    Retry, // Run the wipe again, then fail if it still can't be verified
+    Fail,  // Mark the wipe as failed
+}
+
+/// Outcome of checking that the script confirmed every pass it was asked for. This is
+/// what the script reported, not a read-back of the device.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WipeVerification {
+    pub passed: bool,
+    pub passes_confirmed: u32,
+    pub policy: VerifyFailurePolicy,
+    pub attempts: u32, // Script runs, including retries
+    pub detail: String,
+}
+
+/// How much of the storage the overwrite reached, in bytes
+///
+/// Wear leveling and over-provisioning hide part of the flash from the filesystem, and
+/// space held by existing files is never overwritten, so a logical overwrite can't reach
+/// everything. This puts a number on that gap instead of leaving it as a disclaimer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoverageEstimate {
+    pub capacity_bytes: u64,    // Physical storage size if known, else the data partition
+    pub overwritten_bytes: u64, // Largest amount written in a single pass
+    pub unreachable_bytes: u64, // capacity - overwritten
+    pub unreachable_percent: f32,
+}
+
+/// Kind of non-fatal problem attached to a result, for the UI's warnings panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningCode {
+    LowBattery,         // Running on battery below the warning level; the wipe carried on
+    EncryptionAdvisory, // Extra passes add little on this device's encryption
+    SlowEntropy,        // /dev/urandom limits the write rate
+    PartialCoverage,    // Part of the free space wasn't overwritten
+    VerificationFailed, // Kept despite failing verification (policy: warn)
+    WearLeveling,       // Overwrites can't reach blocks hidden by wear leveling
+    AppDataRemains,     // clear_app_data couldn't clear some (or any) apps
+}
+
+/// A non-fatal problem: the operation went ahead, but the user should know
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Warning {
+    pub code: WarningCode,
+    pub message: String,
+}
+
+impl Warning {
+    fn new(code: WarningCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// How much of the free space measured before the wipe was overwritten
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FreeSpaceCoverage {
+    pub free_before_bytes: u64,
+    pub overwritten_bytes: u64, // Largest amount written in a single pass
+    pub covered_percent: f32,
+    pub full_coverage: bool, // The whole free region was overwritten by a completed wipe
+    pub partial_reason: Option<String>,
+}
+
+/// Certificate of data sanitization derived from a wipe session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WipeCertificate {
+    pub session_id: String,
+    pub device_brand: String,
+    pub device_model: String,
+    pub device_serial: String,
+    pub android_version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_bootloader: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_baseband: Option<String>,
+    pub started_at: u64,
+    pub completed_at: u64,
+    pub method: String,
+    pub nist_level: String,
+    pub result: String, // "success" or "failed"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passes_requested: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passes_completed: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<CoverageEstimate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<WipeVerification>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub free_space_coverage: Option<FreeSpaceCoverage>,
+    pub operator: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub case_reference: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub storage_snapshots: Vec<StorageSnapshot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub phase_durations: Vec<PhaseDuration>,
+    pub key_id: Option<String>,     // Short id of the signing key
+    pub public_key: Option<String>, // Hex Ed25519 public key
+    pub signature: Option<String>,  // Hex Ed25519 signature over the unsigned certificate
+}
+
+/// One wipe in the exported history: only what identifies the device to its owner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub session_id: String,
+    pub started: String,  // UTC
+    pub finished: String, // UTC
+    pub brand: String,
+    pub model: String,
+    pub serial: String,
+    pub method: String,
+    pub result: String, // "completed" or "failed"
+}
+
+/// Outcome of checking a certificate's signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateVerification {
+    pub valid: bool,
+    pub key_id: Option<String>,
+    pub known_key: bool, // Signed by this installation's key
+    pub reason: Option<String>,
+}
+
+/// User-configurable settings, persisted as JSON in the app data directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub audit_log_max_bytes: u64, // Size at which the audit log is rotated
+    pub command_trace: bool,      // Emit a command-trace event for every adb/bash invocation
+    pub battery_poll_secs: u64,   // Battery check interval during a wipe (0 = off)
+    pub battery_warn_percent: u8, // Warn when unplugged and below this level
+    pub kill_adb_server_on_exit: bool, // Opt-in: stop the adb server on exit if this app started it
+    pub on_verify_failure: VerifyFailurePolicy, // What a wipe that can't be verified does
+    pub low_priority_wipe: bool, // Run the wipe script at reduced host CPU/IO priority
+    pub event_websocket: bool,   // Rebroadcast wipe events to websocket clients
+    pub event_websocket_addr: String, // Listen address; loopback unless deliberately changed
+    pub operator: Option<OperatorInfo>, // Recorded with every wipe when set
+    pub require_typed_confirmation: bool, // Wipe/reset need the model or serial typed back
+    pub quick_chunk_bounds: ChunkSizeBounds, // Clamp for quick-mode chunk sizes
+    pub long_device_listing: bool, // List devices with `adb devices -l`, skipping model getprops
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            audit_log_max_bytes: DEFAULT_AUDIT_LOG_MAX_BYTES,
+            command_trace: false,
+            battery_poll_secs: 60,
+            battery_warn_percent: 30,
+            kill_adb_server_on_exit: false,
+            on_verify_failure: VerifyFailurePolicy::Warn,
+            low_priority_wipe: false,
+            event_websocket: false,
+            event_websocket_addr: DEFAULT_EVENT_WEBSOCKET_ADDR.to_string(),
+            operator: None,
+            require_typed_confirmation: false,
+            quick_chunk_bounds: ChunkSizeBounds::default(),
+            long_device_listing: false,
+        }
+    }
+}
+
+/// Battery state from `dumpsys battery`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatteryStatus {
+    pub level: u8, // Percent
+    pub charging: bool,
+    pub power_source: Option<String>, // "AC", "USB", "Wireless" or "Dock" when plugged in
+}
+
+/// Single audit log record, stored as one JSON object per line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: u64, // Unix seconds
+    pub event: String,  // "wipe-started", "wipe-completed", "wipe-aborted", ...
+    pub device_id: Option<String>,
+    pub details: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>, // Build that wrote the entry; None in old logs
+}
+
+/// A host command about to be run, emitted as a `command-trace` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandTrace {
+    pub program: String,
+    pub args: Vec<String>,
+    pub timestamp: u64,
+}
+
+/// One step of prepare_device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrepareStep {
+    pub name: String, // "authorization", "keep_awake", "screen_timeout", "temp_dir", "free_space"
+    pub ok: bool,
+    pub detail: String,
+}
+
+/// Device settings prepare_device changed, with the values to put back afterwards.
+/// None means the setting wasn't changed; Some("null") means it was unset before.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceRestore {
+    pub stay_on_while_plugged_in: Option<String>,
+    pub screen_off_timeout: Option<String>,
+}
+
+/// Outcome of prepare_device; pass `restore` to restore_device once the wipe is over
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrepareReport {
+    pub ready: bool, // Every step succeeded
+    pub steps: Vec<PrepareStep>,
+    pub restore: DeviceRestore,
+}
+
+/// Verdict from probing whether a device can be wiped with adb/dd
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WipeSupport {
+    pub supported: bool,
+    pub reasons: Vec<String>, // Why not, one entry per failed probe
+}
+
+/// Flags a wipe script advertises in its `--help` output
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScriptCapabilities {
+    pub script: String,
+    pub flags: Vec<String>, // e.g. ["-d", "-p", "--passes", "--raw"]
+}
+
+impl ScriptCapabilities {
+    fn supports(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f == flag)
+    }
+}
+
+/// An account signed in on the device, from `dumpsys account`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceAccount {
+    pub name: String,
+    pub account_type: String, // e.g. "com.google"
+}
+
+/// Pre-wipe check for accounts that would trigger Factory Reset Protection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountCheck {
+    pub status: String, // "signed_in", "none" or "unknown"
+    pub accounts: Vec<DeviceAccount>,
+    pub frp_risk: bool, // A Google account is present
+    pub message: String,
+}
+
+/// How fast the device can read the wipe's entropy source, from a short timed read
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntropyRate {
+    pub source: String, // "/dev/urandom"
+    pub bytes: u64,
+    pub seconds: f64,
+    pub mb_per_sec: f64,
+    pub slow: bool, // Slow enough to limit the wipe rather than USB or flash
+    pub message: Option<String>,
+}
+
+/// Storage encryption from `ro.crypto.state` / `ro.crypto.type`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncryptionStatus {
+    pub state: String,                   // "encrypted", "unencrypted" or "unknown"
+    pub encryption_type: Option<String>, // "file" (FBE) or "block" (FDE) when encrypted
+}
+
+/// Device-side state after stopping a wipe, as checked on the device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CleanupResult {
+    pub dd_stopped: bool,   // No dd writing random data is left running
+    pub temp_removed: bool, // /sdcard/wipe_temp no longer exists
+}
+
+/// A wipe process (dd writing from /dev/urandom) running on the device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceWipeProcess {
+    pub pid: u32,
+    pub command: String,
+}
+
+/// What kill_device_wipe_processes found, signalled, and saw still running afterwards
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WipeProcessKill {
+    pub found: Vec<DeviceWipeProcess>,
+    pub killed: Vec<u32>,
+    pub remaining: Vec<DeviceWipeProcess>,
+}
+
+/// Rotated audit log archive on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogArchive {
+    pub file_name: String,
+    pub size_bytes: u64,
 }
 
 // ============================================================================
@@ -91,20 +914,185 @@ fn sanitize_device_id(device_id: &str) -> Result<String, String> {
 
 /// Parse ADB devices output into a list of device IDs
 fn parse_adb_devices(output: &str) -> Vec<(String, String)> {
+    parse_adb_device_states(output)
+        .into_iter()
+        .filter(|(_, state)| state == "device")
+        .collect()
+}
+
+/// Every listed device with its adb state ("device", "unauthorized", "recovery", ...).
+/// A device adb can't open is listed as `no permissions (<hint>); see [<url>]`, which is
+/// reported as the state "no permissions".
+fn parse_adb_device_states(output: &str) -> Vec<(String, String)> {
     output
         .lines()
         .skip(1) // Skip "List of devices attached"
         .filter_map(|line| {
             let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 && parts[1] == "device" {
-                Some((parts[0].to_string(), parts[1].to_string()))
-            } else {
-                None
+            match parts.as_slice() {
+                [id, "no", rest, ..] if rest.starts_with("permissions") => {
+                    Some((id.to_string(), "no permissions".to_string()))
+                }
+                [id, state, ..] => Some((id.to_string(), state.to_string())),
+                _ => None,
             }
         })
         .collect()
 }
 
+/// One device from `adb devices -l`, e.g.
+/// `RF123456  device usb:1-1 product:dm3q model:SM_S918U device:dm3q transport_id:3`.
+/// The key:value fields vary by adb version and transport, so each is optional.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdbDeviceListing {
+    pub id: String,
+    pub state: String,
+    pub product: Option<String>,
+    pub model: Option<String>, // adb writes spaces as underscores; restored here
+    pub device: Option<String>,
+    pub transport_id: Option<u32>,
+}
+
+/// Parse `adb devices -l` (plain `adb devices` output parses too, with no fields)
+fn parse_adb_devices_long(output: &str) -> Vec<AdbDeviceListing> {
+    output
+        .lines()
+        .skip(1) // Skip "List of devices attached"
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let (id, state, fields) = match parts.as_slice() {
+                [id, "no", rest, fields @ ..] if rest.starts_with("permissions") => {
+                    (id, "no permissions", fields)
+                }
+                [id, state, fields @ ..] => (id, *state, fields),
+                _ => return None,
+            };
+            let field = |key: &str| {
+                fields
+                    .iter()
+                    .find_map(|f| f.strip_prefix(key)?.strip_prefix(':'))
+                    .filter(|value| !value.is_empty())
+            };
+            Some(AdbDeviceListing {
+                id: id.to_string(),
+                state: state.to_string(),
+                product: field("product").map(str::to_string),
+                model: field("model").map(|m| m.replace('_', " ")),
+                device: field("device").map(str::to_string),
+                transport_id: field("transport_id").and_then(|t| t.parse().ok()),
+            })
+        })
+        .collect()
+}
+
+/// `adb devices` arguments, with `-l` when the long listing is enabled
+fn adb_devices_args(long: bool) -> &'static [&'static str] {
+    if long {
+        &["devices", "-l"]
+    } else {
+        &["devices"]
+    }
+}
+
+/// adb states of a device booted into recovery: the recovery menu, its "Apply update from
+/// ADB" sideload mode, and rescue party's recovery on Android 11+
+const RECOVERY_STATES: &[&str] = &["recovery", "sideload", "rescue"];
+
+/// Devices adb lists in a recovery state, which parse_adb_devices leaves out because
+/// nothing but the recovery fallback reset can run on them
+fn parse_recovery_devices(output: &str) -> Vec<(String, String)> {
+    parse_adb_device_states(output)
+        .into_iter()
+        .filter(|(_, state)| RECOVERY_STATES.contains(&state.as_str()))
+        .collect()
+}
+
+/// Error for when a device is connected but booted into recovery, so the user isn't told
+/// there is no device at all
+fn recovery_mode_error(listed: &[(String, String)]) -> Option<String> {
+    let (id, state) = listed
+        .iter()
+        .find(|(_, state)| RECOVERY_STATES.contains(&state.as_str()))?;
+    Some(format!(
+        "Device {} is connected in recovery mode ({}). Wiping needs Android running: choose \
+         'Reboot system now' in the recovery menu. A factory reset can still be done from \
+         recovery with 'Wipe data/factory reset'.",
+        id, state
+    ))
+}
+
+/// Error for when the only devices adb lists are ones it has no permission to open. On
+/// Linux that means missing udev rules for the phone's USB vendor id, a top support issue.
+fn no_permissions_error(listed: &[(String, String)], os: &str) -> Option<String> {
+    let (id, _) = listed.iter().find(|(_, state)| state == "no permissions")?;
+    let fix = if os == "linux" {
+        "adb can see the phone but this user isn't allowed to open it (missing udev rules).\n\
+         1. Install udev rules for Android devices (e.g. the android-udev-rules or \
+         android-sdk-platform-tools-common package)\n\
+         2. Add yourself to the plugdev group: sudo usermod -aG plugdev $USER\n\
+         3. Log out and back in, then unplug and reconnect the phone\n\
+         4. Run: adb kill-server"
+    } else {
+        "adb can see the phone but doesn't have permission to open it. Reconnect the phone, \
+         then run: adb kill-server"
+    };
+    Some(format!("Device {} found but not accessible (no permissions).\n{}", id, fix))
+}
+
+/// Collapse entries that are the same physical device (same `ro.serialno`), which some
+/// USB configurations list twice. Keeps the most usable entry: authorized over not, then
+/// USB over network. Only authorized entries can be asked for their serial; the others
+/// are keyed by the serial in their id.
+fn dedupe_devices_by_serial(
+    listed: Vec<(String, String)>,
+    serial_of: impl Fn(&str) -> String,
+) -> Vec<(String, String)> {
+    if listed.len() < 2 {
+        return listed;
+    }
+
+    let rank = |(id, state): &(String, String)| {
+        (state == "device", serial_from_device_id(id).is_some())
+    };
+    let mut kept: Vec<(String, (String, String))> = Vec::new();
+    for entry in listed {
+        let serial = if entry.1 == "device" {
+            Some(serial_of(&entry.0).trim().to_string()).filter(|s| !s.is_empty())
+        } else {
+            None
+        };
+        let serial = serial
+            .or_else(|| serial_from_device_id(&entry.0))
+            .unwrap_or_else(|| entry.0.clone());
+
+        match kept.iter_mut().find(|(s, _)| s.eq_ignore_ascii_case(&serial)) {
+            Some((_, existing)) if rank(&entry) > rank(existing) => *existing = entry,
+            Some(_) => {}
+            None => kept.push((serial, entry)),
+        }
+    }
+
+    kept.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Map an adb state (as `adb devices` or `adb get-state` report it) to a lifecycle state.
+/// `boot_completed` is only consulted for "device", which adb reports as soon as adbd is
+/// up, well before the system finishes booting.
+fn lifecycle_state(adb_state: Option<&str>, boot_completed: bool) -> DeviceLifecycleState {
+    match adb_state {
+        None => DeviceLifecycleState::Absent,
+        Some("device") if boot_completed => DeviceLifecycleState::Online,
+        Some("device") | Some("bootloader") | Some("authorizing") | Some("connecting") => {
+            DeviceLifecycleState::Rebooting
+        }
+        Some("unauthorized") | Some("no") | Some("no permissions") => {
+            DeviceLifecycleState::Unauthorized
+        }
+        Some(state) if RECOVERY_STATES.contains(&state) => DeviceLifecycleState::Recovery,
+        Some(_) => DeviceLifecycleState::Offline,
+    }
+}
+
 /// Parse df output to get storage info
 /// Note: Android df returns 1K-blocks by default (no -m flag support on some devices)
 fn parse_df_output(output: &str) -> Result<StorageInfo, String> {
@@ -133,13 +1121,328 @@ fn parse_df_output(output: &str) -> Result<StorageInfo, String> {
         used_mb: used_kb / 1024,
         available_mb: available_kb / 1024,
         percent_used,
+        physical_capacity_bytes: None,
+        fs_type: None,
+        fs_caveat: None,
+        statfs_check: None,
     })
 }
 
-/// Strip ANSI escape codes from a string
-fn strip_ansi(s: &str) -> String {
-    let mut result = String::with_capacity(s.len());
-    let mut chars = s.chars().peekable();
+/// Total and available megabytes from `stat -f` output (toybox or GNU). Block counts are
+/// in the fundamental block size where one is given.
+fn parse_stat_f_output(output: &str) -> Option<(u64, u64)> {
+    let field = |label: &str| -> Option<u64> {
+        let start = output.find(label)? + label.len();
+        output[start..].split_whitespace().next()?.parse().ok()
+    };
+    let block_size = field("Fundamental block size:").or_else(|| field("Block size:"))?;
+    let blocks_line = output.lines().find(|l| l.trim_start().starts_with("Blocks:"))?;
+    let count = |label: &str| -> Option<u64> {
+        let start = blocks_line.find(label)? + label.len();
+        blocks_line[start..].split_whitespace().next()?.parse().ok()
+    };
+    let to_mb = |blocks: u64| blocks.saturating_mul(block_size) / (1024 * 1024);
+    Some((to_mb(count("Total:")?), to_mb(count("Available:")?)))
+}
+
+/// df and statfs may differ this much (percent of the total) before it's a discrepancy;
+/// free space also moves a little between the two commands
+const STATFS_TOLERANCE_PERCENT: u64 = 2;
+
+fn reconcile_storage(
+    df: &StorageInfo,
+    statfs_total_mb: u64,
+    statfs_available_mb: u64,
+) -> StorageCrossCheck {
+    let tolerance_mb = (statfs_total_mb.max(df.total_mb) * STATFS_TOLERANCE_PERCENT / 100).max(1);
+    let mut differences = Vec::new();
+    for (what, df_mb, statfs_mb) in [
+        ("total", df.total_mb, statfs_total_mb),
+        ("available", df.available_mb, statfs_available_mb),
+    ] {
+        if df_mb.abs_diff(statfs_mb) > tolerance_mb {
+            differences.push(format!("{} {}MB (df) vs {}MB (statfs)", what, df_mb, statfs_mb));
+        }
+    }
+
+    StorageCrossCheck {
+        statfs_total_mb,
+        statfs_available_mb,
+        discrepancy: !differences.is_empty(),
+        detail: if differences.is_empty() {
+            format!("df and statfs agree within {}%.", STATFS_TOLERANCE_PERCENT)
+        } else {
+            format!("df and statfs disagree: {}.", differences.join(", "))
+        },
+    }
+}
+
+/// Total size of the physical disks in `/proc/partitions` output, in bytes
+///
+/// Only whole disks count (sda, mmcblk0, nvme0n1), not their partitions or virtual
+/// devices such as loop, zram, dm-* and ram. UFS exposes several LUNs (sda, sdb, ...)
+/// which together make up the flash chip, so they are summed. Disks holding one of the
+/// `removable` block devices (major, minor), such as an SD card, aren't internal storage.
+fn parse_proc_partitions(output: &str, removable: &[(u32, u32)]) -> Option<u64> {
+    let is_whole_disk = |name: &str| {
+        if let Some(rest) = name.strip_prefix("sd") {
+            !rest.is_empty() && rest.chars().all(|c| c.is_ascii_lowercase())
+        } else if let Some(rest) = name.strip_prefix("mmcblk") {
+            !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit())
+        } else if let Some(rest) = name.strip_prefix("nvme") {
+            rest.split_once('n').is_some_and(|(ctrl, ns)| {
+                !ctrl.is_empty()
+                    && !ns.is_empty()
+                    && ctrl.chars().chain(ns.chars()).all(|c| c.is_ascii_digit())
+            })
+        } else {
+            false
+        }
+    };
+
+    // major minor #blocks name
+    let entries: Vec<(u32, u32, u64, &str)> = output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.as_slice() {
+                [major, minor, blocks, name] => {
+                    Some((major.parse().ok()?, minor.parse().ok()?, blocks.parse().ok()?, *name))
+                }
+                _ => None,
+            }
+        })
+        .collect();
+    // A partition is named after its disk: sda1, mmcblk1p1, nvme0n1p1
+    let on_disk = |name: &str, disk: &str| {
+        name.strip_prefix(disk).is_some_and(|rest| {
+            let number = rest.strip_prefix('p').unwrap_or(rest);
+            rest.is_empty() || (!number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+        })
+    };
+    let removable_names: Vec<&str> = entries
+        .iter()
+        .filter(|(major, minor, _, _)| removable.contains(&(*major, *minor)))
+        .map(|(_, _, _, name)| *name)
+        .collect();
+
+    let total_kb: u64 = entries
+        .iter()
+        .filter(|(_, _, _, name)| is_whole_disk(name))
+        .filter(|(_, _, _, disk)| !removable_names.iter().any(|name| on_disk(name, disk)))
+        .map(|(_, _, blocks, _)| blocks)
+        .sum();
+
+    (total_kb > 0).then_some(total_kb * 1024)
+}
+
+/// Block devices (major, minor) behind the `public:<major>,<minor>` volumes in
+/// `sm list-volumes` output: SD cards and USB drives
+fn parse_public_volume_devices(output: &str) -> Vec<(u32, u32)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let id = line.split_whitespace().next()?.strip_prefix("public:")?;
+            let (major, minor) = id.split_once(',')?;
+            Some((major.parse().ok()?, minor.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Physical SD cards in `sm list-volumes` output ("<id> <state> <fs uuid>" per line).
+/// Removable media are the `public:<major>,<minor>` volumes; internal storage shows up
+/// as `private` and `emulated`. USB drives (major 8, SCSI) are public too, so skipped.
+fn parse_sm_volumes(output: &str) -> Vec<SdCardInfo> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [id, state, uuid] = parts.as_slice() else {
+                return None;
+            };
+            let major = id.strip_prefix("public:")?.split(',').next()?;
+            if major == "8" {
+                return None;
+            }
+            let mounted = *state == "mounted";
+            Some(SdCardInfo {
+                present: true,
+                mounted,
+                path: (mounted && *uuid != "null").then(|| format!("/storage/{}", uuid)),
+                volume_id: Some(id.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Fallback for devices without `sm`: a mounted card appears under /storage by its
+/// volume UUID (e.g. "1234-ABCD"), next to "emulated" and "self"
+fn parse_storage_listing(output: &str) -> Option<SdCardInfo> {
+    let is_uuid = |name: &str| {
+        name.split_once('-').is_some_and(|(a, b)| {
+            [a, b]
+                .iter()
+                .all(|part| part.len() == 4 && part.chars().all(|c| c.is_ascii_hexdigit()))
+        })
+    };
+    output.split_whitespace().find(|name| is_uuid(name)).map(|uuid| SdCardInfo {
+        present: true,
+        mounted: true,
+        path: Some(format!("/storage/{}", uuid)),
+        volume_id: None,
+    })
+}
+
+/// Physical storage size, or None where /proc/partitions isn't readable without root.
+/// An inserted SD card or USB drive is left out.
+fn read_physical_capacity(runner: &dyn CommandRunner, device_id: &str) -> Option<u64> {
+    let removable = runner
+        .adb_shell(device_id, &["sm", "list-volumes"])
+        .ok()
+        .filter(|out| out.success)
+        .map(|out| parse_public_volume_devices(&out.stdout))
+        .unwrap_or_default();
+    runner
+        .adb_shell(device_id, &["cat", "/proc/partitions"])
+        .ok()
+        .filter(|out| out.success)
+        .and_then(|out| parse_proc_partitions(&out.stdout, &removable))
+}
+
+/// Version of this build, recorded in audit entries, sessions and certificates
+const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The `VERSION="x.y.z"` a wipe script declares near its top
+fn parse_script_version(source: &str) -> Option<String> {
+    source
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("VERSION="))
+        .map(|value| value.trim_matches(['"', '\'']).to_string())
+        .filter(|version| !version.is_empty())
+}
+
+/// Collect the flags from a script's `--help` option list ("  -p, --passes N    ...")
+fn parse_script_help(script: &str, help: &str) -> ScriptCapabilities {
+    let mut flags: Vec<String> = Vec::new();
+
+    for line in help.lines().map(str::trim_start) {
+        if !line.starts_with('-') {
+            continue;
+        }
+        // The option column ends at the first run of two spaces
+        let options = line.split("  ").next().unwrap_or_default();
+        for flag in options.split([',', ' ']).filter(|t| t.starts_with('-')) {
+            if !flags.iter().any(|f| f == flag) {
+                flags.push(flag.to_string());
+            }
+        }
+    }
+
+    ScriptCapabilities {
+        script: script.to_string(),
+        flags,
+    }
+}
+
+/// Parse the account list from `adb shell dumpsys account`
+///
+/// Returns None if the output has no "Accounts:" header, so callers can tell
+/// "no accounts" apart from "couldn't read accounts".
+fn parse_dumpsys_accounts(output: &str) -> Option<Vec<DeviceAccount>> {
+    let mut lines = output.lines().map(str::trim);
+    let count: usize = lines
+        .find_map(|l| l.strip_prefix("Accounts:"))
+        .and_then(|n| n.trim().parse().ok())?;
+
+    // "Account {name=user@gmail.com, type=com.google}"
+    let accounts: Vec<DeviceAccount> = lines
+        .filter_map(|l| l.strip_prefix("Account {")?.strip_suffix('}'))
+        .filter_map(|fields| {
+            let (name, account_type) = fields.rsplit_once(", type=")?;
+            Some(DeviceAccount {
+                name: name.strip_prefix("name=")?.to_string(),
+                account_type: account_type.to_string(),
+            })
+        })
+        .take(count)
+        .collect();
+
+    Some(accounts)
+}
+
+/// Turn the parsed account list into a user-facing FRP verdict
+fn build_account_check(accounts: Option<Vec<DeviceAccount>>) -> AccountCheck {
+    let Some(accounts) = accounts else {
+        return AccountCheck {
+            status: "unknown".to_string(),
+            accounts: Vec::new(),
+            frp_risk: false,
+            message: "Could not read accounts on the device. Make sure all accounts are removed \
+                      before wiping to avoid Factory Reset Protection."
+                .to_string(),
+        };
+    };
+
+    let frp_risk = accounts.iter().any(|a| a.account_type == "com.google");
+    let message = if accounts.is_empty() {
+        "No accounts signed in.".to_string()
+    } else if frp_risk {
+        "A Google account is still signed in. Remove it in Settings > Accounts before wiping, \
+         or the next owner will be locked out by Factory Reset Protection."
+            .to_string()
+    } else {
+        format!(
+            "{} account(s) still signed in. Remove them in Settings > Accounts before wiping.",
+            accounts.len()
+        )
+    };
+
+    AccountCheck {
+        status: if accounts.is_empty() { "none" } else { "signed_in" }.to_string(),
+        accounts,
+        frp_risk,
+        message,
+    }
+}
+
+/// Parse `adb shell dumpsys battery` output
+fn parse_battery_status(output: &str) -> Result<BatteryStatus, String> {
+    let mut level = None;
+    let mut scale = 100u32;
+    let mut status = 0u32;
+    let mut power_source = None;
+
+    for line in output.lines() {
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key {
+            "level" => level = value.parse::<u32>().ok(),
+            "scale" => scale = value.parse().unwrap_or(100).max(1),
+            "status" => status = value.parse().unwrap_or(0),
+            "AC powered" | "USB powered" | "Wireless powered" | "Dock powered"
+                if value == "true" && power_source.is_none() =>
+            {
+                power_source = key.strip_suffix(" powered").map(|s| s.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    let level = level.ok_or("Unexpected dumpsys battery output format")?;
+    // BatteryManager status: 2 = charging, 5 = full
+    Ok(BatteryStatus {
+        level: (level * 100 / scale).min(100) as u8,
+        charging: power_source.is_some() || status == 2 || status == 5,
+        power_source,
+    })
+}
+
+/// Strip ANSI escape codes from a string
+fn strip_ansi(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
 
     while let Some(c) = chars.next() {
         if c == '\x1b' {
@@ -160,27 +1463,86 @@ fn strip_ansi(s: &str) -> String {
     result
 }
 
+/// Final summary lines; the capture is the number of passes done
+const PASSES_FINISHED_PATTERNS: &[&str] = &[
+    r"(?i)passes\s+completed\s*:\s*(\d+)",         // "Passes completed: 3"
+    r"(?i)all\s+(\d+)\s+passes\s+(?:finished|done)", // "WIPE_COMPLETE: All 3 passes finished"
+];
+
+/// Lines about a single pass; the capture is the pass number
+const PASS_NUMBER_PATTERNS: &[&str] = &[
+    r"(?i)\bpass\s*#?\s*(\d+)", // "Pass 2", "PASS #2" (not "Passes")
+];
+
+/// Words marking a pass as finished
+const PASS_DONE_PATTERNS: &[&str] = &[r"(?i)\b(?:complete|completed|done|finished)\b"];
+
+/// Temp file removal, per pass ("Cleaning up pass 2...", "Deleting pass 2 data...") or
+/// the final sweep ("Removing temporary files..."). Not the interrupt trap's message.
+const CLEANUP_PATTERNS: &[&str] = &[
+    r"(?i)\b(?:cleaning\s+up|deleting)\s+pass\b",
+    r"(?i)\bremoving\s+temp(?:orary)?\s+files\b",
+];
+
+/// Quick mode's step-by-step fill, "Pass 1: chunk 3 of 16, 192MB written, 51200KB free";
+/// the captures are the step and the step count
+const CHUNK_PATTERNS: &[&str] = &[r"(?i)\bchunk\s+(\d+)\s+of\s+(\d+)"];
+
+/// Within-pass percentage, accepting "50%", "50 %" and "12,5%"
+const PERCENT_PATTERNS: &[&str] = &[r"(\d+(?:[.,]\d+)?)\s*%"];
+
+/// Compiled progress patterns. New wordings (or translations) of the script output
+/// only need another entry in the lists above.
+struct ProgressPatterns {
+    passes_finished: Vec<Regex>,
+    pass_number: Vec<Regex>,
+    pass_done: Vec<Regex>,
+    cleanup: Vec<Regex>,
+    chunk: Vec<Regex>,
+    percent: Vec<Regex>,
+}
+
+fn progress_patterns() -> &'static ProgressPatterns {
+    static PATTERNS: OnceLock<ProgressPatterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        let compile = |patterns: &[&str]| -> Vec<Regex> {
+            patterns.iter().map(|p| Regex::new(p).expect("valid progress pattern")).collect()
+        };
+        ProgressPatterns {
+            passes_finished: compile(PASSES_FINISHED_PATTERNS),
+            pass_number: compile(PASS_NUMBER_PATTERNS),
+            pass_done: compile(PASS_DONE_PATTERNS),
+            cleanup: compile(CLEANUP_PATTERNS),
+            chunk: compile(CHUNK_PATTERNS),
+            percent: compile(PERCENT_PATTERNS),
+        }
+    })
+}
+
+/// First capture of the first matching pattern, as a number
+fn capture_number<T: std::str::FromStr>(patterns: &[Regex], line: &str) -> Option<T> {
+    patterns
+        .iter()
+        .find_map(|re| re.captures(line))
+        .and_then(|caps| caps[1].replace(',', ".").parse().ok())
+}
+
 /// Parse progress from script output
 /// Script outputs:
 /// - "PROGRESS: Pass N - XMB / YMB (Z%)" (within-pass progress from full wipe)
-/// - "Pass N complete" (per-pass completion)
-/// - "Passes completed: N" (final summary)
+/// - "Pass N complete" / "PASS_COMPLETE: Pass N done" (per-pass completion)
+/// - "Passes completed: N" / "WIPE_COMPLETE: All N passes finished" (final summary)
+/// - "Cleaning up pass N..." / "Removing temporary files..." (temp file removal)
+///
+/// Matching is case-insensitive and tolerant of extra spacing; see ProgressPatterns.
 fn parse_progress_line(line: &str, total_passes: u32) -> Option<WipeProgress> {
     // Strip ANSI color codes first
     let clean_line = strip_ansi(line);
+    let patterns = progress_patterns();
 
-    // Must contain "Pass" to be a progress line
-    if !clean_line.contains("Pass") {
-        return None;
-    }
-
-    // Handle "Passes completed: N" format (final summary)
-    if clean_line.contains("Passes completed:") {
-        let pass = clean_line
-            .split("Passes completed:")
-            .nth(1)
-            .and_then(|s| s.trim().parse().ok())
-            .unwrap_or(total_passes);
+    // Final summary
+    if patterns.passes_finished.iter().any(|re| re.is_match(&clean_line)) {
+        let pass = capture_number(&patterns.passes_finished, &clean_line).unwrap_or(total_passes);
 
         return Some(WipeProgress {
             pass,
@@ -189,773 +1551,11061 @@ fn parse_progress_line(line: &str, total_passes: u32) -> Option<WipeProgress> {
             bytes_written: 0,
             message: clean_line,
             phase: "complete".to_string(),
+            rate: None,
+            estimated: false,
         });
     }
 
-    // Handle "Pass N complete" format (per-pass update)
-    // Must match "Pass " followed by a digit (not "Passes")
-    let pass: u32 = if let Some(idx) = clean_line.find("Pass ") {
-        let after_pass = &clean_line[idx + 5..]; // Skip "Pass "
-        after_pass
-            .chars()
-            .take_while(|c| c.is_numeric())
-            .collect::<String>()
-            .parse()
+    // Removing temp files happens after a pass's data is written, so the bar sits at
+    // that pass's end (100% for the final sweep) while the phase says what's going on
+    if patterns.cleanup.iter().any(|re| re.is_match(&clean_line)) {
+        let pass = capture_number(&patterns.pass_number, &clean_line)
+            .unwrap_or(total_passes)
+            .min(total_passes);
+
+        return Some(WipeProgress {
+            pass,
+            total_passes,
+            percent: (pass as f32 / total_passes as f32) * 100.0,
+            bytes_written: 0,
+            message: clean_line,
+            phase: "cleanup".to_string(),
+            rate: None,
+            estimated: false,
+        });
+    }
+
+    // Quick mode: the share of this pass's chunk written so far, i.e. of the free space
+    // it will consume
+    if let Some(caps) = patterns.chunk.iter().find_map(|re| re.captures(&clean_line)) {
+        let chunk: u32 = caps[1].parse().ok()?;
+        let chunks: u32 = caps[2].parse().ok().filter(|&n| n > 0)?;
+        let pass = capture_number(&patterns.pass_number, &clean_line)
             .unwrap_or(1)
-    } else {
-        return None; // "Passes" without space doesn't count
-    };
+            .clamp(1, total_passes);
+        let within_pass = chunk.min(chunks) as f32 / chunks as f32;
+
+        return Some(WipeProgress {
+            pass,
+            total_passes,
+            percent: ((pass - 1) as f32 + within_pass) / total_passes as f32 * 100.0,
+            bytes_written: parse_written_mb(&clean_line).unwrap_or(0) * 1024 * 1024,
+            message: clean_line,
+            phase: "writing".to_string(),
+            rate: None,
+            estimated: false,
+        });
+    }
+
+    // Everything else must name a pass number
+    let pass: u32 = capture_number(&patterns.pass_number, &clean_line)?;
+    let complete = patterns.pass_done.iter().any(|re| re.is_match(&clean_line));
 
     // Calculate overall percent based on completed passes and within-pass progress
-    let percent = if clean_line.contains("complete") {
+    let percent = if complete {
         // Pass complete: overall = (pass / total) * 100
         (pass as f32 / total_passes as f32) * 100.0
-    } else if let Some(pct_idx) = clean_line.find('%') {
-        // Within-pass progress: extract the percentage from the line
-        let start = clean_line[..pct_idx]
-            .rfind(|c: char| !c.is_numeric() && c != '.')
-            .map(|i| i + 1)
-            .unwrap_or(0);
-        let within_pass_pct: f32 = clean_line[start..pct_idx].parse().unwrap_or(0.0);
+    } else {
+        // Within-pass progress: extract the percentage from the line. A pass line with
+        // neither (e.g. "Syncing pass 1...") carries no progress.
+        let within_pass_pct: f32 = capture_number(&patterns.percent, &clean_line)?;
         // Calculate overall progress: ((completed_passes + within_pass/100) / total) * 100
         // Pass 1 at 50% with 3 passes = ((0 + 0.5) / 3) * 100 = 16.7%
         let completed_passes = pass.saturating_sub(1) as f32;
         ((completed_passes + within_pass_pct / 100.0) / total_passes as f32) * 100.0
-    } else {
-        0.0
     };
 
-    let phase = if clean_line.contains("complete") { "complete".to_string() } else { "writing".to_string() };
+    let phase = if complete { "complete".to_string() } else { "writing".to_string() };
+
+    let bytes_written = if clean_line.contains("MB") {
+        parse_written_mb(&clean_line).unwrap_or(0) * 1024 * 1024
+    } else {
+        0
+    };
 
     Some(WipeProgress {
         pass,
         total_passes,
         percent,
-        bytes_written: 0,
+        bytes_written,
         message: clean_line,
         phase,
+        rate: None,
+        estimated: false,
     })
 }
 
-// ============================================================================
-// Tauri Commands
-// ============================================================================
-
-/// Check if ADB is installed and get version info
-#[tauri::command]
-async fn check_adb_status() -> Result<AdbStatus, String> {
-    // Check if ADB is installed
-    let version_output = Command::new("adb")
-        .arg("version")
-        .output();
+/// Megabytes written so far from "PROGRESS: Pass N - XMB / YMB" or "wrote XMB" lines
+fn parse_written_mb(line: &str) -> Option<u64> {
+    let mb_idx = line.find("MB")?;
+    let start = line[..mb_idx]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    line[start..mb_idx].parse().ok()
+}
 
-    match version_output {
-        Ok(output) if output.status.success() => {
-            let version_str = String::from_utf8_lossy(&output.stdout);
-            let version = version_str
-                .lines()
-                .next()
-                .map(|s| s.to_string());
+/// Re-read the serial behind `device_id` and compare it with the one captured when the
+/// workflow started (which, like that capture, falls back to the id when there is none)
+fn check_device_identity_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    expected_serial: &str,
+) -> Result<(), String> {
+    let out = runner.adb_shell(device_id, &["getprop", "ro.serialno"])?;
+    if !out.success {
+        return Err(format!(
+            "Could not confirm the identity of {}: {}. Stopping before the next step.",
+            device_id,
+            out.stderr.trim()
+        ));
+    }
+    let serial = match out.stdout.trim() {
+        "" => device_id,
+        serial => serial,
+    };
+    if !serial.eq_ignore_ascii_case(expected_serial.trim()) {
+        return Err(format!(
+            "Device identity changed: {} was serial {} when this wipe started but is now {}. \
+             Stopping before anything else is done to it; check the connection and start \
+             over.",
+            device_id,
+            expected_serial.trim(),
+            serial
+        ));
+    }
+    Ok(())
+}
 
-            // Count connected devices
-            let devices_output = Command::new("adb")
-                .arg("devices")
-                .output()
-                .map_err(|e| format!("Failed to list devices: {}", e))?;
+/// Safety interlock: check the connected device against the identity the operator expects
+fn check_expected_identity(
+    config: &WipeConfig,
+    device: &DeviceInfo,
+    serial: &str,
+) -> Result<(), String> {
+    let matches =
+        |expected: &str, actual: &str| expected.trim().eq_ignore_ascii_case(actual.trim());
 
-            let devices_str = String::from_utf8_lossy(&devices_output.stdout);
-            let devices = parse_adb_devices(&devices_str);
+    if let Some(expected) = config.expect_serial.as_deref() {
+        if !matches(expected, serial) && !matches(expected, &device.id) {
+            return Err(format!(
+                "Device mismatch: expected serial {} but {} reports {}. Wipe not started.",
+                expected.trim(),
+                device.id,
+                if serial.is_empty() { "no serial" } else { serial }
+            ));
+        }
+    }
 
-            Ok(AdbStatus {
-                installed: true,
-                version,
-                devices_connected: devices.len() as u32,
-            })
+    if let Some(expected) = config.expect_model.as_deref() {
+        if !matches(expected, &device.model) {
+            return Err(format!(
+                "Device mismatch: expected model {} but {} is a {}. Wipe not started.",
+                expected.trim(),
+                device.id,
+                if device.model.is_empty() { "device of unknown model" } else { &device.model }
+            ));
         }
-        _ => Ok(AdbStatus {
-            installed: false,
-            version: None,
-            devices_connected: 0,
-        }),
     }
+
+    Ok(())
 }
 
-/// Check for connected devices and return device info
-#[tauri::command]
-async fn check_adb() -> Result<DeviceInfo, String> {
-    // Run `adb devices` to list connected devices
-    let output = Command::new("adb")
-        .arg("devices")
-        .output()
-        .map_err(|e| format!("Failed to run ADB: {}. Is ADB installed?", e))?;
+/// Estimate how much of the storage a wipe could not reach by overwriting
+fn estimate_coverage(capacity_bytes: u64, overwritten_bytes: u64) -> CoverageEstimate {
+    let overwritten_bytes = overwritten_bytes.min(capacity_bytes);
+    let unreachable_bytes = capacity_bytes - overwritten_bytes;
+    let unreachable_percent = if capacity_bytes == 0 {
+        0.0
+    } else {
+        (unreachable_bytes as f64 / capacity_bytes as f64 * 100.0) as f32
+    };
 
-    if !output.status.success() {
-        return Err("ADB command failed. Please check ADB installation.".to_string());
+    CoverageEstimate {
+        capacity_bytes,
+        overwritten_bytes,
+        unreachable_bytes,
+        unreachable_percent,
     }
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let devices = parse_adb_devices(&stdout);
+/// Share of the pre-wipe free space that counts as all of it. full_wipe.sh fills to 95%
+/// and leaves the rest so the filesystem never runs completely out; one more point
+/// allows for the MB rounding in the script's figures.
+const FULL_COVERAGE_MIN_PERCENT: f32 = 94.0;
 
-    if devices.is_empty() {
-        return Err(
-            "No device connected. Please:\n\
-             1. Connect your Android device via USB\n\
-             2. Enable USB Debugging in Developer Options\n\
-             3. Authorize this computer on your phone"
-                .to_string(),
-        );
+/// Compare what a wipe wrote with the free space before it started. Only a completed
+/// wipe can claim full coverage; an aborted or failed one is partial whatever it wrote.
+fn free_space_coverage(
+    free_before_bytes: u64,
+    overwritten_bytes: u64,
+    completed: bool,
+) -> FreeSpaceCoverage {
+    const MB: u64 = 1024 * 1024;
+    let covered_percent = if free_before_bytes == 0 {
+        100.0
+    } else {
+        (overwritten_bytes.min(free_before_bytes) as f64 / free_before_bytes as f64 * 100.0) as f32
+    };
+    let partial_reason = if !completed {
+        Some("The wipe did not complete (aborted, timed out or failed).".to_string())
+    } else if covered_percent < FULL_COVERAGE_MIN_PERCENT {
+        Some(format!(
+            "Only {}MB of the {}MB free before the wipe was overwritten ({:.1}%).",
+            overwritten_bytes / MB,
+            free_before_bytes / MB,
+            covered_percent
+        ))
+    } else {
+        None
+    };
+
+    FreeSpaceCoverage {
+        free_before_bytes,
+        overwritten_bytes,
+        covered_percent,
+        full_coverage: partial_reason.is_none(),
+        partial_reason,
     }
+}
 
-    // Use first connected device
-    let device_id = &devices[0].0;
+/// One-line description of a coverage estimate for summaries and certificates
+fn describe_coverage(coverage: &CoverageEstimate) -> String {
+    const MB: u64 = 1024 * 1024;
+    format!(
+        "{}MB of {}MB overwritten; {}MB ({:.1}%) unreachable by overwrite",
+        coverage.overwritten_bytes / MB,
+        coverage.capacity_bytes / MB,
+        coverage.unreachable_bytes / MB,
+        coverage.unreachable_percent
+    )
+}
 
-    // Get device properties
-    let get_prop = |prop: &str| -> String {
-        Command::new("adb")
-            .args(["-s", device_id, "shell", "getprop", prop])
-            .output()
-            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
-            .unwrap_or_default()
+/// Read model/brand/version for a device (fields are empty if the device doesn't answer)
+fn read_device_info(device_id: &str) -> DeviceInfo {
+    read_listed_device_info(device_id, None)
+}
+
+/// read_device_info, taking the model from an `adb devices -l` listing when it has one
+fn read_listed_device_info(device_id: &str, listing: Option<&AdbDeviceListing>) -> DeviceInfo {
+    // One getprop dump instead of a round-trip per property; one at a time if it fails
+    let props = read_device_props(device_id);
+    let prop = |name: &str| {
+        if props.is_empty() {
+            get_device_prop(device_id, name)
+        } else {
+            props.get(name).cloned().unwrap_or_default()
+        }
     };
+    let model = listing.and_then(|listing| listing.model.clone());
+    DeviceInfo {
+        id: device_id.to_string(),
+        model: model.unwrap_or_else(|| prop("ro.product.model")),
+        brand: prop("ro.product.brand"),
+        android_version: prop("ro.build.version.release"),
+        usb_speed: read_usb_speed(device_id),
+        ab_device: is_ab_device(&prop("ro.boot.slot_suffix"), &prop("ro.build.ab_update")),
+        bootloader: firmware_version(&prop("ro.bootloader")),
+        baseband: firmware_version(&prop("gsm.version.baseband")),
+    }
+}
 
-    let model = get_prop("ro.product.model");
-    let brand = get_prop("ro.product.brand");
-    let android_version = get_prop("ro.build.version.release");
+/// Every system property of the device, from a single `getprop` (empty if it fails)
+fn read_device_props(device_id: &str) -> HashMap<String, String> {
+    adb_shell(device_id, &["getprop"])
+        .traced()
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| parse_getprop(&String::from_utf8_lossy(&o.stdout)))
+        .unwrap_or_default()
+}
 
-    if model.is_empty() {
-        return Err("Connected device not responding. Please unlock your phone and try again.".to_string());
+/// Parse `getprop` output: one `[name]: [value]` per line
+fn parse_getprop(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, value) = line.trim().split_once("]: [")?;
+            let name = name.strip_prefix('[')?;
+            let value = value.strip_suffix(']')?;
+            Some((name.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// A bootloader/baseband version, or None if the device doesn't report one. Basebands of
+/// multi-modem devices list a version per modem, usually the same one repeated.
+fn firmware_version(value: &str) -> Option<String> {
+    let mut versions: Vec<&str> = Vec::new();
+    let reported = value
+        .split(',')
+        .map(str::trim)
+        .filter(|version| !version.is_empty() && !version.eq_ignore_ascii_case("unknown"));
+    for version in reported {
+        if !versions.contains(&version) {
+            versions.push(version);
+        }
     }
+    (!versions.is_empty()).then(|| versions.join(", "))
+}
 
-    Ok(DeviceInfo {
-        id: device_id.clone(),
-        model,
-        brand,
-        android_version,
-    })
+/// A/B layout from `ro.boot.slot_suffix` ("_a"/"_b") or `ro.build.ab_update` ("true").
+/// Either can be missing on some builds, so one is enough.
+fn is_ab_device(slot_suffix: &str, ab_update: &str) -> bool {
+    let slot_suffix = slot_suffix.trim();
+    (slot_suffix.starts_with('_') && slot_suffix.len() > 1) || ab_update.trim() == "true"
 }
 
-/// Get storage information from connected device
-#[tauri::command]
-async fn get_storage_info(device_id: String) -> Result<StorageInfo, String> {
-    let device_id = sanitize_device_id(&device_id)?;
+/// Host-side sysfs directory listing USB devices (Linux)
+const HOST_USB_SYSFS: &str = "/sys/bus/usb/devices";
 
-    // Note: Don't use -m flag - not supported on all Android devices (e.g., Samsung)
-    // Default output is 1K-blocks which we convert in parse_df_output
-    let output = Command::new("adb")
-        .args(["-s", &device_id, "shell", "df", "/sdcard"])
-        .output()
-        .map_err(|e| format!("Failed to get storage info: {}", e))?;
+/// Human-readable USB speed from a sysfs value: the host's `speed` file (in Mbps) or
+/// the device's `current_speed` ("high-speed", "super-speed", ...)
+fn parse_usb_speed(value: &str) -> Option<String> {
+    let label = match value.trim() {
+        "1.5" | "low-speed" => "USB 1.0 Low Speed (1.5 Mbps)",
+        "12" | "full-speed" => "USB 1.1 Full Speed (12 Mbps)",
+        "480" | "high-speed" => "USB 2.0 High Speed (480 Mbps)",
+        "5000" | "super-speed" => "USB 3.0 SuperSpeed (5 Gbps)",
+        "10000" | "super-speed-plus" => "USB 3.1 SuperSpeed+ (10 Gbps)",
+        "20000" => "USB 3.2 SuperSpeed+ (20 Gbps)",
+        other => {
+            let mbps: f64 = other.parse().ok().filter(|m: &f64| *m > 0.0)?;
+            return Some(format!("USB ({} Mbps)", mbps));
+        }
+    };
+    Some(label.to_string())
+}
 
-    if !output.status.success() {
-        return Err("Failed to read storage info. Device may be locked.".to_string());
-    }
+/// Speed of the host USB device whose `serial` attribute matches
+fn find_usb_speed(sysfs_root: &Path, serial: &str) -> Option<String> {
+    fs::read_dir(sysfs_root)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|dir| {
+            fs::read_to_string(dir.join("serial"))
+                .map(|s| s.trim() == serial)
+                .unwrap_or(false)
+        })
+        .and_then(|dir| fs::read_to_string(dir.join("speed")).ok())
+        .and_then(|speed| parse_usb_speed(&speed))
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_df_output(&stdout)
+/// Negotiated USB speed, from the host's sysfs or else the device's USB controller.
+/// None for network connections or when neither side exposes it.
+fn read_usb_speed(device_id: &str) -> Option<String> {
+    let serial = serial_from_device_id(device_id)?;
+    find_usb_speed(Path::new(HOST_USB_SYSFS), &serial).or_else(|| {
+        SystemRunner
+            .adb_shell(device_id, &["sh", "-c", "cat /sys/class/udc/*/current_speed"])
+            .ok()
+            .filter(|out| out.success)
+            .and_then(|out| out.stdout.lines().find_map(parse_usb_speed))
+    })
 }
 
-/// Execute secure wipe operation with streaming progress
-#[tauri::command]
-async fn run_wipe(
-    window: tauri::Window,
-    state: State<'_, WipeState>,
-    device_id: String,
-    config: WipeConfig,
-) -> Result<String, String> {
-    let device_id = sanitize_device_id(&device_id)?;
+/// Validate an absolute path on the device: letters (any script), digits, spaces and plain
+/// path punctuation only, no ".." segments. Spaces and non-ASCII are quoted by shell_quote.
+fn validate_device_path(path: &str) -> Result<String, String> {
+    let valid_chars = path
+        .chars()
+        .all(|c| c.is_alphanumeric() || "/_.- ".contains(c));
 
-    // Store device ID for abort functionality
+    if !path.starts_with('/')
+        || !valid_chars
+        || path.len() > 256
+        || path.split('/').any(|segment| segment == "..")
     {
-        let mut dev_lock = state.device_id.lock().unwrap();
-        *dev_lock = Some(device_id.clone());
+        return Err(format!("Invalid device path: {}", path));
     }
 
-    // Validate inputs
-    let passes = config.passes.clamp(1, 20);
-    let size_mb = config.size_mb.map(|s| s.clamp(64, 10240)).unwrap_or(1024);
+    Ok(path.to_string())
+}
 
-    // Validate mode
-    if config.mode != "quick" && config.mode != "full" {
-        return Err("Invalid wipe mode. Must be 'quick' or 'full'.".to_string());
+/// Quote a word for the device shell. Words made only of safe characters are passed
+/// through unchanged; `*` counts as safe so globs are still expanded on the device.
+fn shell_quote(word: &str) -> String {
+    let safe = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,*".contains(c));
+    if safe {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
     }
+}
 
-    let script = if config.mode == "quick" {
-        "quick_wipe.sh"
-    } else {
-        "full_wipe.sh"
-    };
+/// Arguments for `adb -s <id> shell <command>`. adb joins everything after "shell" into
+/// one string for the device shell, so the words are quoted and joined here instead of
+/// relying on each adb version to do it consistently.
+fn adb_shell_args(device_id: &str, argv: &[&str]) -> Vec<String> {
+    let command = argv.iter().map(|w| shell_quote(w)).collect::<Vec<_>>().join(" ");
+    vec!["-s".to_string(), device_id.to_string(), "shell".to_string(), command]
+}
 
-    // Get the scripts directory path - check multiple locations
-    let exe_path = std::env::current_exe()
-        .map_err(|e| format!("Failed to get exe path: {}", e))?;
+/// `adb shell` command for a device; the caller runs it
+fn adb_shell(device_id: &str, argv: &[&str]) -> Command {
+    let mut cmd = Command::new("adb");
+    cmd.args(adb_shell_args(device_id, argv));
+    cmd
+}
 
-    let possible_paths = vec![
-        exe_path.parent().unwrap().join("scripts"),
-        exe_path.parent().unwrap().join("../Resources/scripts"),
-        std::path::PathBuf::from("scripts"),
-    ];
+/// Read a single system property from the device (empty if unavailable)
+fn get_device_prop(device_id: &str, prop: &str) -> String {
+    adb_shell(device_id, &["getprop", prop])
+        .traced()
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_default()
+}
 
-    let scripts_dir = possible_paths
-        .into_iter()
-        .find(|p| p.join(script).exists())
-        .ok_or("Scripts directory not found. Please reinstall the application.")?;
+/// Serial number encoded in an adb id, if any. USB ids are the serial itself and mDNS
+/// wireless ids look like "adb-<serial>-<suffix>._adb-tls-connect._tcp"; ip:port and
+/// emulator ids carry none.
+fn serial_from_device_id(device_id: &str) -> Option<String> {
+    if device_id.contains(':') || device_id.starts_with("emulator-") {
+        return None;
+    }
+    if let Some(rest) = device_id.strip_prefix("adb-") {
+        if device_id.contains("._adb-tls") {
+            return rest.split('-').next().map(|s| s.to_string());
+        }
+    }
+    Some(device_id.to_string())
+}
 
-    // Emit start event
-    let _ = window.emit(
-        "wipe-progress",
-        WipeProgress {
-            pass: 0,
-            total_passes: passes,
-            percent: 0.0,
-            bytes_written: 0,
-            message: format!("Starting {} wipe with {} passes...", config.mode, passes),
-            phase: "starting".to_string(),
-        },
-    );
+/// Make sure a device id is still listed by adb; if the device reconnected under a new
+/// transport id, find it again by serial number
+fn resolve_device_id(
+    device_id: &str,
+    serial: Option<&str>,
+    listed: &[(String, String)],
+    serial_of: impl Fn(&str) -> String,
+) -> Result<String, String> {
+    if listed.iter().any(|(id, _)| id == device_id) {
+        return Ok(device_id.to_string());
+    }
 
-    // Build command with sanitized arguments
-    let mut cmd = Command::new("bash");
-    cmd.current_dir(&scripts_dir)
-        .arg(script)
-        .arg("-d")
-        .arg(&device_id)
-        .arg("-p")
-        .arg(passes.to_string())
-        .arg("-y") // Auto-confirm
-        .arg("--raw") // Raw output mode for real-time streaming (no pipe buffering)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
+    let serial = serial
+        .map(|s| s.to_string())
+        .or_else(|| serial_from_device_id(device_id))
+        .ok_or_else(|| {
+            format!(
+                "Device {} is no longer connected. Reconnect it and select it again.",
+                device_id
+            )
+        })?;
 
-    if config.mode == "quick" {
-        cmd.arg("-s").arg(size_mb.to_string());
-    }
+    let matches: Vec<&String> = listed
+        .iter()
+        .map(|(id, _)| id)
+        .filter(|id| serial_of(id).trim().eq_ignore_ascii_case(serial.trim()))
+        .collect();
 
-    // Clear environment for security
-    cmd.env_clear();
-    // But we need PATH for the script to find adb
-    if let Ok(path) = std::env::var("PATH") {
-        cmd.env("PATH", path);
+    match matches.as_slice() {
+        [id] => Ok(id.to_string()),
+        [] => Err(format!(
+            "Device {} (serial {}) is no longer connected. Reconnect it and try again.",
+            device_id, serial
+        )),
+        _ => Err(format!(
+            "Several connected devices report serial {}. Disconnect the others and retry.",
+            serial
+        )),
     }
+}
 
-    let mut child = cmd
-        .spawn()
-        .map_err(|e| format!("Failed to start wipe: {}", e))?;
+/// Currently listed device id for a possibly stale one (see resolve_device_id)
+fn resolve_current_device_id(device_id: &str, serial: Option<&str>) -> Result<String, String> {
+    let output = Command::new("adb")
+        .arg("devices")
+        .traced()
+        .output()
+        .map_err(|e| format!("Failed to list devices: {}", e))?;
+    let listed = parse_adb_devices(&String::from_utf8_lossy(&output.stdout));
 
-    // Stream stdout for progress
-    if let Some(stdout) = child.stdout.take() {
-        let reader = BufReader::new(stdout);
-        let window_clone = window.clone();
+    resolve_device_id(device_id, serial, &listed, |id| get_device_prop(id, "ro.serialno"))
+}
 
-        for line in reader.lines().map_while(Result::ok) {
-            // Parse progress from line
-            if let Some(progress) = parse_progress_line(&line, passes) {
-                let _ = window_clone.emit("wipe-progress", progress);
-            }
+/// Whether `dumpsys window` shows the lock screen. None if it mentions no keyguard state,
+/// which varies between Android versions and OEM builds.
+fn parse_keyguard_locked(dumpsys_window: &str) -> Option<bool> {
+    const KEYS: [&str; 3] = ["mDreamingLockscreen=", "mKeyguardShowing=", "isKeyguardShowing="];
+    let mut found = None;
+    for token in dumpsys_window.split_whitespace() {
+        if let Some(key) = KEYS.iter().find(|key| token.starts_with(*key)) {
+            let locked = token[key.len()..].starts_with("true");
+            found = Some(found.unwrap_or(false) || locked);
         }
     }
+    found
+}
 
-    // Wait for completion
-    let status = child
-        .wait()
-        .map_err(|e| format!("Wipe process error: {}", e))?;
+/// Assemble the structured factory reset result for the screen that was opened
+fn build_factory_reset_result(
+    intent: &str,
+    screen: &str,
+    fallback: bool,
+    is_final: bool,
+    brand: String,
+    model: String,
+    blocked: Vec<BlockedIntent>,
+) -> FactoryResetResult {
+    let phase = if is_final { "final" } else { "initial" };
+    let mut summary = format!(
+        "{} opened on device ({} reset). Follow the steps to confirm the reset on your device.",
+        screen, phase
+    );
+    if fallback && !blocked.is_empty() {
+        summary.push_str(
+            " Your device blocks the automated reset screens; please navigate to reset manually.",
+        );
+    }
+    let instructions = get_instructions(brand.clone(), model.clone());
 
-    // Clear wipe state
-    {
-        let mut dev_lock = state.device_id.lock().unwrap();
-        *dev_lock = None;
+    FactoryResetResult {
+        intent: intent.to_string(),
+        screen: screen.to_string(),
+        fallback,
+        phase: phase.to_string(),
+        brand,
+        model,
+        instructions,
+        summary,
+        blocked,
+        dry_run: false,
+        resolutions: Vec::new(),
     }
+}
 
-    // Emit completion event
-    let _ = window.emit(
-        "wipe-complete",
-        serde_json::json!({
-            "success": status.success(),
-            "mode": config.mode,
-            "passes": passes
-        }),
-    );
+/// Activity a `resolve-activity --brief` call resolved to ("pkg/.Activity"), if any. The
+/// component is the last line; an unresolvable intent prints "No activity found".
+fn parse_resolved_activity(stdout: &str) -> Option<String> {
+    let last = stdout.lines().map(str::trim).rfind(|l| !l.is_empty())?;
+    (last.contains('/') && !last.contains(' ')).then(|| last.to_string())
+}
 
-    if status.success() {
-        Ok(format!(
-            "Wipe completed successfully! {} passes of {} mode.",
-            passes, config.mode
-        ))
+/// Why `am start` refused an intent, or None if the activity started
+fn intent_blocked_reason(stdout: &str, stderr: &str) -> Option<String> {
+    let output = format!("{}\n{}", stdout, stderr);
+
+    if output.contains("Permission Denial") {
+        Some("Permission Denial".to_string())
+    } else if output.contains("SecurityException") {
+        Some("SecurityException".to_string())
+    } else if output.contains("unable to resolve Intent") {
+        Some("Intent not available on this device".to_string())
     } else {
-        Err("Wipe failed. Check device connection and try again.".to_string())
+        output
+            .lines()
+            .find(|l| l.trim_start().starts_with("Error:"))
+            .map(|l| l.trim().to_string())
     }
 }
 
-/// Abort a running wipe operation
-#[tauri::command]
-async fn abort_wipe(
-    window: tauri::Window,
-    state: State<'_, WipeState>,
-) -> Result<String, String> {
-    // Get the device ID
-    let device_id = {
-        let dev_lock = state.device_id.lock().unwrap();
-        dev_lock.clone()
-    };
+/// Current time as Unix seconds
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
 
-    let device_id = match device_id {
-        Some(id) => id,
-        None => return Err("No wipe operation in progress.".to_string()),
-    };
+/// Current time as Unix milliseconds
+fn unix_timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
 
-    // Kill the wipe scripts on the host
-    let _ = Command::new("pkill")
-        .arg("-f")
-        .arg("wipe.sh")
-        .output();
+/// Format Unix seconds as "YYYY-MM-DD HH:MM:SS UTC"
+fn format_utc_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
 
-    // Kill dd process on the device
-    let _ = Command::new("adb")
-        .arg("-s")
-        .arg(&device_id)
-        .arg("shell")
-        .arg("pkill -f 'dd if=/dev/urandom'")
-        .output();
+    // Civil-from-days (Howard Hinnant's algorithm, proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
 
-    // Clean up temp files on the device
-    let _ = Command::new("adb")
-        .arg("-s")
-        .arg(&device_id)
-        .arg("shell")
-        .arg("rm -rf /sdcard/wipe_temp/")
-        .output();
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
 
-    // Clear wipe state
-    {
-        let mut dev_lock = state.device_id.lock().unwrap();
-        *dev_lock = None;
-    }
+// ============================================================================
+// Command Trace
+// ============================================================================
 
-    // Emit abort event
-    let _ = window.emit(
-        "wipe-aborted",
-        serde_json::json!({
-            "message": "Wipe operation aborted and cleaned up."
-        }),
-    );
+type TraceSink = Box<dyn Fn(CommandTrace) + Send + Sync>;
 
-    Ok("Wipe aborted. Temporary files cleaned up.".to_string())
+/// Reports each host command before it runs, when the user has turned tracing on
+///
+/// Commands are built all over this file, so the tracer is a process-wide static
+/// rather than something threaded through every helper.
+struct CommandTracer {
+    enabled: AtomicBool,
+    sink: Mutex<Option<TraceSink>>,
 }
 
-/// Trigger factory reset via ADB (opens settings screen)
-#[tauri::command]
-async fn run_factory_reset(device_id: String, is_final: bool) -> Result<String, String> {
-    let device_id = sanitize_device_id(&device_id)?;
+static COMMAND_TRACER: CommandTracer = CommandTracer::new();
 
-    // Try intents in order of specificity - some are blocked on certain devices
-    let intents = [
-        // Most direct - but often requires system permission
-        ("android.settings.MASTER_CLEAR", "Factory Reset"),
-        // Backup & Reset settings - works on some devices
-        ("android.settings.BACKUP_AND_RESET_SETTINGS", "Backup & Reset"),
-        // Privacy settings - contains reset on some devices
-        ("android.settings.PRIVACY_SETTINGS", "Privacy Settings"),
-        // Internal storage - close to reset on Samsung
-        ("android.settings.INTERNAL_STORAGE_SETTINGS", "Storage Settings"),
-    ];
+impl CommandTracer {
+    /// Off, with no sink
+    const fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            sink: Mutex::new(None),
+        }
+    }
 
-    for (intent, name) in intents {
-        let output = Command::new("adb")
-            .args(["-s", &device_id, "shell", "am", "start", "-a", intent])
-            .output();
+    fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
 
-        if let Ok(out) = output {
-            let stdout = String::from_utf8_lossy(&out.stdout);
-            let stderr = String::from_utf8_lossy(&out.stderr);
+    fn set_sink(&self, sink: TraceSink) {
+        *self.sink.lock().unwrap() = Some(sink);
+    }
 
-            // Check if it worked (no Permission Denial or Error in output)
-            if !stdout.contains("Permission Denial")
-                && !stdout.contains("Error:")
-                && !stderr.contains("Permission Denial")
-                && !stderr.contains("SecurityException")
-            {
-                let phase = if is_final { "final" } else { "initial" };
-                return Ok(format!(
-                    "{} opened on device ({} reset).\n\
-                     Navigate to: Settings > General management > Reset > Factory data reset\n\
-                     Then confirm the reset on your device.",
-                    name, phase
-                ));
-            }
+    fn trace(&self, cmd: &Command) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Some(sink) = self.sink.lock().unwrap().as_ref() {
+            sink(command_trace(cmd));
         }
     }
+}
 
-    // Fallback: just open main Settings
-    let output = Command::new("adb")
-        .args(["-s", &device_id, "shell", "am", "start", "-n", "com.android.settings/.Settings"])
-        .output()
-        .map_err(|e| format!("Failed to open settings: {}", e))?;
+/// Describe a command for the trace; the environment is never included
+fn command_trace(cmd: &Command) -> CommandTrace {
+    CommandTrace {
+        program: cmd.get_program().to_string_lossy().into_owned(),
+        args: cmd
+            .get_args()
+            .map(|a| sanitize_trace_arg(&a.to_string_lossy()))
+            .collect(),
+        timestamp: unix_timestamp(),
+    }
+}
 
-    if output.status.success() {
-        let phase = if is_final { "final" } else { "initial" };
-        Ok(format!(
-            "Settings opened on device ({} reset).\n\
-             Navigate to: General management > Reset > Factory data reset\n\
-             Then confirm the reset on your device.",
-            phase
-        ))
-    } else {
-        Err("Could not open settings. Please manually navigate to Settings > General management > Reset.".to_string())
+/// Mask the value of anything that looks like a credential ("token=...", "--password=...")
+fn sanitize_trace_arg(arg: &str) -> String {
+    const SECRET_NAMES: [&str; 4] = ["pass", "token", "secret", "key"];
+
+    match arg.split_once('=') {
+        Some((name, _)) if SECRET_NAMES.iter().any(|s| name.to_lowercase().contains(s)) => {
+            format!("{}=***", name)
+        }
+        _ => arg.to_string(),
     }
 }
 
-/// Check if device is still connected (for polling after reset)
-#[tauri::command]
-async fn check_device_connected(device_id: String) -> Result<bool, String> {
-    let device_id = sanitize_device_id(&device_id)?;
+/// `Command::new("adb").args(..).traced().output()` reports the command before running it
+trait Traced {
+    fn traced(&mut self) -> &mut Self;
+}
 
-    let output = Command::new("adb")
-        .arg("devices")
-        .output()
-        .map_err(|e| format!("Failed to check devices: {}", e))?;
+impl Traced for Command {
+    fn traced(&mut self) -> &mut Self {
+        COMMAND_TRACER.trace(self);
+        self
+    }
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let devices = parse_adb_devices(&stdout);
+// ============================================================================
+// Command Runner
+// ============================================================================
 
-    Ok(devices.iter().any(|(id, _)| id == &device_id))
+/// Captured result of a finished host command
+#[derive(Debug, Clone, Default)]
+struct CommandOutput {
+    success: bool,
+    stdout: String,
+    stderr: String,
 }
 
-/// Get device-specific factory reset instructions
-#[tauri::command]
-fn get_instructions(brand: String, model: String) -> Vec<String> {
-    let brand_lower = brand.to_lowercase();
-    let model_lower = model.to_lowercase();
+/// Runs host commands; injectable so device probes can be tested without a device
+trait CommandRunner {
+    fn run(&self, program: &str, args: &[String]) -> Result<CommandOutput, String>;
 
-    match brand_lower.as_str() {
-        "samsung" => {
-            if model_lower.contains("s24") || model_lower.contains("s25") {
-                vec![
-                    "1. Go to Settings > General management > Reset".to_string(),
-                    "2. Tap 'Factory data reset'".to_string(),
-                    "3. Scroll down and review the information".to_string(),
-                    "4. Tap 'Reset' at the bottom".to_string(),
-                    "5. Enter your PIN/password if prompted".to_string(),
-                    "6. Tap 'Delete all' to confirm".to_string(),
-                    "".to_string(),
-                    "Note: One UI may require Samsung account verification.".to_string(),
-                ]
-            } else if model_lower.contains("a55") || model_lower.contains("a54") {
-                vec![
-                    "1. Go to Settings > General management > Reset".to_string(),
-                    "2. Tap 'Factory data reset'".to_string(),
-                    "3. Review and tap 'Reset'".to_string(),
-                    "4. Enter your PIN and tap 'Delete all'".to_string(),
-                ]
-            } else {
-                vec![
-                    "1. Go to Settings > General management > Reset".to_string(),
-                    "2. Tap 'Factory data reset'".to_string(),
-                    "3. Tap 'Reset' and confirm with your PIN".to_string(),
-                    "4. Tap 'Delete all' to complete".to_string(),
-                ]
-            }
+    /// Run `adb shell` on a device (see adb_shell_args for quoting)
+    fn adb_shell(&self, device_id: &str, argv: &[&str]) -> Result<CommandOutput, String> {
+        self.run("adb", &adb_shell_args(device_id, argv))
+    }
+}
+
+/// Lets wrappers like AdbdRestartTolerant hold a borrowed runner
+impl<R: CommandRunner + ?Sized> CommandRunner for &R {
+    fn run(&self, program: &str, args: &[String]) -> Result<CommandOutput, String> {
+        (**self).run(program, args)
+    }
+}
+
+/// Runs commands for real, with command tracing
+struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, program: &str, args: &[String]) -> Result<CommandOutput, String> {
+        let output = Command::new(program)
+            .args(args)
+            .traced()
+            .output()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+        Ok(CommandOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+}
+
+/// Runs commands like SystemRunner, but kills any that outlive their own timeout or the
+/// runner's overall deadline. For best-effort work that must not hang, like exit cleanup;
+/// output is only read once the command exits, so it suits commands that print little.
+struct DeadlineRunner {
+    per_command: Duration,
+    deadline: Instant,
+}
+
+impl DeadlineRunner {
+    fn new(per_command: Duration, overall: Duration) -> Self {
+        Self {
+            per_command,
+            deadline: Instant::now() + overall,
         }
-        "google" => vec![
-            "1. Go to Settings > System > Reset options".to_string(),
-            "2. Tap 'Erase all data (factory reset)'".to_string(),
-            "3. Tap 'Erase all data' to confirm".to_string(),
-            "4. Enter your PIN if prompted".to_string(),
-            "5. Wait for the device to restart".to_string(),
-        ],
-        "oneplus" => vec![
-            "1. Go to Settings > System > Reset options".to_string(),
-            "2. Tap 'Erase all data (factory reset)'".to_string(),
-            "3. Tap 'Reset phone'".to_string(),
-            "4. Enter your PIN and confirm".to_string(),
-            "5. Device will reboot and reset".to_string(),
-        ],
-        "motorola" => {
-            if model_lower.contains("edge") {
-                vec![
-                    "1. Go to Settings > System > Reset options".to_string(),
-                    "2. Tap 'Erase all data (factory reset)'".to_string(),
-                    "3. Tap 'Erase all data'".to_string(),
-                    "4. Enter your PIN to confirm".to_string(),
-                ]
-            } else {
-                vec![
-                    "1. Go to Settings > System > Advanced > Reset options".to_string(),
-                    "2. Tap 'Erase all data (factory reset)'".to_string(),
-                    "3. Confirm and enter your PIN".to_string(),
-                ]
+    }
+}
+
+impl CommandRunner for DeadlineRunner {
+    fn run(&self, program: &str, args: &[String]) -> Result<CommandOutput, String> {
+        let budget = self
+            .per_command
+            .min(self.deadline.saturating_duration_since(Instant::now()));
+        if budget.is_zero() {
+            return Err(format!("Skipped {}: deadline passed", program));
+        }
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .traced()
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+        let started = Instant::now();
+        loop {
+            match child.try_wait() {
+                Ok(Some(_)) => break,
+                Ok(None) if started.elapsed() < budget => {
+                    std::thread::sleep(Duration::from_millis(20))
+                }
+                _ => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("{} timed out after {:?}", program, budget));
+                }
             }
         }
-        "nothing" | "cmf" => vec![
-            "1. Go to Settings > System > Reset options".to_string(),
-            "2. Tap 'Erase all data (factory reset)'".to_string(),
-            "3. Tap 'Erase all data' and confirm".to_string(),
-            "4. Enter your PIN if prompted".to_string(),
-        ],
-        _ => vec![
-            "1. Go to Settings > System (or General Management)".to_string(),
-            "2. Find 'Reset' or 'Reset options'".to_string(),
-            "3. Select 'Factory data reset' or 'Erase all data'".to_string(),
-            "4. Follow on-screen prompts to confirm".to_string(),
-            "5. Enter your PIN/password if requested".to_string(),
-            "".to_string(),
-            "Note: Steps may vary by manufacturer and Android version.".to_string(),
-        ],
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+        Ok(CommandOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
     }
 }
 
-/// Revoke ADB debugging on device (optional security step)
-#[tauri::command]
-async fn revoke_adb(device_id: String) -> Result<String, String> {
-    let device_id = sanitize_device_id(&device_id)?;
+/// Most checks any device polling loop makes, whatever its timeout. With a zero or tiny
+/// poll interval the clock alone would let a loop spin thousands of times.
+const MAX_POLL_ATTEMPTS: u32 = 600;
+
+/// Longest a caller may ask a device polling loop to wait
+const MAX_POLL_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// How often a sleeping wait checks whether it has been cancelled
+const SLEEP_SLICE: Duration = Duration::from_millis(50);
+
+/// Sleep for `duration`, waking early once `cancel` is set. False if it was cancelled.
+fn interruptible_sleep(duration: Duration, cancel: &AtomicBool) -> bool {
+    let deadline = Instant::now() + duration;
+    loop {
+        if cancel.load(Ordering::SeqCst) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        std::thread::sleep((deadline - now).min(SLEEP_SLICE));
+    }
+}
+
+/// Cancellation flags of the device waits in progress, so aborting a device ends its waits
+/// instead of leaving them polling until they time out. A wait started after the abort
+/// gets a fresh flag and is unaffected.
+struct DeviceWaits {
+    waits: Mutex<Vec<(String, Weak<AtomicBool>)>>,
+}
+
+static DEVICE_WAITS: DeviceWaits = DeviceWaits {
+    waits: Mutex::new(Vec::new()),
+};
+
+impl DeviceWaits {
+    /// A flag for a new wait on the device, live for as long as the wait holds it
+    fn register(&self, device_id: &str) -> Arc<AtomicBool> {
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut waits = self.waits.lock().unwrap();
+        waits.retain(|(_, wait)| wait.strong_count() > 0);
+        waits.push((device_id.to_string(), Arc::downgrade(&flag)));
+        flag
+    }
+
+    /// Cancel every wait in progress on the device; returns how many there were
+    fn cancel(&self, device_id: &str) -> usize {
+        let waits = self.waits.lock().unwrap();
+        waits
+            .iter()
+            .filter(|(id, _)| id == device_id)
+            .filter_map(|(_, wait)| wait.upgrade())
+            .map(|flag| flag.store(true, Ordering::SeqCst))
+            .count()
+    }
+}
+
+/// Bounds a device polling loop by both time and attempts, so a device stuck in an odd
+/// state ends the loop at its cap instead of relying on the device eventually behaving.
+/// The loop also ends early if the device's waits are cancelled.
+struct PollLimit {
+    deadline: Instant,
+    attempts: u32,
+    cancel: Arc<AtomicBool>,
+}
+
+impl PollLimit {
+    fn new(device_id: &str, timeout: Duration) -> Self {
+        Self {
+            deadline: Instant::now() + timeout.min(MAX_POLL_TIMEOUT),
+            attempts: 0,
+            cancel: DEVICE_WAITS.register(device_id),
+        }
+    }
+
+    /// Count the check just made and sleep `poll` before the next one; false once the time
+    /// or attempt cap is reached, or the wait was cancelled
+    fn next(&mut self, poll: Duration) -> bool {
+        self.attempts += 1;
+        self.attempts < MAX_POLL_ATTEMPTS
+            && Instant::now() < self.deadline
+            && interruptible_sleep(poll, &self.cancel)
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+}
+
+/// What an adb command prints when adbd goes away under it, as it does for a second or
+/// two after `adb root` or `adb unroot` restarts it
+const ADBD_RESTART_ERRORS: &[&str] = &[
+    "device offline",
+    "error: closed",
+    "protocol fault",
+    "no devices/emulators found",
+];
+
+/// How long adbd may take to come back before a failure counts as real
+const ADBD_RESTART_TIMEOUT: Duration = Duration::from_secs(10);
+const ADBD_RESTART_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether a failed adb command looks like adbd restarting rather than a real error
+fn is_adbd_restart_error(output: &CommandOutput) -> bool {
+    let text = format!("{}{}", output.stdout, output.stderr);
+    let missing_device = text.contains("device '") && text.contains("' not found");
+    !output.success && (missing_device || ADBD_RESTART_ERRORS.iter().any(|e| text.contains(e)))
+}
+
+/// The device an adb invocation targets (`adb -s <id> ...`)
+fn adb_target(args: &[String]) -> Option<&str> {
+    args.windows(2)
+        .find(|pair| pair[0] == "-s")
+        .map(|pair| pair[1].as_str())
+}
+
+/// Poll `adb get-state` until the device answers "device" again. A restart flaps the
+/// state (device, then offline or missing, then device), so anything else is waited out.
+fn wait_for_adbd(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    timeout: Duration,
+    poll: Duration,
+) -> bool {
+    let mut limit = PollLimit::new(device_id, timeout);
+    let args: Vec<String> = ["-s", device_id, "get-state"].iter().map(|a| a.to_string()).collect();
+    loop {
+        let state = runner.run("adb", &args);
+        if state.is_ok_and(|out| out.success && out.stdout.trim() == "device") {
+            return true;
+        }
+        if !limit.next(poll) {
+            return false;
+        }
+    }
+}
+
+/// Wraps a runner so an adb command that fails because adbd restarted is run once more
+/// after adbd is back, instead of surfacing a transient transport drop as an error
+struct AdbdRestartTolerant<R: CommandRunner> {
+    inner: R,
+    timeout: Duration,
+    poll: Duration,
+}
+
+impl<R: CommandRunner> AdbdRestartTolerant<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            timeout: ADBD_RESTART_TIMEOUT,
+            poll: ADBD_RESTART_POLL_INTERVAL,
+        }
+    }
+}
+
+impl<R: CommandRunner> CommandRunner for AdbdRestartTolerant<R> {
+    fn run(&self, program: &str, args: &[String]) -> Result<CommandOutput, String> {
+        let output = self.inner.run(program, args)?;
+        let device_id = match adb_target(args) {
+            Some(id) if program == "adb" && is_adbd_restart_error(&output) => id,
+            _ => return Ok(output),
+        };
+        if !wait_for_adbd(&self.inner, device_id, self.timeout, self.poll) {
+            return Ok(output);
+        }
+        self.inner.run(program, args)
+    }
+}
+
+/// Outcome of try_adb_root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdbRootResult {
+    pub root: bool,      // The shell now runs as uid 0
+    pub restarted: bool, // adbd restarted to switch to root
+    pub message: String, // What adb said, for display
+}
+
+/// Ask adbd to restart as root, wait for it to come back, then check the shell's uid.
+/// Only debuggable builds (emulators, userdebug/eng ROMs) allow it; production builds
+/// refuse and stay as they were. A root shell is what the block-level wipe needs.
+fn try_adb_root_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    timeout: Duration,
+    poll: Duration,
+) -> Result<AdbRootResult, String> {
+    let args: Vec<String> = ["-s", device_id, "root"].iter().map(|a| a.to_string()).collect();
+    let output = runner.run("adb", &args)?;
+    let message = format!("{}{}", output.stdout, output.stderr).trim().to_string();
+    let restarted = message.contains("restarting adbd as root");
+
+    if restarted && !wait_for_adbd(runner, device_id, timeout, poll) {
+        return Err(format!(
+            "{} did not come back within {}s after restarting adbd as root",
+            device_id,
+            timeout.as_secs()
+        ));
+    }
+
+    let root = AdbdRestartTolerant { inner: runner, timeout, poll }
+        .adb_shell(device_id, &["id", "-u"])
+        .is_ok_and(|out| out.success && out.stdout.trim() == "0");
+    Ok(AdbRootResult {
+        root,
+        restarted,
+        message,
+    })
+}
+
+/// Try to switch adbd to root on a debuggable build (optional; see try_adb_root_with)
+#[tauri::command]
+async fn try_adb_root(device_id: String) -> Result<AdbRootResult, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    try_adb_root_with(
+        &SystemRunner,
+        &device_id,
+        ADBD_RESTART_TIMEOUT,
+        ADBD_RESTART_POLL_INTERVAL,
+    )
+}
+
+// ============================================================================
+// ADB Server
+// ============================================================================
+
+/// Whether `adb start-server` output shows it had to launch a new daemon
+fn adb_server_was_started(output: &CommandOutput) -> bool {
+    // adb reports "* daemon started successfully" on stderr (older builds used stdout)
+    format!("{}{}", output.stdout, output.stderr).contains("daemon started successfully")
+}
+
+/// Port adb clients and servers use unless ANDROID_ADB_SERVER_PORT overrides it
+const DEFAULT_ADB_SERVER_PORT: &str = "5037";
+
+/// The ANDROID_ADB_SERVER_PORT override this app's adb runs with, if any
+fn adb_server_port_override() -> Option<String> {
+    std::env::var("ANDROID_ADB_SERVER_PORT").ok()
+}
+
+/// The protocol version from `adb version` ("Android Debug Bridge version 1.0.41" is 41),
+/// which is what a running server reports for `host:version`
+fn parse_adb_client_version(text: &str) -> Option<u32> {
+    static VERSION: OnceLock<Regex> = OnceLock::new();
+    let re = VERSION
+        .get_or_init(|| Regex::new(r"Android Debug Bridge version \d+\.\d+\.(\d+)").unwrap());
+    re.captures(text)?[1].parse().ok()
+}
+
+/// Ask the adb server listening on `port` for its version over the host protocol, without
+/// starting or replacing it. None when nothing answers there.
+fn query_adb_server_version(port: &str) -> Option<u32> {
+    use std::io::Read;
+
+    let port: u16 = port.trim().parse().ok()?;
+    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+    let timeout = Duration::from_secs(1);
+    let mut stream = std::net::TcpStream::connect_timeout(&addr, timeout).ok()?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    stream.write_all(b"000chost:version").ok()?;
+    // "OKAY", then a 4-hex-digit length, then the version as 4 hex digits
+    let mut header = [0u8; 8];
+    stream.read_exact(&mut header).ok()?;
+    if &header[..4] != b"OKAY" {
+        return None;
+    }
+    let len = usize::from_str_radix(std::str::from_utf8(&header[4..]).ok()?, 16).ok()?;
+    let mut version = vec![0u8; len];
+    stream.read_exact(&mut version).ok()?;
+    u32::from_str_radix(std::str::from_utf8(&version).ok()?, 16).ok()
+}
+
+/// The (server, client) versions from adb's "adb server version (40) doesn't match this
+/// client (41); killing..." message, printed when it replaces another build's server
+fn parse_adb_version_mismatch(text: &str) -> Option<(u32, u32)> {
+    static MISMATCH: OnceLock<Regex> = OnceLock::new();
+    let re = MISMATCH.get_or_init(|| {
+        Regex::new(r"adb server version \((\d+)\) doesn't match this client \((\d+)\)")
+            .unwrap()
+    });
+    let caps = re.captures(text)?;
+    Some((caps[1].parse().ok()?, caps[2].parse().ok()?))
+}
+
+/// A second adb in play (Android Studio and scrcpy bundle their own) that will keep
+/// fighting this app's adb over the server, from `adb start-server` output, the (server,
+/// client) versions seen just before it and the port override. Returned as a warning with
+/// guidance on aligning them.
+fn detect_adb_server_conflict(
+    output: &CommandOutput,
+    port: Option<&str>,
+    versions: Option<(u32, u32)>,
+) -> Option<String> {
+    let text = format!("{}{}", output.stdout, output.stderr);
+    let port = port.map(str::trim).filter(|p| !p.is_empty());
+    let mismatch = versions.filter(|(server, client)| server != client);
+    if let Some((server, client)) = parse_adb_version_mismatch(&text).or(mismatch) {
+        return Some(format!(
+            "The running adb server was from a different adb (version {}, this one is {}), so \
+             it was restarted. Tools that bundle their own adb, like Android Studio or \
+             scrcpy, will keep restarting it and dropping devices. Point them at the same \
+             platform-tools, or update so all adb copies match.",
+            server, client
+        ));
+    }
+    if text.contains("Address already in use") || text.contains("cannot bind") {
+        return Some(format!(
+            "Port {} is taken by another program, so the adb server couldn't listen on it. \
+             Close other adb servers (or the program holding the port) and check again.",
+            port.unwrap_or(DEFAULT_ADB_SERVER_PORT)
+        ));
+    }
+    match port {
+        Some(port) if port != DEFAULT_ADB_SERVER_PORT => Some(format!(
+            "ANDROID_ADB_SERVER_PORT is set to {}. Tools using the default port {} will start a \
+             second adb server that competes for the same devices; set the same port for all \
+             of them.",
+            port, DEFAULT_ADB_SERVER_PORT
+        )),
+        _ => None,
+    }
+}
+
+/// Tracks whether the adb server was started by this app, so exit cleanup never
+/// kills a server that the user's other tools were already using
+struct AdbServer {
+    owned: AtomicBool,
+    conflict: Mutex<Option<String>>, // From the last start; see detect_adb_server_conflict
+}
+
+impl AdbServer {
+    fn new() -> Self {
+        Self {
+            owned: AtomicBool::new(false),
+            conflict: Mutex::new(None),
+        }
+    }
+
+    /// Start the adb server on `port` (None for the default) if it isn't running and
+    /// remember if we launched it. The running server's version is compared with this
+    /// app's client first, since start-server quietly replaces a mismatched one.
+    fn ensure_started(
+        &self,
+        runner: &dyn CommandRunner,
+        port: Option<&str>,
+    ) -> Result<(), String> {
+        let client = runner
+            .run("adb", &["version".to_string()])
+            .ok()
+            .filter(|out| out.success)
+            .and_then(|out| parse_adb_client_version(&out.stdout));
+        let versions = client.and_then(|client| {
+            let server = query_adb_server_version(port.unwrap_or(DEFAULT_ADB_SERVER_PORT))?;
+            Some((server, client))
+        });
+        let output = runner.run("adb", &["start-server".to_string()])?;
+        let conflict = detect_adb_server_conflict(&output, port, versions);
+        *self.conflict.lock().unwrap() = conflict.clone();
+        if !output.success {
+            let mut error = format!("Failed to start adb server: {}", output.stderr.trim());
+            if let Some(conflict) = conflict {
+                error = format!("{} {}", error, conflict);
+            }
+            return Err(error);
+        }
+        if adb_server_was_started(&output) {
+            self.owned.store(true, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    /// Conflict seen when the server was last started, if any
+    fn conflict(&self) -> Option<String> {
+        self.conflict.lock().unwrap().clone()
+    }
+
+    fn is_owned(&self) -> bool {
+        self.owned.load(Ordering::SeqCst)
+    }
+
+    /// Kill the adb server if this app started it. Returns whether it was killed.
+    fn shutdown(&self, runner: &dyn CommandRunner) -> Result<bool, String> {
+        if !self.is_owned() {
+            return Ok(false);
+        }
+        let output = runner.run("adb", &["kill-server".to_string()])?;
+        if !output.success {
+            return Err(format!("Failed to stop adb server: {}", output.stderr.trim()));
+        }
+        self.owned.store(false, Ordering::SeqCst);
+        Ok(true)
+    }
+}
+
+// ============================================================================
+// Settings
+// ============================================================================
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Settings store backed by a JSON file
+struct SettingsState {
+    path: PathBuf,
+    settings: Mutex<AppSettings>,
+}
+
+impl SettingsState {
+    /// Load settings from the data directory, falling back to defaults
+    fn load(data_dir: &Path) -> Self {
+        let path = data_dir.join(SETTINGS_FILE);
+        let settings = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        Self {
+            path,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    fn get(&self) -> AppSettings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    fn save(&self, settings: AppSettings) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&settings)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        fs::write(&self.path, json).map_err(|e| format!("Failed to save settings: {}", e))?;
+        *self.settings.lock().unwrap() = settings;
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Audit Log
+// ============================================================================
+
+/// Default size at which the audit log is rolled to an archive (5 MB)
+const DEFAULT_AUDIT_LOG_MAX_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Smallest rotation threshold accepted from settings (4 KB)
+const MIN_AUDIT_LOG_MAX_BYTES: u64 = 4 * 1024;
+
+const AUDIT_LOG_FILE: &str = "audit.log";
+
+/// Append-only audit log (JSON lines) with size-based rotation
+struct AuditLog {
+    dir: PathBuf,
+    max_bytes: AtomicU64,
+    // Serializes appends and rotation so no entry is written mid-rename
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            dir,
+            max_bytes: AtomicU64::new(max_bytes.max(MIN_AUDIT_LOG_MAX_BYTES)),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.join(AUDIT_LOG_FILE)
+    }
+
+    fn set_max_bytes(&self, max_bytes: u64) {
+        self.max_bytes
+            .store(max_bytes.max(MIN_AUDIT_LOG_MAX_BYTES), Ordering::SeqCst);
+    }
+
+    /// Append an entry, rotating first if it would push the log past the size limit
+    fn append(&self, entry: &AuditEntry) -> Result<(), String> {
+        let mut line = serde_json::to_string(entry)
+            .map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+        line.push('\n');
+
+        let _guard = self.lock.lock().unwrap();
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create log directory: {}", e))?;
+
+        let current_size = fs::metadata(self.path()).map(|m| m.len()).unwrap_or(0);
+        let max_bytes = self.max_bytes.load(Ordering::SeqCst);
+        if current_size > 0 && current_size + line.len() as u64 > max_bytes {
+            self.rotate_locked()?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path())
+            .map_err(|e| format!("Failed to open audit log: {}", e))?;
+        file.write_all(line.as_bytes())
+            .map_err(|e| format!("Failed to write audit log: {}", e))
+    }
+
+    /// Roll the current log to a timestamped archive; returns the archive path if one was created
+    fn rotate(&self) -> Result<Option<PathBuf>, String> {
+        let _guard = self.lock.lock().unwrap();
+        self.rotate_locked()
+    }
+
+    // Caller must hold `self.lock`. A rename within the same directory is atomic,
+    // so every entry ends up either in the archive or in the fresh log.
+    fn rotate_locked(&self) -> Result<Option<PathBuf>, String> {
+        let path = self.path();
+        match fs::metadata(&path) {
+            Ok(meta) if meta.len() > 0 => {}
+            _ => return Ok(None),
+        }
+
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let mut archive = self.dir.join(format!("audit-{}.log", millis));
+        let mut suffix = 1;
+        while archive.exists() {
+            archive = self.dir.join(format!("audit-{}-{}.log", millis, suffix));
+            suffix += 1;
+        }
+
+        fs::rename(&path, &archive).map_err(|e| format!("Failed to rotate audit log: {}", e))?;
+        Ok(Some(archive))
+    }
+
+    /// List archived logs, oldest first
+    fn list_archives(&self) -> Result<Vec<LogArchive>, String> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(Vec::new()),
+        };
+
+        let mut archives: Vec<LogArchive> = entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let file_name = e.file_name().to_string_lossy().to_string();
+                if file_name.starts_with("audit-") && file_name.ends_with(".log") {
+                    let size_bytes = e.metadata().map(|m| m.len()).unwrap_or(0);
+                    Some(LogArchive { file_name, size_bytes })
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        archives.sort_by_key(|a| archive_sort_key(&a.file_name));
+        Ok(archives)
+    }
+}
+
+/// Order archives by (timestamp, collision suffix) parsed from "audit-<millis>[-<n>].log"
+fn archive_sort_key(file_name: &str) -> (u128, u32) {
+    let stem = file_name
+        .trim_start_matches("audit-")
+        .trim_end_matches(".log");
+    let mut parts = stem.splitn(2, '-');
+    let millis = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+    let suffix = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+    (millis, suffix)
+}
+
+/// Record an audit entry; failures to write are not allowed to break the operation
+fn audit(log: &AuditLog, event: &str, device_id: Option<&str>, details: String) {
+    let _ = log.append(&AuditEntry {
+        timestamp: unix_timestamp(),
+        event: event.to_string(),
+        device_id: device_id.map(|s| s.to_string()),
+        details,
+        app_version: Some(APP_VERSION.to_string()),
+    });
+}
+
+// ============================================================================
+// Known Devices
+// ============================================================================
+
+/// Ids a device has been seen under, oldest first; the last one is current
+struct KnownDevice {
+    ids: Vec<String>,
+    authorized: bool,
+}
+
+/// Devices remembered by serial number, so one that reconnects under a new transport id
+/// (a reboot during reset, USB to wireless) is recognised as the same device
+struct KnownDevices {
+    devices: Mutex<HashMap<String, KnownDevice>>,
+}
+
+impl KnownDevices {
+    fn new() -> Self {
+        Self {
+            devices: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn remember(&self, serial: &str, device_id: &str) {
+        let mut devices = self.devices.lock().unwrap();
+        let known = devices.entry(serial.to_string()).or_insert(KnownDevice {
+            ids: Vec::new(),
+            authorized: true,
+        });
+        if known.ids.last().map(String::as_str) != Some(device_id) {
+            known.ids.push(device_id.to_string());
+        }
+        known.authorized = true;
+    }
+
+    /// Serial of the device that was (or still is) known as `device_id`
+    fn serial_of_id(&self, device_id: &str) -> Option<String> {
+        let devices = self.devices.lock().unwrap();
+        devices
+            .iter()
+            .find(|(_, known)| known.ids.iter().any(|id| id == device_id))
+            .map(|(serial, _)| serial.clone())
+    }
+
+    /// Current id of the device that was (or still is) known as `device_id`
+    fn current_id(&self, device_id: &str) -> Option<String> {
+        let devices = self.devices.lock().unwrap();
+        devices
+            .values()
+            .find(|known| known.ids.iter().any(|id| id == device_id))
+            .and_then(|known| known.ids.last().cloned())
+    }
+
+    /// Match the `adb devices` listing against remembered serials and record any device
+    /// that changed id or authorization. Unauthorized devices can't be asked for their
+    /// serial, so those are matched by the serial in their USB id.
+    fn reassociate(
+        &self,
+        listed: &[(String, String)],
+        serial_of: impl Fn(&str) -> String,
+    ) -> Vec<DeviceReassociation> {
+        let mut devices = self.devices.lock().unwrap();
+        let mut changes = Vec::new();
+
+        for (id, state) in listed {
+            let authorized = state == "device";
+            // Already current and authorized: nothing to re-check
+            if authorized
+                && devices
+                    .values()
+                    .any(|known| known.authorized && known.ids.last() == Some(id))
+            {
+                continue;
+            }
+
+            let serial = if authorized {
+                Some(serial_of(id).trim().to_string()).filter(|s| !s.is_empty())
+            } else {
+                None
+            };
+            let Some(serial) = serial.or_else(|| serial_from_device_id(id)) else {
+                continue;
+            };
+            let Some(known) = devices.get_mut(&serial) else {
+                continue;
+            };
+
+            let previous_id = known.ids.last().cloned().unwrap_or_default();
+            if previous_id == *id && known.authorized == authorized {
+                continue;
+            }
+            if previous_id != *id {
+                known.ids.push(id.clone());
+            }
+            known.authorized = authorized;
+            changes.push(DeviceReassociation {
+                serial,
+                previous_id,
+                device_id: id.clone(),
+                authorized,
+            });
+        }
+
+        changes
+    }
+}
+
+// ============================================================================
+// Device Locks
+// ============================================================================
+
+/// Per-device lock files so two app instances (or windows) can't operate on
+/// the same device at once. Each lock file holds the owning process id.
+struct DeviceLocks {
+    dir: PathBuf,
+}
+
+/// Held lock on a device; the lock file is removed when dropped
+struct DeviceLockGuard {
+    path: PathBuf,
+}
+
+impl Drop for DeviceLockGuard {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl DeviceLocks {
+    fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn lock_path(&self, device_id: &str) -> PathBuf {
+        // Hex keeps distinct ids distinct, and ':' (network transports) out of Windows names
+        self.dir.join(format!("{}.lock", hex::encode(device_id)))
+    }
+
+    /// Take the lock for a device, reclaiming it if the previous owner is gone
+    fn acquire(&self, device_id: &str) -> Result<DeviceLockGuard, String> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create lock directory: {}", e))?;
+        let path = self.lock_path(device_id);
+
+        // The pid is written to a private file first and linked into place, so the lock
+        // file never exists without its owner (an empty one would look stale)
+        static PENDING: AtomicU64 = AtomicU64::new(0);
+        let pending = self.dir.join(format!(
+            "{}.{}.{}.tmp",
+            hex::encode(device_id),
+            std::process::id(),
+            PENDING.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&pending, std::process::id().to_string())
+            .map_err(|e| format!("Failed to lock device: {}", e))?;
+
+        let mut result = Err(format!(
+            "Device {} is in use by another session. Finish or abort that operation first.",
+            device_id
+        ));
+        for _ in 0..2 {
+            match fs::hard_link(&pending, &path) {
+                Ok(()) => {
+                    result = Ok(DeviceLockGuard { path });
+                    break;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if !is_lock_stale(&path) {
+                        break;
+                    }
+                    let _ = fs::remove_file(&path);
+                }
+                Err(e) => {
+                    result = Err(format!("Failed to lock device: {}", e));
+                    break;
+                }
+            }
+        }
+        let _ = fs::remove_file(&pending);
+        result
+    }
+
+    /// Remove locks left behind by processes that are no longer running
+    fn clean_stale(&self) -> usize {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return 0,
+        };
+
+        entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().map(|ext| ext == "lock").unwrap_or(false))
+            .filter(|p| is_lock_stale(p) && fs::remove_file(p).is_ok())
+            .count()
+    }
+}
+
+/// A lock is stale if its owner process no longer exists (or the file is unreadable garbage)
+fn is_lock_stale(path: &Path) -> bool {
+    match fs::read_to_string(path).ok().and_then(|s| s.trim().parse::<u32>().ok()) {
+        Some(pid) => pid != std::process::id() && !is_process_alive(pid),
+        None => true,
+    }
+}
+
+#[cfg(unix)]
+fn is_process_alive(pid: u32) -> bool {
+    // Linux exposes every live process under /proc; elsewhere ask the kernel via kill -0
+    let proc_dir = Path::new("/proc");
+    if proc_dir.join("self").exists() {
+        return proc_dir.join(pid.to_string()).exists();
+    }
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+#[cfg(windows)]
+fn is_process_alive(pid: u32) -> bool {
+    Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).contains(&pid.to_string()))
+        .unwrap_or(false)
+}
+
+// ============================================================================
+// Wipe Timer
+// ============================================================================
+
+/// Hard cap on wipe duration: runs `on_expire` on a background thread unless
+/// finished first
+struct WipeTimer {
+    done: mpsc::Sender<()>,
+    handle: std::thread::JoinHandle<bool>,
+}
+
+impl WipeTimer {
+    fn start(limit: Duration, on_expire: impl FnOnce() + Send + 'static) -> Self {
+        let (done, rx) = mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || match rx.recv_timeout(limit) {
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                on_expire();
+                true
+            }
+            _ => false,
+        });
+        Self { done, handle }
+    }
+
+    /// Stop the timer; returns true if it had already expired
+    fn finish(self) -> bool {
+        let _ = self.done.send(());
+        self.handle.join().unwrap_or(false)
+    }
+}
+
+/// Stop a wipe: kill the host script, the dd on the device, and remove temp files
+fn stop_wipe_processes(device_id: &str, script_group: Option<u32>) -> CleanupResult {
+    stop_wipe_processes_with(&SystemRunner, device_id, script_group)
+}
+
+fn stop_wipe_processes_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    script_group: Option<u32>,
+) -> CleanupResult {
+    // Kill this wipe's script on the host, and the adb it started
+    if let Some(pgid) = script_group {
+        kill_process_group(runner, pgid);
+    }
+
+    // Kill dd process on the device
+    let _ = runner.adb_shell(device_id, &["pkill", "-f", "dd if=/dev/urandom"]);
+
+    // Clean up temp files on the device
+    let _ = runner.adb_shell(device_id, &["rm", "-rf", "/sdcard/wipe_temp/"]);
+
+    // Confirm both actually took. The [d] keeps pgrep from matching this shell itself.
+    let dd_check = "pgrep -f '[d]d if=/dev/urandom' >/dev/null && echo running || echo stopped";
+    let dir_check = format!("test -e {} && echo present || echo removed", WIPE_TEMP_DIR);
+    let confirmed = |check: &str, expected: &str| {
+        runner
+            .adb_shell(device_id, &["sh", "-c", check])
+            .map(|out| out.stdout.trim() == expected)
+            .unwrap_or(false)
+    };
+
+    CleanupResult {
+        dd_stopped: confirmed(dd_check, "stopped"),
+        temp_removed: confirmed(&dir_check, "removed"),
+    }
+}
+
+/// Kill a wipe script and everything it started. The script leads its own process group
+/// (see wipe_script_command), so another wipe or an unrelated wipe.sh is never touched.
+#[cfg(unix)]
+fn kill_process_group(runner: &dyn CommandRunner, pgid: u32) {
+    let args = ["-TERM".to_string(), "--".to_string(), format!("-{}", pgid)];
+    let _ = runner.run("kill", &args);
+}
+
+#[cfg(windows)]
+fn kill_process_group(runner: &dyn CommandRunner, pid: u32) {
+    // /T takes the script's child processes with it
+    let args = ["/F", "/T", "/PID", &pid.to_string()].map(String::from);
+    let _ = runner.run("taskkill", &args);
+}
+
+/// dd processes reading /dev/urandom in `ps -A -o PID,ARGS` output. Matching on the
+/// program name keeps shells whose command line merely mentions dd (pkill -f, the
+/// wipe loop's sh -c) out of the list.
+fn parse_wipe_processes(ps_output: &str) -> Vec<DeviceWipeProcess> {
+    ps_output
+        .lines()
+        .filter_map(|line| {
+            let (pid, command) = line.trim().split_once(char::is_whitespace)?;
+            let pid = pid.parse().ok()?;
+            let command = command.trim();
+            let program = command.split_whitespace().next()?;
+            let is_dd = program == "dd" || program.ends_with("/dd");
+            (is_dd && command.contains("if=/dev/urandom")).then(|| DeviceWipeProcess {
+                pid,
+                command: command.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn list_wipe_processes_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+) -> Result<Vec<DeviceWipeProcess>, String> {
+    let output = runner.adb_shell(device_id, &["ps", "-A", "-o", "PID,ARGS"])?;
+    if !output.success {
+        return Err(format!("Failed to list device processes: {}", output.stderr.trim()));
+    }
+    Ok(parse_wipe_processes(&output.stdout))
+}
+
+/// Kill every lingering wipe dd on the device, then list again to see what survived
+fn kill_wipe_processes_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+) -> Result<WipeProcessKill, String> {
+    let found = list_wipe_processes_with(runner, device_id)?;
+    if found.is_empty() {
+        return Ok(WipeProcessKill {
+            found,
+            killed: Vec::new(),
+            remaining: Vec::new(),
+        });
+    }
+
+    let pids: Vec<String> = found.iter().map(|p| p.pid.to_string()).collect();
+    let mut argv = vec!["kill"];
+    argv.extend(pids.iter().map(String::as_str));
+    // Some may exit by themselves in the meantime; what's left is checked below
+    let _ = runner.adb_shell(device_id, &argv);
+
+    let remaining = list_wipe_processes_with(runner, device_id)?;
+    let killed = found
+        .iter()
+        .filter(|p| !remaining.iter().any(|r| r.pid == p.pid))
+        .map(|p| p.pid)
+        .collect();
+    Ok(WipeProcessKill {
+        found,
+        killed,
+        remaining,
+    })
+}
+
+/// Warnings that follow from how a wipe turned out. Only a successful wipe gets them; a
+/// failed one is reported as an error instead.
+fn outcome_warnings(session: &WipeSession) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    if !session.success {
+        return warnings;
+    }
+    if let Some(v) = session.verification.as_ref().filter(|v| !v.passed) {
+        warnings.push(Warning::new(WarningCode::VerificationFailed, v.detail.clone()));
+    }
+    let partial = session.free_space_coverage.as_ref().and_then(|c| c.partial_reason.clone());
+    if let Some(reason) = partial {
+        warnings.push(Warning::new(WarningCode::PartialCoverage, reason));
+    }
+    warnings.push(Warning::new(
+        WarningCode::WearLeveling,
+        "Flash wear leveling can keep old data in reserved blocks an overwrite can't reach. \
+         The factory reset (which discards the encryption keys) covers those.",
+    ));
+    warnings
+}
+
+/// Told to the user when a data-only wipe finishes
+const DATA_ONLY_RESET_NOTE: &str = "No factory reset was performed: this was a data-only \
+     wipe. Accounts, apps and settings on the device are unchanged.";
+
+/// Payload of the `wipe-complete` event
+fn wipe_complete_payload(session: &WipeSession, keep_temp_files: bool) -> serde_json::Value {
+    let temp_files_note = keep_temp_files.then(|| {
+        format!(
+            "Overwrite data from the last pass was left in {} for inspection. \
+             Delete it when done; a factory reset also removes it.",
+            WIPE_TEMP_DIR
+        )
+    });
+
+    serde_json::json!({
+        "success": session.success,
+        "mode": session.mode,
+        "passes": session.passes,
+        "passes_requested": session.passes,
+        "passes_completed": session.passes_completed,
+        "session_id": session.session_id,
+        "coverage": session.coverage,
+        "coverage_summary": session.coverage.as_ref().map(describe_coverage),
+        "verification": session.verification,
+        "warnings": session.warnings,
+        "storage_snapshots": session.storage_snapshots,
+        "storage_summary": describe_storage_snapshots(&session.storage_snapshots),
+        "phase_durations": session.phase_durations,
+        "phase_summary": describe_phase_durations(&session.phase_durations),
+        "factory_reset_skipped": session.factory_reset_skipped,
+        "reset_note": session.factory_reset_skipped.then_some(DATA_ONLY_RESET_NOTE),
+        "temp_files_kept": keep_temp_files,
+        "temp_files_path": keep_temp_files.then_some(WIPE_TEMP_DIR),
+        "temp_files_note": temp_files_note
+    })
+}
+
+/// Payload of the `wipe-aborted` event
+fn abort_payload(cleanup: &CleanupResult) -> serde_json::Value {
+    let message = match (cleanup.dd_stopped, cleanup.temp_removed) {
+        (true, true) => "Wipe operation aborted and cleaned up.".to_string(),
+        (dd_stopped, temp_removed) => {
+            let mut problems = Vec::new();
+            if !dd_stopped {
+                problems.push("dd may still be running on the device");
+            }
+            if !temp_removed {
+                problems.push("temporary files may remain in /sdcard/wipe_temp");
+            }
+            format!("Wipe aborted, but cleanup could not be confirmed: {}.", problems.join(" and "))
+        }
+    };
+
+    serde_json::json!({
+        "message": message,
+        "cleanup": cleanup
+    })
+}
+
+// ============================================================================
+// Battery Monitor
+// ============================================================================
+
+/// Watches successive battery readings for the charger being pulled mid-wipe
+struct BatteryMonitor {
+    warn_percent: u8,
+    was_charging: Option<bool>,
+    warned: bool,
+}
+
+impl BatteryMonitor {
+    fn new(warn_percent: u8) -> Self {
+        Self {
+            warn_percent,
+            was_charging: None,
+            warned: false,
+        }
+    }
+
+    /// Record a reading; returns a warning the first time the device is running on
+    /// battery below the threshold, and again whenever it is unplugged while low
+    fn check(&mut self, status: &BatteryStatus) -> Option<String> {
+        let unplugged = self.was_charging == Some(true) && !status.charging;
+        self.was_charging = Some(status.charging);
+
+        if status.charging {
+            self.warned = false;
+            return None;
+        }
+        if status.level >= self.warn_percent || (self.warned && !unplugged) {
+            return None;
+        }
+
+        self.warned = true;
+        Some(if unplugged {
+            format!(
+                "Charger disconnected at {}% battery. Reconnect it before the battery runs out.",
+                status.level
+            )
+        } else {
+            format!(
+                "Battery at {}% and not charging. Connect a charger to finish the wipe safely.",
+                status.level
+            )
+        })
+    }
+}
+
+/// Read the battery state of a device
+fn read_battery_status(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+) -> Result<BatteryStatus, String> {
+    let output = runner
+        .adb_shell(device_id, &["dumpsys", "battery"])
+        .map_err(|e| format!("Failed to read battery status: {}", e))?;
+
+    if !output.success {
+        return Err("Failed to read battery status. Device may be disconnected.".to_string());
+    }
+
+    parse_battery_status(&output.stdout)
+}
+
+/// Act on one battery reading during a wipe: report it, and warn (once per monitor rule)
+/// when the device is running low on battery. Warnings are also kept for the session.
+fn record_battery_reading(
+    monitor: &mut BatteryMonitor,
+    status: BatteryStatus,
+    events: &dyn EventSink,
+    warnings: &mut Vec<Warning>,
+) {
+    if let Some(message) = monitor.check(&status) {
+        emit_event(
+            events,
+            "battery-warning",
+            serde_json::json!({ "message": message, "level": status.level }),
+        );
+        warnings.push(Warning::new(WarningCode::LowBattery, message));
+    }
+    emit_event(events, "battery-status", status);
+}
+
+/// Polls the battery on a background thread for the length of a wipe
+struct BatteryWatch {
+    done: mpsc::Sender<()>,
+    handle: std::thread::JoinHandle<Vec<Warning>>,
+}
+
+impl BatteryWatch {
+    fn start(
+        runner: Arc<dyn CommandRunner + Send + Sync>,
+        events: Arc<dyn EventSink>,
+        device_id: String,
+        interval: Duration,
+        warn_percent: u8,
+    ) -> Self {
+        let (done, rx) = mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || {
+            let mut monitor = BatteryMonitor::new(warn_percent);
+            let mut warnings = Vec::new();
+            while let Err(mpsc::RecvTimeoutError::Timeout) = rx.recv_timeout(interval) {
+                let Ok(status) = read_battery_status(&*runner, &device_id) else {
+                    continue;
+                };
+                record_battery_reading(&mut monitor, status, &*events, &mut warnings);
+            }
+            warnings
+        });
+        Self { done, handle }
+    }
+
+    /// Stop polling; returns the warnings raised while it ran
+    fn stop(self) -> Vec<Warning> {
+        let _ = self.done.send(());
+        self.handle.join().unwrap_or_default()
+    }
+}
+
+// ============================================================================
+// Events
+// ============================================================================
+
+/// Where frontend events go: the window in the app, a recorder in tests, so the
+/// sequence of events a wipe or abort produces can be asserted
+trait EventSink: Send + Sync {
+    fn emit_json(&self, event: &str, payload: serde_json::Value);
+}
+
+impl EventSink for tauri::Window {
+    fn emit_json(&self, event: &str, payload: serde_json::Value) {
+        EVENT_BROADCAST.broadcast(event, &payload);
+        let _ = self.emit(event, payload);
+    }
+}
+
+/// An event a wipe emitted, kept with its session for review
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionEvent {
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub at_ms: u64, // Unix milliseconds
+}
+
+/// Forwards a wipe's events and keeps a copy of them for its session. Progress is thinned
+/// to one update per whole percent (per pass and phase), and interpolated estimates are
+/// left out, so a long wipe doesn't record every update.
+struct RecordingSink {
+    inner: Arc<dyn EventSink>,
+    recorded: Mutex<Vec<SessionEvent>>,
+    last_progress: Mutex<Option<String>>,
+}
+
+impl RecordingSink {
+    fn new(inner: Arc<dyn EventSink>) -> Self {
+        Self {
+            inner,
+            recorded: Mutex::new(Vec::new()),
+            last_progress: Mutex::new(None),
+        }
+    }
+
+    fn take(&self) -> Vec<SessionEvent> {
+        std::mem::take(&mut *self.recorded.lock().unwrap())
+    }
+}
+
+impl EventSink for RecordingSink {
+    fn emit_json(&self, event: &str, payload: serde_json::Value) {
+        let keep = event != "wipe-progress" || {
+            let step = format!(
+                "{}:{}:{}",
+                payload["pass"],
+                payload["phase"],
+                payload["percent"].as_f64().unwrap_or(0.0).floor()
+            );
+            let mut last = self.last_progress.lock().unwrap();
+            let new_step = last.as_deref() != Some(step.as_str());
+            *last = Some(step);
+            payload["estimated"] != true && new_step
+        };
+        if keep {
+            self.recorded.lock().unwrap().push(SessionEvent {
+                event: event.to_string(),
+                payload: payload.clone(),
+                at_ms: unix_timestamp_ms(),
+            });
+        }
+        self.inner.emit_json(event, payload);
+    }
+}
+
+/// Emit a session's recorded events again, in order, each wrapped in a `session-replay`
+/// event ({event, payload, at_ms}) so no live listener mistakes it for a running wipe.
+/// Returns how many were emitted.
+fn replay_events(events: &dyn EventSink, recorded: &[SessionEvent]) -> usize {
+    for recorded_event in recorded {
+        emit_event(events, "session-replay", recorded_event);
+    }
+    recorded.len()
+}
+
+/// Sends events to the app window alone, for those the WebSocket feed mustn't carry
+struct WindowOnly<'a>(&'a tauri::Window);
+
+impl EventSink for WindowOnly<'_> {
+    fn emit_json(&self, event: &str, payload: serde_json::Value) {
+        let _ = self.0.emit(event, payload);
+    }
+}
+
+/// Emit a serializable payload through a sink
+fn emit_event(events: &dyn EventSink, event: &str, payload: impl Serialize) {
+    if let Ok(payload) = serde_json::to_value(payload) {
+        events.emit_json(event, payload);
+    }
+}
+
+// ============================================================================
+// Event Websocket
+// ============================================================================
+
+/// Loopback only, so a bench machine doesn't expose wipe progress to the network
+const DEFAULT_EVENT_WEBSOCKET_ADDR: &str = "127.0.0.1:9477";
+
+/// A dashboard that stops reading is dropped rather than allowed to stall a wipe
+const EVENT_WEBSOCKET_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How often the listener checks whether the websocket has been turned off
+const EVENT_WEBSOCKET_POLL: Duration = Duration::from_millis(100);
+
+/// Events queued for a client before it's treated as stalled and dropped
+const EVENT_WEBSOCKET_BACKLOG: usize = 256;
+
+/// Rebroadcasts every window event to connected websocket clients, for headless benches
+/// monitored from another machine. Each event is one text frame holding
+/// `{"event": "<name>", "payload": <the same payload the window gets>}`.
+///
+/// Process-wide for the same reason as the command tracer: events are emitted from
+/// every wipe path. Each client has its own thread, so a slow handshake or a stalled
+/// reader never holds up the others. Clients only receive; anything they send is ignored.
+struct EventBroadcaster {
+    enabled: AtomicBool,
+    listening: Mutex<Option<(std::net::SocketAddr, Arc<AtomicBool>)>>, // With its stop flag
+    clients: Mutex<Vec<mpsc::SyncSender<String>>>,
+}
+
+static EVENT_BROADCAST: EventBroadcaster = EventBroadcaster::new();
+
+impl EventBroadcaster {
+    const fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            listening: Mutex::new(None),
+            clients: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Start listening on `addr` when enabled; turning it off closes the listener and
+    /// disconnects clients, so a changed address takes effect the next time it's turned on.
+    /// Returns a warning when the listener is reachable from other machines.
+    fn configure(&'static self, enabled: bool, addr: &str) -> Result<Option<String>, String> {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            if let Some((_, stop)) = self.listening.lock().unwrap().take() {
+                stop.store(true, Ordering::Relaxed);
+            }
+            self.clients.lock().unwrap().clear();
+            return Ok(None);
+        }
+        let listening = self.listening.lock().unwrap().as_ref().map(|(local, _)| *local);
+        let local = match listening {
+            Some(local) => local,
+            None => self.listen(addr).inspect_err(|_| {
+                self.enabled.store(false, Ordering::Relaxed);
+            })?,
+        };
+        Ok((!local.ip().is_loopback()).then(|| {
+            format!(
+                "The event websocket listens on {}, so anyone on the network can watch wipes. \
+                 Use a loopback address and forward the port over SSH instead.",
+                local
+            )
+        }))
+    }
+
+    fn listen(&'static self, addr: &str) -> Result<std::net::SocketAddr, String> {
+        let failed =
+            |e: std::io::Error| format!("Failed to start event websocket on {}: {}", addr, e);
+        let listener = std::net::TcpListener::bind(addr).map_err(failed)?;
+        let local = listener.local_addr().map_err(failed)?;
+        // Polled, so the listener can be closed when the websocket is turned off
+        listener.set_nonblocking(true).map_err(failed)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        *self.listening.lock().unwrap() = Some((local, stop.clone()));
+
+        std::thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => self.serve(stream),
+                    Err(_) => std::thread::sleep(EVENT_WEBSOCKET_POLL),
+                }
+            }
+        });
+        Ok(local)
+    }
+
+    /// Complete a client's handshake and forward broadcasts to it, on its own thread
+    fn serve(&'static self, stream: std::net::TcpStream) {
+        std::thread::spawn(move || {
+            let timeouts = [
+                stream.set_nonblocking(false),
+                stream.set_read_timeout(Some(Duration::from_secs(5))),
+                stream.set_write_timeout(Some(EVENT_WEBSOCKET_WRITE_TIMEOUT)),
+            ];
+            if timeouts.iter().any(Result::is_err) {
+                return;
+            }
+            let Ok(mut socket) = tungstenite::accept(stream) else {
+                return;
+            };
+            let (sender, messages) = mpsc::sync_channel(EVENT_WEBSOCKET_BACKLOG);
+            {
+                let mut clients = self.clients.lock().unwrap();
+                if !self.enabled.load(Ordering::Relaxed) {
+                    return;
+                }
+                clients.push(sender);
+            }
+            // Ends when the client goes away, or is dropped by broadcast or by configure
+            for message in messages {
+                if socket.send(tungstenite::Message::text(message)).is_err() {
+                    return;
+                }
+            }
+            let _ = socket.close(None);
+            let _ = socket.flush();
+        });
+    }
+
+    fn broadcast(&self, event: &str, payload: &serde_json::Value) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut clients = self.clients.lock().unwrap();
+        if clients.is_empty() {
+            return;
+        }
+        let message = serde_json::json!({ "event": event, "payload": payload }).to_string();
+        clients.retain(|client| client.try_send(message.clone()).is_ok());
+    }
+}
+
+// ============================================================================
+// Progress Streaming
+// ============================================================================
+
+/// Pending progress events held before intermediate updates start being dropped
+const PROGRESS_QUEUE_CAPACITY: usize = 16;
+
+/// Bounded hand-off between the stdout reader thread and the emitter
+///
+/// The reader never blocks: when the queue is full, an intermediate "writing" update
+/// is dropped to make room, since a newer one supersedes it. Phase changes (pass
+/// complete, final summary) and the latest update are always kept.
+struct ProgressQueue {
+    inner: Mutex<(VecDeque<WipeProgress>, bool)>, // (pending, closed)
+    ready: Condvar,
+    capacity: usize,
+}
+
+impl ProgressQueue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new((VecDeque::new(), false)),
+            ready: Condvar::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    fn push(&self, progress: WipeProgress) {
+        let mut inner = self.inner.lock().unwrap();
+        let queue = &mut inner.0;
+        if queue.len() >= self.capacity {
+            if let Some(idx) = queue.iter().rposition(|p| p.phase == "writing") {
+                queue.remove(idx);
+            }
+        }
+        queue.push_back(progress);
+        self.ready.notify_one();
+    }
+
+    /// No more progress will be pushed; next() returns Closed once drained
+    fn close(&self) {
+        self.inner.lock().unwrap().1 = true;
+        self.ready.notify_all();
+    }
+
+    /// Next progress update, waiting up to `timeout` for one to arrive
+    fn next(&self, timeout: Duration) -> QueueItem {
+        let mut inner = self.inner.lock().unwrap();
+        loop {
+            if let Some(progress) = inner.0.pop_front() {
+                return QueueItem::Progress(progress);
+            }
+            if inner.1 {
+                return QueueItem::Closed;
+            }
+            let (guard, wait) = self.ready.wait_timeout(inner, timeout).unwrap();
+            inner = guard;
+            if wait.timed_out() && inner.0.is_empty() && !inner.1 {
+                return QueueItem::Idle;
+            }
+        }
+    }
+}
+
+enum QueueItem {
+    Progress(WipeProgress),
+    Idle, // Nothing arrived within the timeout
+    Closed,
+}
+
+/// How long a wipe may run without a recognizable progress line before the UI is told
+/// to switch to an indeterminate progress display
+const PROGRESS_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Decides when to send `progress-mode` hints: "indeterminate" if the script hasn't
+/// produced any progress within the grace period, "determinate" once it finally does
+struct ProgressModeTracker {
+    started: Instant,
+    grace: Duration,
+    seen_progress: bool,
+    indeterminate: bool,
+}
+
+impl ProgressModeTracker {
+    fn new(started: Instant, grace: Duration) -> Self {
+        Self {
+            started,
+            grace,
+            seen_progress: false,
+            indeterminate: false,
+        }
+    }
+
+    fn on_progress(&mut self) -> Option<&'static str> {
+        self.seen_progress = true;
+        if self.indeterminate {
+            self.indeterminate = false;
+            return Some("determinate");
+        }
+        None
+    }
+
+    fn on_idle(&mut self, now: Instant) -> Option<&'static str> {
+        if self.seen_progress
+            || self.indeterminate
+            || now.duration_since(self.started) < self.grace
+        {
+            return None;
+        }
+        self.indeterminate = true;
+        Some("indeterminate")
+    }
+}
+
+/// Longest script output line kept; the rest of a longer line is discarded
+const MAX_LINE_BYTES: usize = 64 * 1024;
+
+/// One line of script output, cut to the reader's limit
+#[derive(Debug)]
+struct OutputLine {
+    text: String,
+    truncated: bool,
+}
+
+/// Like `BufRead::lines`, but never holds more than `max` bytes of a line in memory, so
+/// output without newlines can't grow the buffer without bound. Invalid UTF-8 is
+/// replaced rather than ending the stream.
+struct BoundedLines<R> {
+    reader: R,
+    max: usize,
+}
+
+impl<R: BufRead> BoundedLines<R> {
+    fn new(reader: R, max: usize) -> Self {
+        Self { reader, max }
+    }
+}
+
+impl<R: BufRead> Iterator for BoundedLines<R> {
+    type Item = OutputLine;
+
+    fn next(&mut self) -> Option<OutputLine> {
+        let mut line = Vec::new();
+        let mut truncated = false;
+        let mut read_any = false;
+
+        loop {
+            let buf = match self.reader.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            if buf.is_empty() {
+                break; // EOF
+            }
+            read_any = true;
+
+            let newline = buf.iter().position(|&b| b == b'\n');
+            let chunk = &buf[..newline.unwrap_or(buf.len())];
+            let room = self.max.saturating_sub(line.len());
+            truncated |= chunk.len() > room;
+            line.extend_from_slice(&chunk[..chunk.len().min(room)]);
+
+            let used = chunk.len() + newline.map_or(0, |_| 1);
+            self.reader.consume(used);
+            if newline.is_some() {
+                break;
+            }
+        }
+
+        if !read_any {
+            return None;
+        }
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        Some(OutputLine {
+            text: String::from_utf8_lossy(&line).into_owned(),
+            truncated,
+        })
+    }
+}
+
+// ============================================================================
+// Sessions & Certificates
+// ============================================================================
+
+/// Stores one JSON file per wipe session in the app data directory
+struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn save(&self, session: &WipeSession) -> Result<(), String> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create session directory: {}", e))?;
+        let json = serde_json::to_string_pretty(session)
+            .map_err(|e| format!("Failed to serialize session: {}", e))?;
+        fs::write(self.dir.join(format!("{}.json", session.session_id)), json)
+            .map_err(|e| format!("Failed to save session: {}", e))
+    }
+
+    fn load(&self, session_id: &str) -> Result<WipeSession, String> {
+        let session_id = sanitize_session_id(session_id)?;
+        let json = fs::read_to_string(self.dir.join(format!("{}.json", session_id)))
+            .map_err(|_| format!("Session {} not found.", session_id))?;
+        serde_json::from_str(&json).map_err(|e| format!("Corrupt session record: {}", e))
+    }
+
+    /// Keep a session's events beside it. Not a .json file, so list() doesn't read it.
+    fn save_events(&self, session_id: &str, events: &[SessionEvent]) -> Result<(), String> {
+        let session_id = sanitize_session_id(session_id)?;
+        let json = serde_json::to_string(events)
+            .map_err(|e| format!("Failed to serialize session events: {}", e))?;
+        fs::write(self.dir.join(format!("{}.events", session_id)), json)
+            .map_err(|e| format!("Failed to save session events: {}", e))
+    }
+
+    /// A session's recorded events; none for sessions recorded before events were kept
+    fn load_events(&self, session_id: &str) -> Result<Vec<SessionEvent>, String> {
+        let session = self.load(session_id)?;
+        let path = self.dir.join(format!("{}.events", session.session_id));
+        match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| format!("Corrupt session events: {}", e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(format!("Failed to read session events: {}", e)),
+        }
+    }
+
+    /// All readable sessions, oldest first. Corrupt records are skipped.
+    fn list(&self) -> Result<Vec<WipeSession>, String> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read sessions: {}", e)),
+        };
+
+        let mut sessions: Vec<WipeSession> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|json| serde_json::from_str(&json).ok())
+            .collect();
+        sessions.sort_by_key(|session| session.started_at);
+        Ok(sessions)
+    }
+
+    /// Sessions recorded for one device serial (case-insensitive), oldest first
+    fn find_by_serial(&self, serial: &str) -> Result<Vec<WipeSession>, String> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|session| session.serial.eq_ignore_ascii_case(serial))
+            .collect())
+    }
+}
+
+/// Session ids become file names, so only allow a conservative character set
+fn sanitize_session_id(session_id: &str) -> Result<String, String> {
+    let valid = session_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !valid || session_id.is_empty() || session_id.len() > 96 {
+        return Err("Invalid session ID format".to_string());
+    }
+
+    Ok(session_id.to_string())
+}
+
+/// New batch id from the start time in milliseconds
+fn new_batch_id() -> String {
+    format!("batch-{}", unix_timestamp_ms())
+}
+
+/// New session id: start time in milliseconds plus the (file-name safe) device id
+fn new_session_id(device_id: &str) -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    format!("{}-{}", millis, device_id.replace([':', '.'], "_"))
+}
+
+/// Human-readable description of the overwrite method used
+fn describe_wipe_method(mode: &str, passes: u32, size_mb: Option<u32>) -> String {
+    let plural = if passes == 1 { "pass" } else { "passes" };
+    match (mode, size_mb) {
+        ("quick", Some(size)) => format!(
+            "Quick overwrite: {} {} x {}MB of random data",
+            passes, plural, size
+        ),
+        _ => format!(
+            "Full overwrite: {} {} filling free space with random data",
+            passes, plural
+        ),
+    }
+}
+
+fn history_entry(session: &WipeSession) -> HistoryEntry {
+    HistoryEntry {
+        session_id: session.session_id.clone(),
+        started: format_utc_timestamp(session.started_at),
+        finished: format_utc_timestamp(session.finished_at),
+        brand: session.device.brand.clone(),
+        model: session.device.model.clone(),
+        serial: session.serial.clone(),
+        method: describe_wipe_method(&session.mode, session.passes, session.size_mb),
+        result: if session.success { "completed" } else { "failed" }.to_string(),
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn history_to_csv(entries: &[HistoryEntry]) -> String {
+    let mut csv = "session_id,started,finished,brand,model,serial,method,result\n".to_string();
+    for e in entries {
+        let fields = [
+            &e.session_id, &e.started, &e.finished, &e.brand, &e.model, &e.serial, &e.method,
+            &e.result,
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn history_to_json(entries: &[HistoryEntry]) -> Result<String, String> {
+    serde_json::to_string_pretty(entries).map_err(|e| format!("Failed to serialize history: {}", e))
+}
+
+/// Longest operator name or case reference accepted
+const OPERATOR_FIELD_MAX_CHARS: usize = 100;
+
+/// Trim the operator fields and reject empty names, control characters and overlong values.
+/// A blank case reference is dropped rather than recorded.
+fn validate_operator(operator: &OperatorInfo) -> Result<OperatorInfo, String> {
+    let check = |label: &str, value: &str| -> Result<String, String> {
+        let value = value.trim();
+        if value.chars().count() > OPERATOR_FIELD_MAX_CHARS {
+            return Err(format!(
+                "{} is too long (at most {} characters).",
+                label, OPERATOR_FIELD_MAX_CHARS
+            ));
+        }
+        if value.chars().any(char::is_control) {
+            return Err(format!("{} contains control characters.", label));
+        }
+        Ok(value.to_string())
+    };
+
+    let name = check("Operator name", &operator.name)?;
+    if name.is_empty() {
+        return Err("Operator name is empty. Clear the operator instead.".to_string());
+    }
+    let case_reference = match &operator.case_reference {
+        Some(reference) => Some(check("Case reference", reference)?).filter(|r| !r.is_empty()),
+        None => None,
+    };
+    Ok(OperatorInfo {
+        name,
+        case_reference,
+    })
+}
+
+/// ` operator=<name> case=<ref>` for audit details, or nothing if no operator is set
+fn describe_operator(operator: Option<&OperatorInfo>) -> String {
+    match operator {
+        Some(OperatorInfo {
+            name,
+            case_reference: Some(case),
+        }) => format!(" operator={:?} case={:?}", name, case),
+        Some(operator) => format!(" operator={:?}", operator.name),
+        None => String::new(),
+    }
+}
+
+/// Build the certificate for a session
+fn build_certificate(session: &WipeSession) -> WipeCertificate {
+    let nist_level = if session.success {
+        "NIST SP 800-88 Clear (logical overwrite of user-accessible storage)"
+    } else {
+        "None (wipe did not complete)"
+    };
+
+    WipeCertificate {
+        session_id: session.session_id.clone(),
+        device_brand: session.device.brand.clone(),
+        device_model: session.device.model.clone(),
+        device_serial: session.serial.clone(),
+        android_version: session.device.android_version.clone(),
+        device_bootloader: session.device.bootloader.clone(),
+        device_baseband: session.device.baseband.clone(),
+        started_at: session.started_at,
+        completed_at: session.finished_at,
+        method: describe_wipe_method(&session.mode, session.passes, session.size_mb),
+        nist_level: nist_level.to_string(),
+        result: if session.success { "success" } else { "failed" }.to_string(),
+        passes_requested: session.passes_completed.map(|_| session.passes),
+        passes_completed: session.passes_completed,
+        coverage: session.coverage.clone(),
+        verification: session.verification.clone(),
+        free_space_coverage: session.free_space_coverage.clone(),
+        operator: session.operator.as_ref().map(|o| o.name.clone()),
+        case_reference: session.operator.as_ref().and_then(|o| o.case_reference.clone()),
+        storage_snapshots: session.storage_snapshots.clone(),
+        app_version: session.app_version.clone(),
+        script_version: session.script_version.clone(),
+        phase_durations: session.phase_durations.clone(),
+        key_id: None,
+        public_key: None,
+        signature: None,
+    }
+}
+
+const SIGNING_KEY_FILE: &str = "signing_key";
+
+/// Short identifier for a public key: the first 8 bytes in hex
+fn signing_key_id(key: &VerifyingKey) -> String {
+    hex::encode(&key.to_bytes()[..8])
+}
+
+/// Load this installation's signing key, creating it on first use
+fn load_or_create_signing_key(data_dir: &Path) -> Result<SigningKey, String> {
+    let path = data_dir.join(SIGNING_KEY_FILE);
+
+    if let Ok(bytes) = fs::read(&path) {
+        let secret: [u8; 32] = bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| "Signing key file is corrupt.".to_string())?;
+        return Ok(SigningKey::from_bytes(&secret));
+    }
+
+    let mut secret = [0u8; 32];
+    getrandom::getrandom(&mut secret).map_err(|e| format!("Failed to generate key: {}", e))?;
+
+    // Owner-only from the moment it exists, never briefly readable by other users
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(&secret))
+        .map_err(|e| format!("Failed to save signing key: {}", e))?;
+
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+/// Bytes covered by the signature: the certificate serialized without its signature
+fn certificate_signing_payload(cert: &WipeCertificate) -> Result<Vec<u8>, String> {
+    let mut unsigned = cert.clone();
+    unsigned.signature = None;
+    serde_json::to_vec(&unsigned).map_err(|e| format!("Failed to serialize certificate: {}", e))
+}
+
+/// Sign a certificate, embedding the public key and key id
+fn sign_certificate(cert: &mut WipeCertificate, key: &SigningKey) -> Result<(), String> {
+    let verifying_key = key.verifying_key();
+    cert.key_id = Some(signing_key_id(&verifying_key));
+    cert.public_key = Some(hex::encode(verifying_key.to_bytes()));
+
+    let payload = certificate_signing_payload(cert)?;
+    cert.signature = Some(hex::encode(key.sign(&payload).to_bytes()));
+    Ok(())
+}
+
+/// Check a certificate's signature against its embedded public key
+fn verify_certificate_signature(
+    cert: &WipeCertificate,
+    known_key: Option<&VerifyingKey>,
+) -> CertificateVerification {
+    let invalid = |reason: &str| CertificateVerification {
+        valid: false,
+        key_id: cert.key_id.clone(),
+        known_key: false,
+        reason: Some(reason.to_string()),
+    };
+
+    let (public_key, signature) = match (&cert.public_key, &cert.signature) {
+        (Some(k), Some(s)) => (k, s),
+        _ => return invalid("Certificate is not signed."),
+    };
+
+    let key_bytes: Option<[u8; 32]> = hex::decode(public_key)
+        .ok()
+        .and_then(|b| b.as_slice().try_into().ok());
+    let verifying_key = match key_bytes.and_then(|b| VerifyingKey::from_bytes(&b).ok()) {
+        Some(k) => k,
+        None => return invalid("Embedded public key is malformed."),
+    };
+
+    let signature = match hex::decode(signature)
+        .ok()
+        .and_then(|b| Signature::from_slice(&b).ok())
+    {
+        Some(s) => s,
+        None => return invalid("Signature is malformed."),
+    };
+
+    if cert.key_id.as_deref() != Some(signing_key_id(&verifying_key).as_str()) {
+        return invalid("Key id does not match the embedded public key.");
+    }
+
+    let payload = match certificate_signing_payload(cert) {
+        Ok(p) => p,
+        Err(e) => return invalid(&e),
+    };
+    if verifying_key.verify(&payload, &signature).is_err() {
+        return invalid("Signature does not match the certificate contents (tampered?).");
+    }
+
+    CertificateVerification {
+        valid: true,
+        key_id: cert.key_id.clone(),
+        known_key: known_key.map(|k| k == &verifying_key).unwrap_or(false),
+        reason: None,
+    }
+}
+
+/// Load a certificate JSON file and verify it; malformed files are reported as invalid
+fn verify_certificate_file(
+    path: &Path,
+    known_key: Option<&VerifyingKey>,
+) -> Result<CertificateVerification, String> {
+    let json = fs::read_to_string(path).map_err(|e| format!("Failed to read certificate: {}", e))?;
+
+    match serde_json::from_str::<WipeCertificate>(&json) {
+        Ok(cert) => Ok(verify_certificate_signature(&cert, known_key)),
+        Err(e) => Ok(CertificateVerification {
+            valid: false,
+            key_id: None,
+            known_key: false,
+            reason: Some(format!("Not a valid certificate file: {}", e)),
+        }),
+    }
+}
+
+/// Label/value rows shown on the printable certificate
+fn certificate_rows(cert: &WipeCertificate) -> Vec<(&'static str, String)> {
+    vec![
+        ("Certificate ID", cert.session_id.clone()),
+        ("Device", format!("{} {}", cert.device_brand, cert.device_model)),
+        ("Serial number", cert.device_serial.clone()),
+        ("Android version", cert.android_version.clone()),
+        (
+            "Firmware",
+            match (&cert.device_bootloader, &cert.device_baseband) {
+                (Some(bootloader), Some(baseband)) => {
+                    format!("Bootloader {}, baseband {}", bootloader, baseband)
+                }
+                (Some(bootloader), None) => format!("Bootloader {}", bootloader),
+                (None, Some(baseband)) => format!("Baseband {}", baseband),
+                (None, None) => "Not recorded".to_string(),
+            },
+        ),
+        ("Wipe started", format_utc_timestamp(cert.started_at)),
+        ("Wipe completed", format_utc_timestamp(cert.completed_at)),
+        ("Method", cert.method.clone()),
+        ("Sanitization level", cert.nist_level.clone()),
+        ("Result", cert.result.clone()),
+        (
+            "Passes completed",
+            match (cert.passes_completed, cert.passes_requested) {
+                (Some(completed), Some(requested)) => format!("{} of {}", completed, requested),
+                _ => "Not recorded".to_string(),
+            },
+        ),
+        (
+            "Overwrite coverage",
+            cert.coverage
+                .as_ref()
+                .map(describe_coverage)
+                .unwrap_or_else(|| "Not measured".to_string()),
+        ),
+        (
+            "Passes confirmed (script-reported)",
+            cert.verification
+                .as_ref()
+                .map(|v| v.detail.clone())
+                .unwrap_or_else(|| "Not recorded".to_string()),
+        ),
+        (
+            "Free space",
+            cert.free_space_coverage
+                .as_ref()
+                .map(|c| match &c.partial_reason {
+                    None => format!("Fully overwritten ({:.1}%)", c.covered_percent),
+                    Some(reason) => format!("Partial: {}", reason),
+                })
+                .unwrap_or_else(|| "Not measured".to_string()),
+        ),
+        (
+            "Operator",
+            cert.operator.clone().unwrap_or_else(|| "Not recorded".to_string()),
+        ),
+        (
+            "Case reference",
+            cert.case_reference.clone().unwrap_or_else(|| "None".to_string()),
+        ),
+        ("Free space over time", describe_storage_snapshots(&cert.storage_snapshots)),
+        ("Time per phase", describe_phase_durations(&cert.phase_durations)),
+        (
+            "Software",
+            match (&cert.app_version, &cert.script_version) {
+                (Some(app), Some(script)) => format!("App {}, wipe script {}", app, script),
+                (Some(app), None) => format!("App {}", app),
+                _ => "Not recorded".to_string(),
+            },
+        ),
+        (
+            "Signing key",
+            cert.key_id.clone().unwrap_or_else(|| "None".to_string()),
+        ),
+        (
+            "Signature",
+            cert.signature.clone().unwrap_or_else(|| "Unsigned".to_string()),
+        ),
+    ]
+}
+
+/// The built-in PDF fonts only cover Latin-1, so replace anything outside printable ASCII
+fn pdf_text(s: &str) -> Vec<u8> {
+    s.chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() { c as u8 } else { b'?' })
+        .collect()
+}
+
+/// Characters of a value that fit in the value column at 11pt
+const PDF_VALUE_CHARS_PER_LINE: usize = 56;
+
+/// Render a certificate as a single-page A4 PDF
+fn render_certificate_pdf(
+    cert: &WipeCertificate,
+    generation: &GenerationToken,
+) -> Result<Vec<u8>, String> {
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_id = Ref::new(3);
+    let font_id = Ref::new(4);
+    let bold_font_id = Ref::new(5);
+    let content_id = Ref::new(6);
+    let font = Name(b"F1");
+    let bold_font = Name(b"F2");
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids([page_id]).count(1);
+
+    let mut page = pdf.page(page_id);
+    page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+    page.parent(page_tree_id);
+    page.contents(content_id);
+    let mut resources = page.resources();
+    let mut fonts = resources.fonts();
+    fonts.pair(font, font_id);
+    fonts.pair(bold_font, bold_font_id);
+    fonts.finish();
+    resources.finish();
+    page.finish();
+
+    // Helvetica is one of the 14 standard fonts, so nothing needs embedding
+    pdf.type1_font(font_id).base_font(Name(b"Helvetica"));
+    pdf.type1_font(bold_font_id).base_font(Name(b"Helvetica-Bold"));
+
+    let mut content = Content::new();
+
+    // Title and rule
+    content.begin_text();
+    content.set_font(bold_font, 20.0);
+    content.next_line(72.0, 770.0);
+    content.show(Str(b"Certificate of Data Sanitization"));
+    content.end_text();
+    content.set_line_width(1.0);
+    content.move_to(72.0, 758.0);
+    content.line_to(523.0, 758.0);
+    content.stroke();
+
+    // Field rows; long values (signature, public key) wrap onto extra lines
+    let mut y = 725.0;
+    for (label, value) in certificate_rows(cert) {
+        generation.check()?;
+        content.begin_text();
+        content.set_font(bold_font, 11.0);
+        content.next_line(72.0, y);
+        content.show(Str(pdf_text(label).as_slice()));
+        content.end_text();
+
+        let value = pdf_text(&value);
+        for chunk in value.chunks(PDF_VALUE_CHARS_PER_LINE) {
+            content.begin_text();
+            content.set_font(font, 11.0);
+            content.next_line(200.0, y);
+            content.show(Str(chunk));
+            content.end_text();
+            y -= 15.0;
+        }
+        y -= 7.0;
+    }
+
+    // Footer
+    content.begin_text();
+    content.set_font(font, 9.0);
+    content.next_line(72.0, 72.0);
+    content.show(Str(
+        b"Generated by SecureWipe Wizard - OnlyParams, a division of Ciphracore Systems LLC",
+    ));
+    content.end_text();
+    content.begin_text();
+    content.set_font(font, 9.0);
+    content.next_line(72.0, 60.0);
+    content.show(Str(
+        b"The JSON certificate is the canonical record; this PDF is a printable view.",
+    ));
+    content.end_text();
+
+    pdf.stream(content_id, &content.finish());
+    Ok(pdf.finish())
+}
+
+/// Devices listed on each page of a batch report PDF
+const PDF_BATCH_DEVICES_PER_PAGE: usize = 11;
+
+/// Render a combined batch report as A4 pages listing each device and its certificate
+fn render_batch_report_pdf(
+    report: &BatchReport,
+    entries: &[BatchReportEntry],
+    generation: &GenerationToken,
+) -> Result<Vec<u8>, String> {
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let font_id = Ref::new(3);
+    let bold_font_id = Ref::new(4);
+    let font = Name(b"F1");
+    let bold_font = Name(b"F2");
+
+    let pages: Vec<&[BatchReportEntry]> = if entries.is_empty() {
+        vec![&[]]
+    } else {
+        entries.chunks(PDF_BATCH_DEVICES_PER_PAGE).collect()
+    };
+    // Each page is followed by its content stream
+    let page_ids: Vec<Ref> = (0..pages.len()).map(|i| Ref::new(5 + 2 * i as i32)).collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id)
+        .kids(page_ids.iter().copied())
+        .count(pages.len() as i32);
+    pdf.type1_font(font_id).base_font(Name(b"Helvetica"));
+    pdf.type1_font(bold_font_id).base_font(Name(b"Helvetica-Bold"));
+
+    let line = |content: &mut Content, f: Name, size: f32, x: f32, y: f32, text: &str| {
+        content.begin_text();
+        content.set_font(f, size);
+        content.next_line(x, y);
+        content.show(Str(pdf_text(text).as_slice()));
+        content.end_text();
+    };
+
+    for (i, devices) in pages.iter().enumerate() {
+        generation.check()?;
+        let content_id = Ref::new(6 + 2 * i as i32);
+        let mut page = pdf.page(page_ids[i]);
+        page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+        page.parent(page_tree_id);
+        page.contents(content_id);
+        let mut resources = page.resources();
+        let mut fonts = resources.fonts();
+        fonts.pair(font, font_id);
+        fonts.pair(bold_font, bold_font_id);
+        fonts.finish();
+        resources.finish();
+        page.finish();
+
+        let mut content = Content::new();
+        line(&mut content, bold_font, 20.0, 72.0, 770.0, "Batch Wipe Report");
+        let summary = format!(
+            "{} - finished {} - {} succeeded, {} failed, {} cancelled - page {} of {}",
+            report.batch_id,
+            format_utc_timestamp(report.finished_at),
+            report.succeeded,
+            report.failed,
+            report.cancelled,
+            i + 1,
+            pages.len()
+        );
+        line(&mut content, font, 9.0, 72.0, 752.0, &summary);
+        content.set_line_width(1.0);
+        content.move_to(72.0, 744.0);
+        content.line_to(523.0, 744.0);
+        content.stroke();
+
+        let mut y = 718.0;
+        for entry in devices.iter() {
+            let device = match (entry.model.as_str(), entry.serial.as_str()) {
+                ("", _) => entry.device_id.clone(),
+                (model, serial) => format!("{} (serial {})", model, serial),
+            };
+            line(&mut content, bold_font, 11.0, 72.0, y, &device);
+            line(&mut content, bold_font, 11.0, 440.0, y, &entry.result);
+            let certificate = entry.certificate.as_deref().unwrap_or("None (not wiped)");
+            line(&mut content, font, 9.0, 72.0, y - 14.0, &format!("Certificate: {}", certificate));
+            let times = match (&entry.started, &entry.finished) {
+                (Some(started), Some(finished)) => format!("{} to {}", started, finished),
+                _ => entry.message.clone(),
+            };
+            line(&mut content, font, 9.0, 72.0, y - 26.0, &times);
+            y -= 52.0;
+        }
+
+        line(
+            &mut content,
+            font,
+            9.0,
+            72.0,
+            60.0,
+            "Each device's signed certificate is the canonical record of its wipe.",
+        );
+        pdf.stream(content_id, &content.finish());
+    }
+    Ok(pdf.finish())
+}
+
+/// Export paths come from the frontend's save dialog; require an absolute path
+/// with the expected extension in an existing directory
+fn validate_export_path(path: &str, extension: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path);
+    if !path.is_absolute() {
+        return Err("Export path must be absolute.".to_string());
+    }
+    let has_extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().eq_ignore_ascii_case(extension))
+        .unwrap_or(false);
+    if !has_extension {
+        return Err(format!("Export path must end in .{}", extension));
+    }
+    match path.parent() {
+        Some(parent) if parent.is_dir() => Ok(path),
+        _ => Err("Export directory does not exist.".to_string()),
+    }
+}
+
+// ============================================================================
+// Batch Wipes
+// ============================================================================
+
+/// Devices to wipe one after another with a shared config, e.g. loaded from a JSON file
+/// like `{"devices": ["RF123456", "emulator-5554"], "config": {"mode": "quick", ...}}`.
+/// Instead of listing ids, `"select": "all"` or a glob such as `"select": "SM-S92*"`
+/// picks devices from those connected when the batch starts (see resolve_batch_selector).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchConfig {
+    #[serde(default)]
+    pub devices: Vec<String>,
+    #[serde(default)]
+    pub select: Option<String>, // "all", or a glob over adb ids, serials and models
+    pub config: WipeConfig,
+    #[serde(default)]
+    pub stop_on_failure: bool, // Skip the remaining devices after a failed wipe
+    #[serde(default)]
+    pub pause_between_devices: bool, // Wait for continue_batch before each next device
+}
+
+impl BatchConfig {
+    /// Check every device id and the shared config up front, so a bad entry fails the
+    /// batch before any device is touched. A selector is only checked for being usable;
+    /// it's resolved to devices when the batch starts.
+    fn validate(&self, chunk: ChunkSizeBounds) -> Result<BatchConfig, String> {
+        if let Some(select) = &self.select {
+            if !self.devices.is_empty() {
+                return Err("Batch config has both devices and select; use one.".to_string());
+            }
+            validate_batch_selector(select)?;
+            return Ok(BatchConfig {
+                devices: Vec::new(),
+                select: Some(select.trim().to_string()),
+                config: self.config.validate_and_clamp(chunk)?,
+                stop_on_failure: self.stop_on_failure,
+                pause_between_devices: self.pause_between_devices,
+            });
+        }
+        if self.devices.is_empty() {
+            return Err("Batch config lists no devices.".to_string());
+        }
+        let mut devices: Vec<String> = Vec::new();
+        for device_id in &self.devices {
+            let device_id = sanitize_device_id(device_id)?;
+            if devices.contains(&device_id) {
+                return Err(format!("Device {} is listed more than once.", device_id));
+            }
+            devices.push(device_id);
+        }
+
+        Ok(BatchConfig {
+            devices,
+            select: None,
+            config: self.config.validate_and_clamp(chunk)?,
+            stop_on_failure: self.stop_on_failure,
+            pause_between_devices: self.pause_between_devices,
+        })
+    }
+}
+
+/// A connected, authorized device as batch selectors see it
+#[derive(Debug, Clone, PartialEq)]
+struct DeviceCandidate {
+    id: String,
+    serial: String,
+    model: String,
+}
+
+fn validate_batch_selector(select: &str) -> Result<(), String> {
+    let select = select.trim();
+    let valid = !select.is_empty()
+        && select.len() <= 64
+        && select
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.:*? ".contains(c));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid batch selector: {}", select))
+    }
+}
+
+/// Case-insensitive glob with `*` (any run) and `?` (one character)
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None; // (pattern index after the last *, text index it matched up to)
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((after_star, matched)) => {
+                    p = after_star;
+                    t = matched + 1;
+                    backtrack = Some((after_star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Device ids a selector picks from the connected devices, in listing order: every device
+/// for "all", otherwise those whose adb id, serial or model matches the glob
+fn resolve_batch_selector(
+    select: &str,
+    connected: &[DeviceCandidate],
+) -> Result<Vec<String>, String> {
+    let select = select.trim();
+    let devices: Vec<String> = connected
+        .iter()
+        .filter(|d| {
+            select.eq_ignore_ascii_case("all")
+                || [&d.id, &d.serial, &d.model].iter().any(|field| glob_match(select, field))
+        })
+        .map(|d| d.id.clone())
+        .collect();
+    if devices.is_empty() {
+        return Err(format!(
+            "No connected device matches '{}' ({} connected).",
+            select,
+            connected.len()
+        ));
+    }
+    Ok(devices)
+}
+
+/// Authorized devices currently listed by adb, with their serial and model
+fn connected_device_candidates(long: bool) -> Result<Vec<DeviceCandidate>, String> {
+    let output = Command::new("adb")
+        .args(adb_devices_args(long))
+        .traced()
+        .output()
+        .map_err(|e| format!("Failed to list devices: {}", e))?;
+    let listed = parse_adb_devices_long(&String::from_utf8_lossy(&output.stdout));
+    Ok(listed
+        .into_iter()
+        .filter(|listing| listing.state == "device")
+        .map(|listing| DeviceCandidate {
+            serial: get_device_prop(&listing.id, "ro.serialno"),
+            model: listing
+                .model
+                .unwrap_or_else(|| get_device_prop(&listing.id, "ro.product.model")),
+            id: listing.id,
+        })
+        .collect())
+}
+
+/// Turn a selector batch into a device list and validate it like a listed one
+fn resolve_batch_devices(
+    batch: &BatchConfig,
+    chunk: ChunkSizeBounds,
+    connected: impl FnOnce() -> Result<Vec<DeviceCandidate>, String>,
+) -> Result<BatchConfig, String> {
+    let Some(select) = &batch.select else {
+        return batch.validate(chunk);
+    };
+    let devices = resolve_batch_selector(select, &connected()?)?;
+    BatchConfig {
+        devices,
+        select: None,
+        ..batch.clone()
+    }
+    .validate(chunk)
+}
+
+fn parse_batch_config(json: &str, chunk: ChunkSizeBounds) -> Result<BatchConfig, String> {
+    let batch: BatchConfig =
+        serde_json::from_str(json).map_err(|e| format!("Invalid batch config: {}", e))?;
+    batch.validate(chunk)
+}
+
+fn load_batch_config(path: &Path, chunk: ChunkSizeBounds) -> Result<BatchConfig, String> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read batch config {}: {}", path.display(), e))?;
+    parse_batch_config(&json, chunk)
+}
+
+/// Outcome of one device in a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchDeviceResult {
+    pub device_id: String,
+    pub success: bool,
+    pub skipped: bool,   // Not attempted (stop_on_failure)
+    pub cancelled: bool, // Cancelled by the operator, before or during its wipe
+    pub message: String,
+}
+
+/// Per-device results of a whole batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchReport {
+    #[serde(default)]
+    pub batch_id: String, // Names the saved report, and tags the batch's wipe sessions
+    #[serde(default)]
+    pub finished_at: u64, // Unix seconds
+    pub results: Vec<BatchDeviceResult>,
+    pub succeeded: usize,
+    pub failed: usize, // Including skipped devices
+    pub cancelled: usize,
+}
+
+/// Where each device of a batch stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchDeviceStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Skipped,
+    Cancelled,
+}
+
+/// Per-device status of the current (or last) batch, so a single device can be cancelled
+/// while the batch carries on with the rest
+#[derive(Default)]
+struct BatchState {
+    devices: Mutex<Vec<(String, BatchDeviceStatus)>>,
+    awaiting_next: Mutex<Option<String>>, // Device a paused batch is waiting to be swapped in
+    next_ready: Condvar,
+    batch_id: Mutex<String>,
+}
+
+impl BatchState {
+    /// Start tracking a new batch; only one runs at a time
+    fn begin(&self, devices: &[String]) -> Result<(), String> {
+        let mut tracked = self.devices.lock().unwrap();
+        let running = tracked.iter().any(|(_, status)| {
+            matches!(status, BatchDeviceStatus::Pending | BatchDeviceStatus::Running)
+        });
+        if running {
+            return Err("A batch is already running. Wait for it to finish.".to_string());
+        }
+        *tracked = devices
+            .iter()
+            .map(|id| (id.clone(), BatchDeviceStatus::Pending))
+            .collect();
+        *self.batch_id.lock().unwrap() = new_batch_id();
+        Ok(())
+    }
+
+    /// Id of the current (or last) batch; empty before the first one
+    fn batch_id(&self) -> String {
+        self.batch_id.lock().unwrap().clone()
+    }
+
+    fn status(&self, device_id: &str) -> Option<BatchDeviceStatus> {
+        let tracked = self.devices.lock().unwrap();
+        tracked.iter().find(|(id, _)| id == device_id).map(|(_, status)| *status)
+    }
+
+    fn set(&self, device_id: &str, status: BatchDeviceStatus) {
+        let mut tracked = self.devices.lock().unwrap();
+        if let Some(entry) = tracked.iter_mut().find(|(id, _)| id == device_id) {
+            entry.1 = status;
+        }
+    }
+
+    /// Mark a device running; false if it was cancelled first
+    fn start(&self, device_id: &str) -> bool {
+        let mut tracked = self.devices.lock().unwrap();
+        match tracked.iter_mut().find(|(id, _)| id == device_id) {
+            Some(entry) if entry.1 != BatchDeviceStatus::Pending => false,
+            Some(entry) => {
+                entry.1 = BatchDeviceStatus::Running;
+                true
+            }
+            None => true, // Untracked, so nothing can cancel it
+        }
+    }
+
+    /// Cancel a pending device, or a running one if `stop_running` aborts its wipe. The
+    /// status is only changed once the cancel has taken effect, so a wipe that finishes
+    /// first keeps its own outcome. Returns the status it had and what `stop_running` said.
+    fn cancel(
+        &self,
+        device_id: &str,
+        stop_running: impl FnOnce() -> Result<String, String>,
+    ) -> Result<(BatchDeviceStatus, Option<String>), String> {
+        let mut tracked = self.devices.lock().unwrap();
+        let entry = tracked
+            .iter_mut()
+            .find(|(id, _)| id == device_id)
+            .ok_or_else(|| format!("Device {} is not part of the batch.", device_id))?;
+        match entry.1 {
+            BatchDeviceStatus::Pending => {
+                entry.1 = BatchDeviceStatus::Cancelled;
+                // No point waiting for a device that won't be wiped
+                let mut awaiting = self.awaiting_next.lock().unwrap();
+                if awaiting.as_deref() == Some(device_id) {
+                    *awaiting = None;
+                    self.next_ready.notify_all();
+                }
+                Ok((BatchDeviceStatus::Pending, None))
+            }
+            // Held locked while stopping, so run_batch sees the wipe's end and its cause together
+            BatchDeviceStatus::Running => {
+                let stopped = stop_running()?;
+                entry.1 = BatchDeviceStatus::Cancelled;
+                Ok((BatchDeviceStatus::Running, Some(stopped)))
+            }
+            done => {
+                let done = format!("{:?}", done).to_lowercase();
+                Err(format!("Device {} has already finished ({}).", device_id, done))
+            }
+        }
+    }
+
+    /// Block a paused batch until continue_batch says `next` is connected (or it's cancelled)
+    fn await_next(&self, next: &str) {
+        *self.awaiting_next.lock().unwrap() = Some(next.to_string());
+        // cancel() may have run just before the wait was registered
+        let cancelled = self.status(next) == Some(BatchDeviceStatus::Cancelled);
+        let mut awaiting = self.awaiting_next.lock().unwrap();
+        if cancelled {
+            *awaiting = None;
+        }
+        while awaiting.is_some() {
+            awaiting = self.next_ready.wait(awaiting).unwrap();
+        }
+    }
+
+    /// Let a paused batch carry on; returns the device it was waiting for
+    fn resume(&self) -> Result<String, String> {
+        let next = self
+            .awaiting_next
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| "The batch isn't waiting for a device.".to_string())?;
+        self.next_ready.notify_all();
+        Ok(next)
+    }
+}
+
+/// Wipe each device in turn with `wipe_one`, reporting each as a `batch-device` event and
+/// the totals as `batch-complete`. The wipes themselves emit their usual events. Devices
+/// cancelled in `batch_state` are passed over (or, mid-wipe, recorded as cancelled).
+/// With `pause_between_devices`, each device after the first waits for continue_batch,
+/// announced by a `batch-awaiting-next` event, so one cable can serve the whole batch.
+fn run_batch(
+    batch: &BatchConfig,
+    batch_state: &BatchState,
+    events: &dyn EventSink,
+    wipe_one: &mut dyn FnMut(&str, &WipeConfig) -> Result<String, String>,
+) -> BatchReport {
+    let mut results = Vec::new();
+    let mut stopped = false;
+    let mut previous: Option<&str> = None;
+    for device_id in &batch.devices {
+        let mut result = BatchDeviceResult {
+            device_id: device_id.clone(),
+            success: false,
+            skipped: false,
+            cancelled: false,
+            message: String::new(),
+        };
+        let cancelled = batch_state.status(device_id) == Some(BatchDeviceStatus::Cancelled);
+        if let Some(previous) = previous.filter(|_| batch.pause_between_devices) {
+            if !stopped && !cancelled {
+                emit_event(
+                    events,
+                    "batch-awaiting-next",
+                    serde_json::json!({ "device_id": device_id, "previous": previous }),
+                );
+                batch_state.await_next(device_id);
+            }
+        }
+        if !stopped && batch_state.start(device_id) {
+            previous = Some(device_id);
+            let outcome = wipe_one(device_id, &batch.config);
+            // Cancelling aborts the wipe, which isn't a failure of the batch
+            if batch_state.status(device_id) == Some(BatchDeviceStatus::Cancelled) {
+                result.cancelled = true;
+                result.message = "Cancelled during the wipe.".to_string();
+            } else {
+                stopped = batch.stop_on_failure && outcome.is_err();
+                let status = match outcome {
+                    Ok(_) => BatchDeviceStatus::Succeeded,
+                    Err(_) => BatchDeviceStatus::Failed,
+                };
+                batch_state.set(device_id, status);
+                result.success = outcome.is_ok();
+                result.message = outcome.unwrap_or_else(|e| e);
+            }
+        } else if batch_state.status(device_id) == Some(BatchDeviceStatus::Cancelled) {
+            result.cancelled = true;
+            result.message = "Cancelled before it started.".to_string();
+        } else {
+            batch_state.set(device_id, BatchDeviceStatus::Skipped);
+            result.skipped = true;
+            result.message = "Skipped after an earlier failure.".to_string();
+        }
+        emit_event(events, "batch-device", &result);
+        results.push(result);
+    }
+
+    let succeeded = results.iter().filter(|r| r.success).count();
+    let cancelled = results.iter().filter(|r| r.cancelled).count();
+    let report = BatchReport {
+        batch_id: batch_state.batch_id(),
+        finished_at: unix_timestamp(),
+        failed: results.len() - succeeded - cancelled,
+        succeeded,
+        cancelled,
+        results,
+    };
+    emit_event(events, "batch-complete", &report);
+    report
+}
+
+/// Finished batch reports, one JSON file per batch, kept for export_batch_report
+struct BatchStore {
+    dir: PathBuf,
+}
+
+impl BatchStore {
+    fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn save(&self, report: &BatchReport) -> Result<(), String> {
+        let batch_id = sanitize_session_id(&report.batch_id)?;
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create batch directory: {}", e))?;
+        let json = serde_json::to_string_pretty(report)
+            .map_err(|e| format!("Failed to serialize batch report: {}", e))?;
+        fs::write(self.dir.join(format!("{}.json", batch_id)), json)
+            .map_err(|e| format!("Failed to save batch report: {}", e))
+    }
+
+    fn load(&self, batch_id: &str) -> Result<BatchReport, String> {
+        let batch_id = sanitize_session_id(batch_id)?;
+        let json = fs::read_to_string(self.dir.join(format!("{}.json", batch_id)))
+            .map_err(|_| format!("Batch {} not found.", batch_id))?;
+        serde_json::from_str(&json).map_err(|e| format!("Corrupt batch report: {}", e))
+    }
+}
+
+/// One device's line in a combined batch report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchReportEntry {
+    pub device_id: String,
+    pub model: String,
+    pub serial: String,
+    pub result: String,              // "succeeded", "failed", "skipped" or "cancelled"
+    pub certificate: Option<String>, // Session id its certificate is generated from
+    pub started: Option<String>,     // UTC; None if its wipe never started
+    pub finished: Option<String>,
+    pub message: String,
+}
+
+/// Join a batch's results with the sessions its wipes recorded
+fn batch_report_entries(report: &BatchReport, sessions: &[WipeSession]) -> Vec<BatchReportEntry> {
+    report
+        .results
+        .iter()
+        .map(|result| {
+            let session = sessions.iter().rev().find(|session| {
+                session.batch_id.as_deref() == Some(report.batch_id.as_str())
+                    && session.device.id == result.device_id
+            });
+            let outcome = match result {
+                r if r.success => "succeeded",
+                r if r.cancelled => "cancelled",
+                r if r.skipped => "skipped",
+                _ => "failed",
+            };
+            BatchReportEntry {
+                device_id: result.device_id.clone(),
+                model: session.map(|s| s.device.model.clone()).unwrap_or_default(),
+                serial: session.map(|s| s.serial.clone()).unwrap_or_default(),
+                result: outcome.to_string(),
+                certificate: session.map(|s| s.session_id.clone()),
+                started: session.map(|s| format_utc_timestamp(s.started_at)),
+                finished: session.map(|s| format_utc_timestamp(s.finished_at)),
+                message: result.message.clone(),
+            }
+        })
+        .collect()
+}
+
+fn batch_report_to_csv(entries: &[BatchReportEntry]) -> String {
+    let mut csv =
+        "device_id,model,serial,result,certificate,started,finished,message\n".to_string();
+    for e in entries {
+        let none = String::new();
+        let fields = [
+            &e.device_id,
+            &e.model,
+            &e.serial,
+            &e.result,
+            e.certificate.as_ref().unwrap_or(&none),
+            e.started.as_ref().unwrap_or(&none),
+            e.finished.as_ref().unwrap_or(&none),
+            &e.message,
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn batch_report_to_json(
+    report: &BatchReport,
+    entries: &[BatchReportEntry],
+) -> Result<String, String> {
+    let document = serde_json::json!({
+        "batch_id": report.batch_id,
+        "finished": format_utc_timestamp(report.finished_at),
+        "succeeded": report.succeeded,
+        "failed": report.failed,
+        "cancelled": report.cancelled,
+        "devices": entries
+    });
+    serde_json::to_string_pretty(&document)
+        .map_err(|e| format!("Failed to serialize batch report: {}", e))
+}
+
+/// Cancel one device of the running batch: a pending device is passed over, a running
+/// one has its wipe aborted (or its start cancelled). The rest of the batch is unaffected.
+fn abort_device_in_batch_with(
+    batch_state: &BatchState,
+    state: &WipeState,
+    audit_log: &AuditLog,
+    events: &dyn EventSink,
+    runner: &dyn CommandRunner,
+    device_id: &str,
+) -> Result<String, String> {
+    let (previous, stopped) = batch_state.cancel(device_id, || {
+        // run_wipe claims the device first thing, so a running device without a claim
+        // has just finished (or is a moment from starting)
+        if !state.is_claimed_for(device_id) {
+            return Err(format!("The wipe on {} isn't running; nothing was aborted.", device_id));
+        }
+        abort_wipe_with(state, audit_log, events, runner, AbortBehavior::CleanupOnly)
+    })?;
+    let message = stopped.unwrap_or_else(|| format!("Device {} will be skipped.", device_id));
+    emit_event(
+        events,
+        "batch-device-cancelled",
+        serde_json::json!({
+            "device_id": device_id,
+            "was_running": previous == BatchDeviceStatus::Running
+        }),
+    );
+    Ok(message)
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Check if ADB is installed and get version info
+#[tauri::command]
+async fn check_adb_status(adb_server: State<'_, AdbServer>) -> Result<AdbStatus, String> {
+    // Check if ADB is installed
+    let version_output = Command::new("adb")
+        .arg("version")
+        .traced()
+        .output();
+
+    match version_output {
+        Ok(output) if output.status.success() => {
+            // Start the server explicitly so we know whether we own it. A server that won't
+            // start is reported with the rest of the status rather than hiding it.
+            let port = adb_server_port_override();
+            let server_error = adb_server.ensure_started(&SystemRunner, port.as_deref()).err();
+
+            let version_str = String::from_utf8_lossy(&output.stdout);
+            let version = version_str
+                .lines()
+                .next()
+                .map(|s| s.to_string());
+
+            // Count connected devices
+            let devices_output = Command::new("adb")
+                .arg("devices")
+                .traced()
+                .output()
+                .map_err(|e| format!("Failed to list devices: {}", e))?;
+
+            let devices_str = String::from_utf8_lossy(&devices_output.stdout);
+            let devices = dedupe_devices_by_serial(parse_adb_devices(&devices_str), |id| {
+                get_device_prop(id, "ro.serialno")
+            });
+
+            Ok(AdbStatus {
+                installed: true,
+                version,
+                devices_connected: devices.len() as u32,
+                recovery_devices: parse_recovery_devices(&devices_str)
+                    .into_iter()
+                    .map(|(id, _)| id)
+                    .collect(),
+                server_conflict: adb_server.conflict(),
+                server_error,
+            })
+        }
+        _ => Ok(AdbStatus {
+            installed: false,
+            version: None,
+            devices_connected: 0,
+            recovery_devices: Vec::new(),
+            server_conflict: None,
+            server_error: None,
+        }),
+    }
+}
+
+/// Characters of the serial the user may type instead of the model
+const CONFIRMATION_SERIAL_CHARS: usize = 4;
+
+/// Whether a typed confirmation names this device: its model, or the last 4 characters of
+/// its serial, ignoring case and surrounding spaces
+fn check_confirmation_token(token: Option<&str>, model: &str, serial: &str) -> Result<(), String> {
+    let token = token.map(str::trim).unwrap_or_default();
+    if token.is_empty() {
+        return Err(format!(
+            "Type the device model ({}) or the last {} characters of its serial to confirm.",
+            model, CONFIRMATION_SERIAL_CHARS
+        ));
+    }
+    let serial: Vec<char> = serial.chars().collect();
+    let serial_tail = serial
+        .len()
+        .checked_sub(CONFIRMATION_SERIAL_CHARS)
+        .map(|start| serial[start..].iter().collect::<String>());
+    let matches = |expected: &str| !expected.is_empty() && token.eq_ignore_ascii_case(expected);
+    if matches(model) || serial_tail.is_some_and(|tail| matches(&tail)) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Confirmation '{}' doesn't match the connected device ({}). Nothing was changed.",
+            token, model
+        ))
+    }
+}
+
+/// The typed-confirmation interlock for destructive commands, when settings turn it on
+fn confirm_destructive_with(
+    runner: &dyn CommandRunner,
+    settings: &AppSettings,
+    device_id: &str,
+    token: Option<&str>,
+) -> Result<(), String> {
+    if !settings.require_typed_confirmation {
+        return Ok(());
+    }
+    let prop = |name: &str| {
+        runner
+            .adb_shell(device_id, &["getprop", name])
+            .ok()
+            .filter(|out| out.success)
+            .map(|out| out.stdout.trim().to_string())
+            .unwrap_or_default()
+    };
+    check_confirmation_token(token, &prop("ro.product.model"), &prop("ro.serialno"))
+}
+
+/// Check for connected devices and return device info
+#[tauri::command]
+async fn check_adb(
+    state: State<'_, WipeState>,
+    known: State<'_, KnownDevices>,
+    settings: State<'_, SettingsState>,
+) -> Result<DeviceInfo, String> {
+    // Run `adb devices` to list connected devices
+    let output = Command::new("adb")
+        .args(adb_devices_args(settings.get().long_device_listing))
+        .traced()
+        .output()
+        .map_err(|e| format!("Failed to run ADB: {}. Is ADB installed?", e))?;
+
+    if !output.status.success() {
+        return Err("ADB command failed. Please check ADB installation.".to_string());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let devices = dedupe_devices_by_serial(parse_adb_devices(&stdout), |id| {
+        get_device_prop(id, "ro.serialno")
+    });
+
+    if devices.is_empty() {
+        let listed = parse_adb_device_states(&stdout);
+        if let Some(error) = no_permissions_error(&listed, std::env::consts::OS) {
+            return Err(error);
+        }
+        if let Some(error) = recovery_mode_error(&listed) {
+            return Err(error);
+        }
+        return Err(
+            "No device connected. Please:\n\
+             1. Connect your Android device via USB\n\
+             2. Enable USB Debugging in Developer Options\n\
+             3. Authorize this computer on your phone"
+                .to_string(),
+        );
+    }
+
+    // Use first connected device
+    let listings = parse_adb_devices_long(&stdout);
+    let listing = listings.iter().find(|listing| listing.id == devices[0].0);
+    let device_info = read_listed_device_info(&devices[0].0, listing);
+
+    if device_info.model.is_empty() {
+        return Err("Connected device not responding. Please unlock your phone and try again.".to_string());
+    }
+    remember_serial(&known, &device_info.id);
+    // Detecting the device afresh starts a new workflow on it
+    state.end_workflow(&device_info.id);
+
+    Ok(device_info)
+}
+
+/// Current adb id of the physical device the UI knew as `old_id`, matched by the serial
+/// remembered for it (or the serial in the id itself)
+fn refresh_device_id(
+    known: &KnownDevices,
+    old_id: &str,
+    listed: &[(String, String)],
+    serial_of: impl Fn(&str) -> String,
+) -> Result<String, String> {
+    let serial = known.serial_of_id(old_id);
+    resolve_device_id(old_id, serial.as_deref(), listed, serial_of)
+}
+
+/// After unplugging/replugging or a mode switch: find the device the UI was showing under
+/// its new transport id and return fresh info for it
+#[tauri::command]
+async fn refresh_device(
+    known: State<'_, KnownDevices>,
+    old_id: String,
+) -> Result<DeviceInfo, String> {
+    let old_id = sanitize_device_id(&old_id)?;
+    let output = Command::new("adb")
+        .arg("devices")
+        .traced()
+        .output()
+        .map_err(|e| format!("Failed to list devices: {}", e))?;
+    let listed = parse_adb_devices(&String::from_utf8_lossy(&output.stdout));
+
+    let device_id =
+        refresh_device_id(&known, &old_id, &listed, |id| get_device_prop(id, "ro.serialno"))?;
+    let device_info = read_device_info(&device_id);
+    if device_info.model.is_empty() {
+        return Err("Device found again but not responding. Unlock it and try again.".to_string());
+    }
+    remember_serial(&known, &device_info.id);
+    Ok(device_info)
+}
+
+/// Remember a device by its serial, falling back to the adb id. Returns the serial.
+fn remember_serial(known: &KnownDevices, device_id: &str) -> String {
+    let serial = get_device_prop(device_id, "ro.serialno");
+    let serial = if serial.is_empty() { device_id.to_string() } else { serial };
+    known.remember(&serial, device_id);
+    serial
+}
+
+/// Remember an authorized device so it's recognised if it reconnects under a new id.
+/// Returns its serial.
+#[tauri::command]
+async fn remember_device(
+    known: State<'_, KnownDevices>,
+    device_id: String,
+) -> Result<String, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    Ok(remember_serial(&known, &device_id))
+}
+
+/// Get storage information from connected device
+#[tauri::command]
+async fn get_storage_info(
+    device_id: String,
+    mount_path: Option<String>,
+    cross_check: Option<bool>,
+) -> Result<StorageInfo, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    let mount_path = validate_device_path(mount_path.as_deref().unwrap_or(DEFAULT_MOUNT_PATH))?;
+    let mut info = read_storage_info_with(&SystemRunner, &device_id, &mount_path)?;
+    if cross_check.unwrap_or(false) {
+        info.statfs_check = cross_check_statfs(&SystemRunner, &device_id, &mount_path, &info);
+    }
+    Ok(info)
+}
+
+/// Look for a physical SD card with `sm list-volumes`, falling back to the /storage
+/// listing where `sm` isn't available. A mounted card is preferred over an unmounted one.
+fn detect_sdcard_with(runner: &dyn CommandRunner, device_id: &str) -> Result<SdCardInfo, String> {
+    let none = SdCardInfo {
+        present: false,
+        mounted: false,
+        path: None,
+        volume_id: None,
+    };
+    let volumes = runner.adb_shell(device_id, &["sm", "list-volumes"])?;
+    if volumes.success && !volumes.stdout.trim().is_empty() {
+        let mut cards = parse_sm_volumes(&volumes.stdout);
+        cards.sort_by_key(|card| !card.mounted);
+        return Ok(cards.into_iter().next().unwrap_or(none));
+    }
+
+    let listing = runner.adb_shell(device_id, &["ls", "/storage"])?;
+    if !listing.success {
+        return Err(format!("Could not list storage volumes: {}", listing.stderr.trim()));
+    }
+    Ok(parse_storage_listing(&listing.stdout).unwrap_or(none))
+}
+
+/// Whether a physical SD card is inserted and where it is mounted, to decide whether it
+/// needs wiping as well as the internal storage
+#[tauri::command]
+async fn detect_sdcard(device_id: String) -> Result<SdCardInfo, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    detect_sdcard_with(&SystemRunner, &device_id)
+}
+
+/// Read statfs for the same path and compare it with df; None if `stat -f` isn't usable
+fn cross_check_statfs(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    mount_path: &str,
+    df: &StorageInfo,
+) -> Option<StorageCrossCheck> {
+    let output = runner.adb_shell(device_id, &["stat", "-f", mount_path]).ok()?;
+    let (total_mb, available_mb) = parse_stat_f_output(&output.stdout).filter(|_| output.success)?;
+    Some(reconcile_storage(df, total_mb, available_mb))
+}
+
+const SNAPSHOT_LABEL_MAX_CHARS: usize = 32;
+
+/// Snapshot labels go into certificates, so keep them short and plain
+fn validate_snapshot_label(label: &str) -> Result<String, String> {
+    let label = label.trim().to_lowercase();
+    let plain = label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if label.is_empty() || label.chars().count() > SNAPSHOT_LABEL_MAX_CHARS || !plain {
+        return Err(format!(
+            "Invalid snapshot label. Use up to {} letters, digits, '-' or '_'.",
+            SNAPSHOT_LABEL_MAX_CHARS
+        ));
+    }
+    Ok(label)
+}
+
+/// Per-phase totals, in the order phases were first entered. Each phase lasts until the
+/// next transition; the last one until `ended_at_ms`.
+fn phase_durations(transitions: &[PhaseTransition], ended_at_ms: u64) -> Vec<PhaseDuration> {
+    let mut durations: Vec<PhaseDuration> = Vec::new();
+    for (i, transition) in transitions.iter().enumerate() {
+        let until = transitions.get(i + 1).map_or(ended_at_ms, |next| next.at_ms);
+        let spent = until.saturating_sub(transition.at_ms);
+        match durations.iter_mut().find(|d| d.phase == transition.phase) {
+            Some(total) => total.duration_ms += spent,
+            None => durations.push(PhaseDuration {
+                phase: transition.phase.clone(),
+                duration_ms: spent,
+            }),
+        }
+    }
+    durations
+}
+
+/// One line per report, e.g. "writing 12m 4s, verifying 2s, cleanup 1s"
+fn describe_phase_durations(durations: &[PhaseDuration]) -> String {
+    if durations.is_empty() {
+        return "Not recorded".to_string();
+    }
+    durations
+        .iter()
+        .map(|d| {
+            let secs = d.duration_ms / 1000;
+            match secs / 60 {
+                0 => format!("{} {}s", d.phase, secs),
+                mins => format!("{} {}m {}s", d.phase, mins, secs % 60),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// One line per report: free space at each snapshot, e.g.
+/// "before 40960 MB free, during 12 MB free, after 40958 MB free"
+fn describe_storage_snapshots(snapshots: &[StorageSnapshot]) -> String {
+    if snapshots.is_empty() {
+        return "Not recorded".to_string();
+    }
+    snapshots
+        .iter()
+        .map(|s| format!("{} {} MB free", s.label, s.storage.available_mb))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Record the device's storage under a label. Snapshots are kept with the device's next
+/// wipe session: take "before" ahead of the wipe and "during" while it fills the free
+/// space. The wipe itself adds "before" (if none was taken) and "after" once its script
+/// has exited and cleaned up.
+#[tauri::command]
+async fn snapshot_storage(
+    state: State<'_, WipeState>,
+    device_id: String,
+    label: String,
+) -> Result<StorageSnapshot, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    let label = validate_snapshot_label(&label)?;
+    let snapshot = StorageSnapshot {
+        label,
+        taken_at: unix_timestamp(),
+        storage: read_storage_info(&device_id)?,
+    };
+    state.add_snapshot(&device_id, snapshot.clone());
+    Ok(snapshot)
+}
+
+/// Filesystem reported by get_storage_info when no mount path is given
+const DEFAULT_MOUNT_PATH: &str = "/sdcard";
+
+/// Sync the device, then run df on its shared storage. The figures go into wipe records
+/// (coverage, snapshots), so they must not count writes still in the page cache.
+fn read_storage_info(device_id: &str) -> Result<StorageInfo, String> {
+    read_synced_storage_info_with(&SystemRunner, device_id, DEFAULT_MOUNT_PATH)
+}
+
+/// Flush the device's cached writes with `sync`, then read df, so free space reflects what
+/// was actually committed rather than what is still sitting in the page cache. A failed
+/// sync doesn't stop the reading; df is still the best figure available.
+fn read_synced_storage_info_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    mount_path: &str,
+) -> Result<StorageInfo, String> {
+    let _ = runner.adb_shell(device_id, &["sync"]);
+    read_storage_info_with(runner, device_id, mount_path)
+}
+
+fn read_storage_info_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    mount_path: &str,
+) -> Result<StorageInfo, String> {
+    // Note: Don't use -m flag - not supported on all Android devices (e.g., Samsung)
+    // Default output is 1K-blocks which we convert in parse_df_output
+    let output = runner
+        .adb_shell(device_id, &["df", mount_path])
+        .map_err(|e| format!("Failed to get storage info: {}", e))?;
+
+    if !output.success {
+        return Err("Failed to read storage info. Device may be locked.".to_string());
+    }
+
+    let mut info = parse_df_output(&output.stdout)?;
+    info.physical_capacity_bytes = read_physical_capacity(runner, device_id);
+    info.fs_type = read_fs_type(runner, device_id, mount_path);
+    info.fs_caveat = info.fs_type.as_deref().and_then(fs_type_caveat).map(str::to_string);
+    Ok(info)
+}
+
+/// Filesystem type mounted at `mount_point`, from `mount` output in either toybox form
+/// (`<dev> on <dir> type <fs> (<opts>)`) or /proc/mounts form (`<dev> <dir> <fs> <opts> 0 0`).
+/// The last entry wins, as a later mount shadows an earlier one.
+fn parse_mount_fs_type(mount_output: &str, mount_point: &str) -> Option<String> {
+    mount_output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [_, "on", dir, "type", fs, ..] => Some((*dir, *fs)),
+                [_, dir, fs, ..] => Some((*dir, *fs)),
+                _ => None,
+            }
+        })
+        .filter(|(dir, _)| *dir == mount_point)
+        .map(|(_, fs)| fs.to_string())
+        .next_back()
+}
+
+/// The mount whose filesystem actually holds a path's data. Emulated shared storage is a
+/// FUSE/sdcardfs view of /data/media, so its own mount only ever says "fuse".
+fn backing_mount_point(path: &str) -> &str {
+    match check_user_data_path(path) {
+        Ok(_) => "/data",
+        Err(_) => path,
+    }
+}
+
+fn read_fs_type(runner: &dyn CommandRunner, device_id: &str, path: &str) -> Option<String> {
+    let output = runner.adb_shell(device_id, &["mount"]).ok().filter(|out| out.success)?;
+    parse_mount_fs_type(&output.stdout, backing_mount_point(path))
+}
+
+/// f2fs is log-structured: an overwrite goes to fresh blocks and the old ones are only
+/// reclaimed by garbage collection, so filling free space is a weaker guarantee there
+fn fs_type_caveat(fs_type: &str) -> Option<&'static str> {
+    match fs_type {
+        "f2fs" => Some(
+            "f2fs writes changes to new blocks, so old data may survive until garbage \
+             collection reclaims it. Rely on encryption plus factory reset rather than the \
+             overwrite alone.",
+        ),
+        _ => None,
+    }
+}
+
+/// Wipe scripts shipped with the app
+const WIPE_SCRIPTS: [&str; 2] = ["quick_wipe.sh", "full_wipe.sh"];
+
+/// Find the directory containing a bundled script - check multiple locations
+fn find_scripts_dir(script: &str) -> Result<PathBuf, String> {
+    let exe_path = std::env::current_exe()
+        .map_err(|e| format!("Failed to get exe path: {}", e))?;
+
+    let possible_paths = vec![
+        exe_path.parent().unwrap().join("scripts"),
+        exe_path.parent().unwrap().join("../Resources/scripts"),
+        std::path::PathBuf::from("scripts"),
+    ];
+
+    find_script_in(possible_paths, script)
+}
+
+/// First candidate directory holding the script, which must also be readable: bash is
+/// given the path, so a script that exists but can't be read would otherwise surface as
+/// a confusing failure once the wipe starts
+fn find_script_in(candidates: Vec<PathBuf>, script: &str) -> Result<PathBuf, String> {
+    let dir = candidates
+        .into_iter()
+        .find(|p| p.join(script).exists())
+        .ok_or_else(|| {
+            "Scripts directory not found. Please reinstall the application.".to_string()
+        })?;
+    check_script_readable(&dir.join(script))?;
+    Ok(dir)
+}
+
+fn check_script_readable(path: &Path) -> Result<(), String> {
+    let not_readable = |e: std::io::Error| {
+        format!(
+            "Wipe script found but not readable/executable at {}: {}. Check its permissions \
+             and that the install directory isn't mounted noexec.",
+            path.display(),
+            e
+        )
+    };
+    // Reading a byte also catches a directory in the script's place, which opens fine
+    let mut file = fs::File::open(path).map_err(not_readable)?;
+    std::io::Read::read(&mut file, &mut [0u8; 1]).map_err(not_readable)?;
+    Ok(())
+}
+
+/// Windows `BELOW_NORMAL_PRIORITY_CLASS` process creation flag
+const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+
+/// How the wipe script is launched on the host
+#[derive(Debug, Clone, PartialEq)]
+enum HostPriority {
+    Normal,              // Not requested, or nothing available to lower it with
+    Prefix(Vec<String>), // Run through these wrappers, e.g. nice and ionice
+    CreationFlags(u32),  // Windows priority class
+}
+
+/// Pick a way to lower the wipe's CPU/IO priority on `os` (as in std::env::consts::OS),
+/// using only the wrappers `available` finds. Anything else runs at normal priority.
+fn host_priority(os: &str, low_priority: bool, available: impl Fn(&str) -> bool) -> HostPriority {
+    if !low_priority {
+        return HostPriority::Normal;
+    }
+    if os == "windows" {
+        return HostPriority::CreationFlags(BELOW_NORMAL_PRIORITY_CLASS);
+    }
+
+    let mut prefix: Vec<&str> = Vec::new();
+    if available("nice") {
+        prefix.extend(["nice", "-n", "10"]);
+    }
+    // Lowest best-effort level rather than the idle class, which can starve the wipe
+    if os == "linux" && available("ionice") {
+        prefix.extend(["ionice", "-c", "2", "-n", "7"]);
+    }
+    if prefix.is_empty() {
+        HostPriority::Normal
+    } else {
+        HostPriority::Prefix(prefix.into_iter().map(String::from).collect())
+    }
+}
+
+/// Whether `program` is an executable file on the host's PATH
+fn on_host_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+/// Where wipe scripts run. The scripts directory may be on a read-only volume (AppImage,
+/// mounted DMG), and the scripts write their log (phone_wipe.log) to the working directory.
+/// A fresh, randomly named directory only this user can open, made once per app run, so
+/// no other local user can pre-create it or plant a symlink in place of the log.
+fn script_work_dir() -> Result<PathBuf, String> {
+    static WORK_DIR: OnceLock<PathBuf> = OnceLock::new();
+    if let Some(dir) = WORK_DIR.get() {
+        return Ok(dir.clone());
+    }
+
+    let mut suffix = [0u8; 8];
+    getrandom::getrandom(&mut suffix).map_err(|e| format!("Failed to name work dir: {}", e))?;
+    let dir = std::env::temp_dir().join(format!("securewipe-wizard-{}", hex::encode(suffix)));
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    // Not create_all: an existing directory of that name is someone else's
+    builder
+        .create(&dir)
+        .map_err(|e| format!("Failed to create working directory {}: {}", dir.display(), e))?;
+    Ok(WORK_DIR.get_or_init(|| dir).clone())
+}
+
+/// `bash <scripts_dir>/<script> <args>` run from `work_dir`, with a cleared environment
+/// apart from PATH (the script needs it to find adb)
+fn wipe_script_command(
+    priority: &HostPriority,
+    scripts_dir: &Path,
+    script: &str,
+    script_args: &[String],
+    work_dir: &Path,
+) -> Command {
+    let mut cmd = prioritized_command("bash", priority);
+    cmd.current_dir(work_dir)
+        .arg(scripts_dir.join(script))
+        .args(script_args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // Its own process group, so stopping the wipe can kill exactly this script and its adb
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+
+    cmd.env_clear();
+    if let Ok(path) = std::env::var("PATH") {
+        cmd.env("PATH", path);
+    }
+    cmd
+}
+
+/// Command for `program`, launched at `priority`
+fn prioritized_command(program: &str, priority: &HostPriority) -> Command {
+    match priority {
+        HostPriority::Prefix(prefix) => {
+            let mut cmd = Command::new(&prefix[0]);
+            cmd.args(&prefix[1..]).arg(program);
+            cmd
+        }
+        HostPriority::CreationFlags(_flags) => {
+            #[allow(unused_mut)]
+            let mut cmd = Command::new(program);
+            #[cfg(windows)]
+            {
+                use std::os::windows::process::CommandExt;
+                cmd.creation_flags(*_flags);
+            }
+            cmd
+        }
+        HostPriority::Normal => Command::new(program),
+    }
+}
+
+/// Script capabilities, probed once per script and kept for the life of the app
+struct ScriptCapabilityCache {
+    entries: Mutex<Vec<ScriptCapabilities>>,
+}
+
+impl ScriptCapabilityCache {
+    fn new() -> Self {
+        Self {
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Cached capabilities for a script, running `probe` on first use
+    fn get_or_probe(
+        &self,
+        script: &str,
+        probe: impl FnOnce() -> Result<ScriptCapabilities, String>,
+    ) -> Result<ScriptCapabilities, String> {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(caps) = entries.iter().find(|c| c.script == script) {
+            return Ok(caps.clone());
+        }
+        let caps = probe()?;
+        entries.push(caps.clone());
+        Ok(caps)
+    }
+}
+
+/// Run `<script> --help` and parse the flags it lists
+fn probe_script_capabilities(
+    runner: &dyn CommandRunner,
+    script: &str,
+) -> Result<ScriptCapabilities, String> {
+    let path = find_scripts_dir(script)?.join(script);
+    let output = runner.run("bash", &[path.to_string_lossy().into_owned(), "--help".to_string()])?;
+    if !output.success {
+        return Err(format!("{} --help failed: {}", script, output.stderr.trim()));
+    }
+    Ok(parse_script_help(script, &output.stdout))
+}
+
+/// Arguments for a wipe script run, and whether raw (unbuffered) streaming is on. `--raw`
+/// is only passed to scripts known to support it; older ones reject unknown flags.
+fn wipe_script_args(
+    device_id: &str,
+    config: &WipeConfig,
+    capabilities: Option<&ScriptCapabilities>,
+) -> (Vec<String>, bool) {
+    let mut args = vec![
+        "-d".to_string(),
+        device_id.to_string(),
+        "-p".to_string(),
+        config.passes.to_string(),
+        "-y".to_string(), // Auto-confirm
+    ];
+
+    let raw_streaming = capabilities.is_some_and(|caps| caps.supports("--raw"));
+    if raw_streaming {
+        args.push("--raw".to_string()); // Real-time streaming (no pipe buffering)
+    }
+    if let (true, Some(size_mb)) = (config.mode == "quick", config.size_mb) {
+        args.extend(["-s".to_string(), size_mb.to_string()]);
+    }
+    if config.keep_temp_files {
+        args.push("--keep-files".to_string());
+    }
+
+    (args, raw_streaming)
+}
+
+/// Flags supported by each bundled wipe script
+#[tauri::command]
+async fn get_script_capabilities(
+    cache: State<'_, ScriptCapabilityCache>,
+) -> Result<Vec<ScriptCapabilities>, String> {
+    WIPE_SCRIPTS
+        .iter()
+        .map(|script| {
+            cache.get_or_probe(script, || probe_script_capabilities(&SystemRunner, script))
+        })
+        .collect()
+}
+
+/// Directory the wipe scripts fill with random data
+const WIPE_TEMP_DIR: &str = "/sdcard/wipe_temp";
+
+/// Mounts the wipe may write into. Anything else is a system partition (or unknown), and
+/// filling it could brick the device.
+const USER_DATA_ROOTS: [&str; 3] = ["/sdcard", "/storage/emulated", "/data/media"];
+
+/// Safety interlock for configurable wipe targets: the path, with `.` segments and
+/// repeated or trailing slashes removed, must be one of USER_DATA_ROOTS or inside one
+fn check_user_data_path(path: &str) -> Result<String, String> {
+    let path = validate_device_path(path)?;
+    let segments: Vec<&str> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect();
+    let normalized = format!("/{}", segments.join("/"));
+
+    let in_user_data = USER_DATA_ROOTS.iter().any(|root| {
+        normalized == *root
+            || normalized
+                .strip_prefix(root)
+                .is_some_and(|rest| rest.starts_with('/'))
+    });
+    if !in_user_data {
+        return Err(format!(
+            "Refusing to wipe system path: {} (only {} can be wiped)",
+            path,
+            USER_DATA_ROOTS.join(", ")
+        ));
+    }
+    Ok(normalized)
+}
+
+/// Other names the shared storage view is mounted under, which /sdcard resolves through
+/// on current Android versions (e.g. /sdcard -> /storage/self/primary -> /mnt/user/0/...)
+const SHARED_STORAGE_VIEWS: [&str; 3] = ["/storage/self", "/mnt/user", "/mnt/runtime"];
+
+/// Where a device path really points after following every symlink (`readlink -f`)
+fn resolve_device_path_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    path: &str,
+) -> Result<String, String> {
+    let path = validate_device_path(path)?;
+    let out = runner.adb_shell(device_id, &["readlink", "-f", &path])?;
+    let resolved = out.stdout.trim();
+    if !out.success || !resolved.starts_with('/') || resolved.lines().count() != 1 {
+        return Err(format!("Could not resolve the real location of {}", path));
+    }
+    Ok(resolved.to_string())
+}
+
+/// Defense in depth for the wipe target: `/sdcard` is a symlink, so an altered link could
+/// send the fill somewhere else. The resolved path must still be inside user storage.
+fn check_resolved_user_data_path_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    path: &str,
+) -> Result<String, String> {
+    let path = check_user_data_path(path)?;
+    let resolved = resolve_device_path_with(runner, device_id, &path)?;
+    let in_shared_view = SHARED_STORAGE_VIEWS.iter().any(|root| {
+        resolved
+            .strip_prefix(root)
+            .is_some_and(|rest| rest.starts_with('/'))
+    });
+    if !in_shared_view && check_user_data_path(&resolved).is_err() {
+        return Err(format!(
+            "Refusing to wipe {}: it resolves to {}, outside user storage",
+            path, resolved
+        ));
+    }
+    Ok(resolved)
+}
+
+/// Smallest amount of free space worth wiping (the quick mode minimum chunk)
+const MIN_WIPE_FREE_MB: u64 = 64;
+
+/// Probe shell access, a writable temp dir, dd, and free space on a device
+fn check_wipe_supported_with(runner: &dyn CommandRunner, device_id: &str) -> WipeSupport {
+    let mut reasons = Vec::new();
+
+    // Nothing else can be probed without a shell
+    match runner.adb_shell(device_id, &["echo", "ok"]) {
+        Ok(out) if out.success && out.stdout.trim() == "ok" => {}
+        _ => {
+            return WipeSupport {
+                supported: false,
+                reasons: vec!["No shell access. Unlock the device and authorize USB debugging."
+                    .to_string()],
+            }
+        }
+    }
+
+    let probe = format!(
+        "mkdir -p {dir} && touch {dir}/.probe && rm -f {dir}/.probe && echo writable",
+        dir = WIPE_TEMP_DIR
+    );
+    match runner.adb_shell(device_id, &["sh", "-c", &probe]) {
+        Ok(out) if out.stdout.contains("writable") => {}
+        Ok(out) if !out.stderr.trim().is_empty() => {
+            reasons.push(format!("{} is not writable: {}", WIPE_TEMP_DIR, out.stderr.trim()))
+        }
+        _ => reasons.push(format!(
+            "{} is not writable (storage may be read-only or restricted).",
+            WIPE_TEMP_DIR
+        )),
+    }
+
+    match runner.adb_shell(device_id, &["which", "dd"]) {
+        Ok(out) if out.success && !out.stdout.trim().is_empty() => {}
+        _ => reasons.push("dd is not available on the device.".to_string()),
+    }
+
+    match runner
+        .adb_shell(device_id, &["df", "/sdcard"])
+        .and_then(|out| parse_df_output(&out.stdout))
+    {
+        Ok(info) if info.available_mb >= MIN_WIPE_FREE_MB => {}
+        Ok(info) => reasons.push(format!(
+            "Only {}MB free; at least {}MB is needed.",
+            info.available_mb, MIN_WIPE_FREE_MB
+        )),
+        Err(_) => reasons.push("Could not read free space on /sdcard.".to_string()),
+    }
+
+    WipeSupport {
+        supported: reasons.is_empty(),
+        reasons,
+    }
+}
+
+/// Screen timeout while prepared: the largest value Android accepts, i.e. never
+const PREPARED_SCREEN_OFF_TIMEOUT: &str = "2147483647";
+
+/// Read an Android setting and set a new value, returning the old one for restoring
+fn swap_device_setting(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    namespace: &str,
+    key: &str,
+    value: &str,
+) -> Result<String, String> {
+    let current = runner.adb_shell(device_id, &["settings", "get", namespace, key])?;
+    if !current.success {
+        return Err(format!("could not read {}: {}", key, current.stderr.trim()));
+    }
+    let put = runner.adb_shell(device_id, &["settings", "put", namespace, key, value])?;
+    if !put.success {
+        return Err(format!("could not set {}: {}", key, put.stderr.trim()));
+    }
+    Ok(current.stdout.trim().to_string())
+}
+
+/// Everything the device needs before a wipe, as one report: adb authorization, staying
+/// awake on USB power, no screen timeout, the temp dir, and enough free space for the
+/// config. Authorization failing stops the rest; later steps all run so the report shows
+/// every problem at once.
+fn prepare_device_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    config: &WipeConfig,
+) -> PrepareReport {
+    let mut steps = Vec::new();
+    let mut restore = DeviceRestore::default();
+    let mut step = |name: &str, result: Result<String, String>| {
+        let ok = result.is_ok();
+        steps.push(PrepareStep {
+            name: name.to_string(),
+            ok,
+            detail: result.unwrap_or_else(|e| e),
+        });
+        ok
+    };
+
+    let authorized = match runner.adb_shell(device_id, &["echo", "ok"]) {
+        Ok(out) if out.success && out.stdout.trim() == "ok" => {
+            Ok("USB debugging authorized".to_string())
+        }
+        _ => Err("No shell access. Unlock the device and authorize USB debugging.".to_string()),
+    };
+    if !step("authorization", authorized) {
+        return PrepareReport {
+            ready: false,
+            steps,
+            restore,
+        };
+    }
+
+    // 7 = stay awake on AC, USB and wireless power
+    let keep_awake =
+        swap_device_setting(runner, device_id, "global", "stay_on_while_plugged_in", "7");
+    step(
+        "keep_awake",
+        keep_awake.map(|previous| {
+            restore.stay_on_while_plugged_in = Some(previous);
+            "Screen stays on while plugged in".to_string()
+        }),
+    );
+
+    let timeout = swap_device_setting(
+        runner,
+        device_id,
+        "system",
+        "screen_off_timeout",
+        PREPARED_SCREEN_OFF_TIMEOUT,
+    );
+    step(
+        "screen_timeout",
+        timeout.map(|previous| {
+            restore.screen_off_timeout = Some(previous);
+            "Screen timeout disabled".to_string()
+        }),
+    );
+
+    let temp_dir = check_user_data_path(WIPE_TEMP_DIR).and_then(|dir| {
+        let out = runner.adb_shell(device_id, &["mkdir", "-p", &dir])?;
+        if out.success {
+            Ok(format!("{} ready", dir))
+        } else {
+            Err(format!("{} could not be created: {}", dir, out.stderr.trim()))
+        }
+    });
+    step("temp_dir", temp_dir);
+
+    let needed_mb = match config.mode.as_str() {
+        "quick" => config.size_mb.map(u64::from).unwrap_or(MIN_WIPE_FREE_MB),
+        _ => MIN_WIPE_FREE_MB,
+    };
+    let free_space = read_storage_info_with(runner, device_id, DEFAULT_MOUNT_PATH).and_then(|info| {
+        if info.available_mb >= needed_mb {
+            Ok(format!("{}MB free", info.available_mb))
+        } else {
+            Err(format!("Only {}MB free; {}MB is needed.", info.available_mb, needed_mb))
+        }
+    });
+    step("free_space", free_space);
+
+    PrepareReport {
+        ready: steps.iter().all(|s| s.ok),
+        steps,
+        restore,
+    }
+}
+
+/// Put back the settings prepare_device changed. Every setting is attempted; the errors
+/// of any that fail are returned together.
+fn restore_device_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    restore: &DeviceRestore,
+) -> Result<(), String> {
+    let settings = [
+        ("global", "stay_on_while_plugged_in", &restore.stay_on_while_plugged_in),
+        ("system", "screen_off_timeout", &restore.screen_off_timeout),
+    ];
+    let mut errors = Vec::new();
+    for (namespace, key, previous) in settings {
+        let Some(previous) = previous else { continue };
+        let output = if previous == "null" {
+            runner.adb_shell(device_id, &["settings", "delete", namespace, key])
+        } else {
+            runner.adb_shell(device_id, &["settings", "put", namespace, key, previous])
+        };
+        match output {
+            Ok(out) if out.success => {}
+            Ok(out) => errors.push(format!("{}: {}", key, out.stderr.trim())),
+            Err(e) => errors.push(format!("{}: {}", key, e)),
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Could not restore device settings: {}", errors.join("; ")))
+    }
+}
+
+/// Pre-wipe setup in one call (Step 1 of the wizard); undo it with restore_device
+#[tauri::command]
+async fn prepare_device(
+    settings: State<'_, SettingsState>,
+    device_id: String,
+    config: WipeConfig,
+) -> Result<PrepareReport, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    let config = config.validate_and_clamp(settings.get().quick_chunk_bounds)?;
+    Ok(prepare_device_with(&AdbdRestartTolerant::new(SystemRunner), &device_id, &config))
+}
+
+/// Teardown for prepare_device: restore the settings it changed
+#[tauri::command]
+async fn restore_device(device_id: String, restore: DeviceRestore) -> Result<(), String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    restore_device_with(&AdbdRestartTolerant::new(SystemRunner), &device_id, &restore)
+}
+
+/// Fast gate before offering a wipe: can this device be wiped at all?
+#[tauri::command]
+async fn check_wipe_supported(device_id: String) -> Result<WipeSupport, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    Ok(check_wipe_supported_with(&SystemRunner, &device_id))
+}
+
+/// Warn before wiping if accounts (especially Google, which enables FRP) are signed in
+#[tauri::command]
+async fn check_accounts(device_id: String) -> Result<AccountCheck, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    let accounts = AdbdRestartTolerant::new(SystemRunner)
+        .adb_shell(&device_id, &["dumpsys", "account"])
+        .ok()
+        .filter(|out| out.success)
+        .and_then(|out| parse_dumpsys_accounts(&out.stdout));
+
+    Ok(build_account_check(accounts))
+}
+
+fn parse_encryption_status(crypto_state: &str, crypto_type: &str) -> EncryptionStatus {
+    let state = match crypto_state.trim() {
+        "encrypted" => "encrypted",
+        "unencrypted" => "unencrypted",
+        _ => "unknown",
+    };
+    let encryption_type = match crypto_type.trim() {
+        "" => None,
+        t if state == "encrypted" => Some(t.to_string()),
+        _ => None,
+    };
+    EncryptionStatus {
+        state: state.to_string(),
+        encryption_type,
+    }
+}
+
+fn read_encryption_status(runner: &dyn CommandRunner, device_id: &str) -> EncryptionStatus {
+    let prop = |name: &str| {
+        runner
+            .adb_shell(device_id, &["getprop", name])
+            .ok()
+            .filter(|out| out.success)
+            .map(|out| out.stdout)
+            .unwrap_or_default()
+    };
+    parse_encryption_status(&prop("ro.crypto.state"), &prop("ro.crypto.type"))
+}
+
+/// Report whether the device's storage is encrypted, and how
+#[tauri::command]
+async fn get_encryption_status(device_id: String) -> Result<EncryptionStatus, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    Ok(read_encryption_status(&SystemRunner, &device_id))
+}
+
+/// With file-based encryption the factory reset destroys the keys (crypto-erase), which
+/// is what actually sanitizes the data; extra overwrite passes only add hours
+fn encryption_advisory(encryption: &EncryptionStatus, passes: u32) -> Option<String> {
+    let file_based =
+        encryption.state == "encrypted" && encryption.encryption_type.as_deref() == Some("file");
+    (file_based && passes > 1).then(|| {
+        format!(
+            "This device uses file-based encryption, so the factory reset erases its keys \
+             (crypto-erase). One pass plus the reset is sufficient; {} passes will take much \
+             longer for negligible benefit.",
+            passes
+        )
+    })
+}
+
+/// Emit a non-blocking `wipe-advisory` if the chosen pass count is wasted on this device
+fn advise_on_passes(
+    runner: &dyn CommandRunner,
+    events: &dyn EventSink,
+    device_id: &str,
+    passes: u32,
+) -> Option<Warning> {
+    let encryption = read_encryption_status(runner, device_id);
+    let message = encryption_advisory(&encryption, passes)?;
+    emit_event(
+        events,
+        "wipe-advisory",
+        serde_json::json!({ "message": message, "passes": passes, "encryption": encryption }),
+    );
+    Some(Warning::new(WarningCode::EncryptionAdvisory, message))
+}
+
+/// Entropy source the wipe scripts read from
+const ENTROPY_SOURCE: &str = "/dev/urandom";
+
+/// Sample read for the entropy probe: 16 x 1MB, a fraction of a second on a healthy kernel
+const ENTROPY_PROBE_BLOCKS: &str = "16";
+
+/// Below this the entropy source, not USB or flash, caps the write rate
+const ENTROPY_SLOW_MB_PER_SEC: f64 = 20.0;
+
+/// Bytes and seconds from dd's summary line, toybox ("16777216 bytes (16 M) copied,
+/// 0.091 s, 176 M/s") or GNU ("... copied, 0.091 s, 184 MB/s")
+fn parse_dd_summary(output: &str) -> Option<(u64, f64)> {
+    let line = output.lines().find(|l| l.contains("bytes") && l.contains("copied"))?;
+    let bytes = line.split_whitespace().next()?.parse().ok()?;
+    let seconds = line
+        .split(',')
+        .find_map(|field| field.trim().strip_suffix(" s")?.trim().parse().ok())?;
+    Some((bytes, seconds))
+}
+
+fn classify_entropy_rate(bytes: u64, seconds: f64) -> EntropyRate {
+    let mb_per_sec = if seconds > 0.0 {
+        bytes as f64 / (1024.0 * 1024.0) / seconds
+    } else {
+        f64::INFINITY
+    };
+    let slow = mb_per_sec < ENTROPY_SLOW_MB_PER_SEC;
+    let message = slow.then(|| {
+        format!(
+            "{} on this device only delivers about {:.1} MB/s, so the wipe will be limited by \
+             random-number generation rather than USB or storage speed. A pattern of zeros would \
+             be faster, but this tool only writes random data; expect a longer wipe.",
+            ENTROPY_SOURCE, mb_per_sec
+        )
+    });
+    EntropyRate {
+        source: ENTROPY_SOURCE.to_string(),
+        bytes,
+        seconds,
+        mb_per_sec,
+        slow,
+        message,
+    }
+}
+
+/// Time a short read from the entropy source. dd's own timing is used when it prints one,
+/// since the host-side time also includes adb's round trip.
+fn probe_entropy_rate_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+) -> Result<EntropyRate, String> {
+    let input = format!("if={}", ENTROPY_SOURCE);
+    let count = format!("count={}", ENTROPY_PROBE_BLOCKS);
+    let started = Instant::now();
+    let output = runner.adb_shell(
+        device_id,
+        &["dd", &input, "of=/dev/null", "bs=1048576", &count],
+    )?;
+    let elapsed = started.elapsed().as_secs_f64();
+    if !output.success {
+        return Err(format!("Entropy probe failed: {}", output.stderr.trim()));
+    }
+
+    let summary = format!("{}\n{}", output.stdout, output.stderr);
+    let (bytes, seconds) = parse_dd_summary(&summary).unwrap_or_else(|| {
+        let blocks: u64 = ENTROPY_PROBE_BLOCKS.parse().unwrap_or(0);
+        (blocks * 1024 * 1024, elapsed)
+    });
+    Ok(classify_entropy_rate(bytes, seconds))
+}
+
+/// Warn before a wipe when the entropy source is the bottleneck
+fn advise_on_entropy(
+    runner: &dyn CommandRunner,
+    events: &dyn EventSink,
+    device_id: &str,
+) -> Option<Warning> {
+    let rate = probe_entropy_rate_with(runner, device_id).ok()?;
+    let message = rate.message.clone()?;
+    emit_event(
+        events,
+        "wipe-advisory",
+        serde_json::json!({ "message": message, "entropy": rate }),
+    );
+    Some(Warning::new(WarningCode::SlowEntropy, message))
+}
+
+/// Apps cleared (and not) by clear_app_data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppDataClear {
+    pub cleared: Vec<String>,
+    pub failed: Vec<String>, // Refused by pm, typically device admin apps
+}
+
+/// Package names from `pm list packages`, one `package:com.example.app` per line
+fn parse_package_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("package:"))
+        .filter(|name| {
+            !name.is_empty()
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_')
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Shell command clearing one app's data and cache
+fn pm_clear_args(package: &str) -> [&str; 3] {
+    ["pm", "clear", package]
+}
+
+/// Trim every app's cache, then clear the data of each user-installed (`-3`) app.
+/// System apps are left alone: clearing them can break the device before the reset.
+fn clear_app_data_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+) -> Result<AppDataClear, String> {
+    // pm frees cache until this much space is available, i.e. all of it
+    let _ = runner.adb_shell(device_id, &["pm", "trim-caches", "999999G"]);
+    let listing = runner.adb_shell(device_id, &["pm", "list", "packages", "-3"])?;
+    if !listing.success {
+        return Err(format!("Failed to list installed apps: {}", listing.stderr.trim()));
+    }
+
+    let mut result = AppDataClear {
+        cleared: Vec::new(),
+        failed: Vec::new(),
+    };
+    for package in parse_package_list(&listing.stdout) {
+        let cleared = runner
+            .adb_shell(device_id, &pm_clear_args(&package))
+            .is_ok_and(|out| out.success && out.stdout.trim() == "Success");
+        if cleared {
+            result.cleared.push(package);
+        } else {
+            result.failed.push(package);
+        }
+    }
+    Ok(result)
+}
+
+/// Clear app data before a wipe that asked for it; a warning if any of it remains
+fn clear_app_data_step(
+    runner: &dyn CommandRunner,
+    events: &dyn EventSink,
+    audit_log: &AuditLog,
+    device_id: &str,
+) -> Option<Warning> {
+    let message = match clear_app_data_with(runner, device_id) {
+        Ok(result) => {
+            audit(
+                audit_log,
+                "app-data-cleared",
+                Some(device_id),
+                format!("cleared={} failed={:?}", result.cleared.len(), result.failed),
+            );
+            emit_event(events, "app-data-cleared", &result);
+            if result.failed.is_empty() {
+                return None;
+            }
+            format!(
+                "{} app(s) refused to clear and keep their data until the factory reset: {}",
+                result.failed.len(),
+                result.failed.join(", ")
+            )
+        }
+        Err(e) => format!("App data was not cleared: {}", e),
+    };
+    emit_event(events, "wipe-advisory", serde_json::json!({ "message": message }));
+    Some(Warning::new(WarningCode::AppDataRemains, message))
+}
+
+/// Measure how fast the device reads /dev/urandom
+#[tauri::command]
+async fn probe_entropy_rate(device_id: String) -> Result<EntropyRate, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    probe_entropy_rate_with(&SystemRunner, &device_id)
+}
+
+/// List dd processes still writing random data on the device (e.g. after a crash)
+#[tauri::command]
+async fn list_device_wipe_processes(device_id: String) -> Result<Vec<DeviceWipeProcess>, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    list_wipe_processes_with(&SystemRunner, &device_id)
+}
+
+/// Kill lingering wipe dd processes on the device and report what was found and killed
+#[tauri::command]
+async fn kill_device_wipe_processes(
+    state: State<'_, WipeState>,
+    audit_log: State<'_, AuditLog>,
+    device_id: String,
+) -> Result<WipeProcessKill, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    if state.device_id.lock().unwrap().as_deref() == Some(device_id.as_str()) {
+        return Err("A wipe is running on this device. Use Abort to stop it.".to_string());
+    }
+    let result = kill_wipe_processes_with(&SystemRunner, &device_id)?;
+    audit(
+        &audit_log,
+        "wipe-processes-killed",
+        Some(&device_id),
+        format!("found={} killed={:?}", result.found.len(), result.killed),
+    );
+    Ok(result)
+}
+
+/// Get battery level and charging state
+#[tauri::command]
+async fn get_battery_status(device_id: String) -> Result<BatteryStatus, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    read_battery_status(&SystemRunner, &device_id)
+}
+
+/// Execute secure wipe operation with streaming progress
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn run_wipe(
+    window: tauri::Window,
+    state: State<'_, WipeState>,
+    audit_log: State<'_, AuditLog>,
+    locks: State<'_, DeviceLocks>,
+    sessions: State<'_, SessionStore>,
+    settings: State<'_, SettingsState>,
+    capability_cache: State<'_, ScriptCapabilityCache>,
+    device_id: String,
+    config: WipeConfig,
+    confirmation_token: Option<String>,
+) -> Result<String, String> {
+    let ctx = WipeContext {
+        runner: Arc::new(SystemRunner),
+        state: &state,
+        audit_log: &audit_log,
+        sessions: &sessions,
+        settings: settings.get(),
+        batch_id: None,
+    };
+    let checked_id = sanitize_device_id(&device_id)?;
+    let token = confirmation_token.as_deref();
+    confirm_destructive_with(&SystemRunner, &ctx.settings, &checked_id, token)?;
+    run_wipe_with(&ctx, Arc::new(window), &locks, &capability_cache, &device_id, &config)
+}
+
+/// Wipe several devices one after another with the same config, without the wizard
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn run_batch_wipe(
+    window: tauri::Window,
+    state: State<'_, WipeState>,
+    audit_log: State<'_, AuditLog>,
+    locks: State<'_, DeviceLocks>,
+    sessions: State<'_, SessionStore>,
+    settings: State<'_, SettingsState>,
+    capability_cache: State<'_, ScriptCapabilityCache>,
+    batch_state: State<'_, BatchState>,
+    batches: State<'_, BatchStore>,
+    batch: BatchConfig,
+) -> Result<BatchReport, String> {
+    let ctx = WipeContext {
+        runner: Arc::new(SystemRunner),
+        state: &state,
+        audit_log: &audit_log,
+        sessions: &sessions,
+        settings: settings.get(),
+        batch_id: None,
+    };
+    let batch = batch.validate(ctx.settings.quick_chunk_bounds)?;
+    run_batch_wipe_with(
+        &ctx,
+        Arc::new(window),
+        &locks,
+        &capability_cache,
+        &batch_state,
+        &batches,
+        &batch,
+    )
+}
+
+/// Run a batch described by a JSON config file (see BatchConfig)
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn run_batch_wipe_file(
+    window: tauri::Window,
+    state: State<'_, WipeState>,
+    audit_log: State<'_, AuditLog>,
+    locks: State<'_, DeviceLocks>,
+    sessions: State<'_, SessionStore>,
+    settings: State<'_, SettingsState>,
+    capability_cache: State<'_, ScriptCapabilityCache>,
+    batch_state: State<'_, BatchState>,
+    batches: State<'_, BatchStore>,
+    path: String,
+) -> Result<BatchReport, String> {
+    let ctx = WipeContext {
+        runner: Arc::new(SystemRunner),
+        state: &state,
+        audit_log: &audit_log,
+        sessions: &sessions,
+        settings: settings.get(),
+        batch_id: None,
+    };
+    let batch = load_batch_config(Path::new(&path), ctx.settings.quick_chunk_bounds)?;
+    run_batch_wipe_with(
+        &ctx,
+        Arc::new(window),
+        &locks,
+        &capability_cache,
+        &batch_state,
+        &batches,
+        &batch,
+    )
+}
+
+fn run_batch_wipe_with(
+    ctx: &WipeContext,
+    events: Arc<dyn EventSink>,
+    locks: &DeviceLocks,
+    capability_cache: &ScriptCapabilityCache,
+    batch_state: &BatchState,
+    batches: &BatchStore,
+    batch: &BatchConfig,
+) -> Result<BatchReport, String> {
+    // Selectors pick from the devices connected now; the resolved set is reported first
+    let select = batch.select.clone();
+    let chunk = ctx.settings.quick_chunk_bounds;
+    let long = ctx.settings.long_device_listing;
+    let batch = &resolve_batch_devices(batch, chunk, || connected_device_candidates(long))?;
+    emit_event(
+        &*events,
+        "batch-resolved",
+        serde_json::json!({ "select": select, "devices": batch.devices }),
+    );
+    batch_state.begin(&batch.devices)?;
+    let batch_id = batch_state.batch_id();
+    audit(
+        ctx.audit_log,
+        "batch-started",
+        None,
+        format!(
+            "batch={} devices={} mode={}",
+            batch_id,
+            batch.devices.join(","),
+            batch.config.mode
+        ),
+    );
+    let batch_ctx = WipeContext {
+        runner: ctx.runner.clone(),
+        settings: ctx.settings.clone(),
+        batch_id: Some(batch_id),
+        ..*ctx
+    };
+    let report = run_batch(batch, batch_state, &*events, &mut |device_id, config| {
+        run_wipe_with(&batch_ctx, events.clone(), locks, capability_cache, device_id, config)
+    });
+    record_batch_report(ctx.audit_log, &*events, batches, &report);
+    Ok(report)
+}
+
+/// Audit a finished batch, then save its report for export_batch_report. The devices are
+/// already wiped, so a report that can't be saved is logged and reported, not an error.
+fn record_batch_report(
+    audit_log: &AuditLog,
+    events: &dyn EventSink,
+    batches: &BatchStore,
+    report: &BatchReport,
+) {
+    audit(
+        audit_log,
+        "batch-completed",
+        None,
+        format!(
+            "batch={} succeeded={} failed={} cancelled={}",
+            report.batch_id, report.succeeded, report.failed, report.cancelled
+        ),
+    );
+    if let Err(e) = batches.save(report) {
+        let details = format!("batch={} {}", report.batch_id, e);
+        audit(audit_log, "batch-report-not-saved", None, details);
+        emit_event(
+            events,
+            "batch-report-not-saved",
+            serde_json::json!({ "batch_id": report.batch_id, "error": e }),
+        );
+    }
+}
+
+/// Carry on with a batch paused for the operator to connect its next device
+#[tauri::command]
+async fn continue_batch(batch_state: State<'_, BatchState>) -> Result<String, String> {
+    let next = batch_state.resume()?;
+    Ok(format!("Continuing the batch with {}.", next))
+}
+
+/// Cancel one device of the running batch without stopping the others
+#[tauri::command]
+async fn abort_device_in_batch(
+    window: tauri::Window,
+    batch_state: State<'_, BatchState>,
+    state: State<'_, WipeState>,
+    audit_log: State<'_, AuditLog>,
+    device_id: String,
+) -> Result<String, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    abort_device_in_batch_with(
+        &batch_state,
+        &state,
+        &audit_log,
+        &window,
+        &SystemRunner,
+        &device_id,
+    )
+}
+
+/// A wipe checked and located, ready for its script to be started
+struct PreparedWipe {
+    device_id: String, // Current id, which may differ from the one asked for
+    _device_lock: DeviceLockGuard, // Held for the whole wipe
+    device: DeviceInfo,
+    serial: String,
+    config: WipeConfig, // Validated and clamped
+    script: &'static str,
+    scripts_dir: PathBuf,
+    work_dir: PathBuf,
+}
+
+/// Validate a claimed wipe, lock and identify its device, and find the script to run
+fn prepare_wipe(
+    ctx: &WipeContext,
+    locks: &DeviceLocks,
+    device_id: &str,
+    config: &WipeConfig,
+) -> Result<PreparedWipe, String> {
+    let config = config.validate_and_clamp(ctx.settings.quick_chunk_bounds)?;
+    check_user_data_path(WIPE_TEMP_DIR)?;
+
+    // The id may be stale if the device reconnected; the claim follows the current one
+    let device_id = resolve_current_device_id(device_id, config.expect_serial.as_deref())?;
+    ctx.state.reclaim_as(&device_id);
+
+    // Held for the whole wipe; released when run_wipe_with returns
+    let device_lock = locks.acquire(&device_id)?;
+
+    // Capture device identity up front; it may not answer once the wipe is done
+    let device = read_device_info(&device_id);
+    let serial = match get_device_prop(&device_id, "ro.serialno") {
+        s if s.is_empty() => device_id.clone(),
+        s => s,
+    };
+    check_expected_identity(&config, &device, &serial)?;
+    ctx.state.start_workflow(&device_id, &serial);
+    if config.emulator_test {
+        let qemu = match get_device_prop(&device_id, "ro.kernel.qemu") {
+            p if p.is_empty() => get_device_prop(&device_id, "ro.boot.qemu"),
+            p => p,
+        };
+        check_emulator_target(&device_id, &qemu)?;
+    }
+    check_resolved_user_data_path_with(&*ctx.runner, &device_id, WIPE_TEMP_DIR)?;
+
+    let script = if config.mode == "quick" {
+        "quick_wipe.sh"
+    } else {
+        "full_wipe.sh"
+    };
+
+    let scripts_dir = find_scripts_dir(script)?;
+    let work_dir = script_work_dir()?;
+    Ok(PreparedWipe {
+        device_id,
+        _device_lock: device_lock,
+        device,
+        serial,
+        config,
+        script,
+        scripts_dir,
+        work_dir,
+    })
+}
+
+/// Wipe one device with the real scripts: validate, lock, identify, then execute_wipe
+fn run_wipe_with(
+    ctx: &WipeContext,
+    events: Arc<dyn EventSink>,
+    locks: &DeviceLocks,
+    capability_cache: &ScriptCapabilityCache,
+    device_id: &str,
+    config: &WipeConfig,
+) -> Result<String, String> {
+    let device_id = sanitize_device_id(device_id)?;
+    // Claimed before anything else, so an abort from here on cancels the wipe. Nothing
+    // after prepare_wipe fails before execute_wipe, which releases it.
+    ctx.state.claim(&device_id);
+    let prepared = match prepare_wipe(ctx, locks, &device_id, config) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            ctx.state.release(&device_id, false);
+            return Err(e);
+        }
+    };
+    let PreparedWipe {
+        device_id,
+        _device_lock,
+        device,
+        serial,
+        config,
+        script,
+        scripts_dir,
+        work_dir,
+    } = prepared;
+    ctx.state.clear_progress(&device_id);
+
+    let mut warnings: Vec<Warning> = [
+        advise_on_passes(&*ctx.runner, &*events, &device_id, config.passes),
+        advise_on_entropy(&*ctx.runner, &*events, &device_id),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    // A changed device is stopped by the identity guard in spawn below
+    if config.clear_app_data
+        && !ctx.state.launch_cancelled()
+        && ctx.state.guard_identity(&*ctx.runner, &device_id).is_ok()
+    {
+        let events = &*events;
+        warnings.extend(clear_app_data_step(&*ctx.runner, events, ctx.audit_log, &device_id));
+    }
+    let script_version = fs::read_to_string(scripts_dir.join(script))
+        .ok()
+        .and_then(|source| parse_script_version(&source));
+    // Unknown capabilities (probe failed) count as unsupported, like an old script
+    let capabilities = capability_cache
+        .get_or_probe(script, || probe_script_capabilities(&*ctx.runner, script))
+        .ok();
+    let (script_args, raw_streaming) = wipe_script_args(&device_id, &config, capabilities.as_ref());
+
+    let session_id = new_session_id(&device_id);
+    let started_at = unix_timestamp();
+    let storage = read_synced_storage_info_with(&*ctx.runner, &device_id, DEFAULT_MOUNT_PATH);
+    let storage = storage.ok();
+
+    // Spawned again if a retry is needed after a failed verification
+    let priority = host_priority(
+        std::env::consts::OS,
+        ctx.settings.low_priority_wipe,
+        on_host_path,
+    );
+    let guarded_id = device_id.clone();
+    let mut spawn = || -> Result<Box<dyn WipeProcess>, String> {
+        // Every run (including a retry) writes over the device: make sure it's still the one
+        ctx.state.guard_identity(&*ctx.runner, &guarded_id)?;
+        let mut cmd = wipe_script_command(&priority, &scripts_dir, script, &script_args, &work_dir);
+        let child = cmd
+            .traced()
+            .spawn()
+            .map_err(|e| format!("Failed to start wipe: {}", e))?;
+        Ok(Box::new(child))
+    };
+
+    let run = WipeRun {
+        device_id,
+        device,
+        serial,
+        config,
+        raw_streaming,
+        storage,
+        session_id,
+        started_at,
+        warnings,
+        script_version,
+    };
+    execute_wipe(ctx, events, run, &mut spawn)
+}
+
+/// Managed state a running wipe reports to, and the runner its adb commands go through
+struct WipeContext<'a> {
+    runner: Arc<dyn CommandRunner + Send + Sync>, // Shared with the battery watch thread
+    state: &'a WipeState,
+    audit_log: &'a AuditLog,
+    sessions: &'a SessionStore,
+    settings: AppSettings,
+    batch_id: Option<String>, // Recorded in the sessions of a batch's wipes
+}
+
+/// A prepared wipe whose script has been started
+struct WipeRun {
+    device_id: String,
+    device: DeviceInfo,
+    serial: String,
+    config: WipeConfig, // Already validated and clamped
+    raw_streaming: bool,
+    storage: Option<StorageInfo>, // Read before the wipe started writing
+    session_id: String,
+    started_at: u64,
+    warnings: Vec<Warning>, // Raised while preparing, before the script started
+    script_version: Option<String>,
+}
+
+/// A running wipe script: the child process in the app, canned output in tests
+trait WipeProcess {
+    fn take_stdout(&mut self) -> Option<Box<dyn std::io::Read + Send>>;
+
+    /// Wait for the script to exit; true if it succeeded
+    fn wait_success(&mut self) -> Result<bool, String>;
+
+    /// Host process id, which also names the script's process group
+    fn pid(&self) -> Option<u32> {
+        None
+    }
+}
+
+impl WipeProcess for std::process::Child {
+    fn take_stdout(&mut self) -> Option<Box<dyn std::io::Read + Send>> {
+        self.stdout
+            .take()
+            .map(|stdout| Box::new(stdout) as Box<dyn std::io::Read + Send>)
+    }
+
+    fn wait_success(&mut self) -> Result<bool, String> {
+        self.wait()
+            .map(|status| status.success())
+            .map_err(|e| format!("Wipe process error: {}", e))
+    }
+
+    fn pid(&self) -> Option<u32> {
+        Some(self.id())
+    }
+}
+
+/// Start a prepared wipe, stream its progress to the frontend, verify it (retrying if the
+/// policy says so), then record and report the outcome
+fn execute_wipe<'p>(
+    ctx: &WipeContext,
+    events: Arc<dyn EventSink>,
+    run: WipeRun,
+    spawn: &mut dyn FnMut() -> Result<Box<dyn WipeProcess + 'p>, String>,
+) -> Result<String, String> {
+    let WipeRun {
+        device_id,
+        device,
+        serial,
+        config,
+        raw_streaming,
+        storage,
+        session_id,
+        started_at,
+        mut warnings,
+        script_version,
+    } = run;
+    // Everything emitted from here on is also kept with the session
+    let recording = Arc::new(RecordingSink::new(events));
+    let events: Arc<dyn EventSink> = recording.clone();
+    // Already validated and clamped by validate_and_clamp
+    let passes = config.passes;
+    let size_mb = config.size_mb.unwrap_or(0);
+    let quick = config.mode == "quick";
+    // Every progress update is also kept for get_current_progress
+    let emit_progress = |progress: WipeProgress| {
+        ctx.state.record_progress(&device_id, &progress);
+        emit_event(&*events, "wipe-progress", progress);
+    };
+    let mut process = match ctx.state.launch(spawn) {
+        Ok(process) => process,
+        Err(e) => {
+            ctx.state.release(&device_id, false);
+            return Err(e);
+        }
+    };
+    ctx.state.take_passes_completed(&device_id);
+    ctx.state.take_phases(&device_id);
+    if config.skip_factory_reset {
+        ctx.state.data_only.lock().unwrap().insert(device_id.clone());
+    }
+
+    // Emit start event
+    emit_progress(WipeProgress {
+        pass: 0,
+        total_passes: passes,
+        percent: 0.0,
+        bytes_written: 0,
+        message: format!(
+            "Starting {} wipe with {} passes...{}",
+            config.mode,
+            passes,
+            if raw_streaming {
+                ""
+            } else {
+                " This script doesn't support --raw, so progress may arrive in bursts."
+            }
+        ),
+        phase: "starting".to_string(),
+        rate: None,
+        estimated: false,
+    });
+
+    audit(
+        ctx.audit_log,
+        "wipe-started",
+        Some(&device_id),
+        format!(
+            "mode={} passes={} raw={} script_version={}{}",
+            config.mode,
+            passes,
+            raw_streaming,
+            script_version.as_deref().unwrap_or("unknown"),
+            describe_operator(ctx.settings.operator.as_ref())
+        ),
+    );
+
+    // Safety cap: takes the same path as a user abort once the limit is reached
+    let timer = (config.max_duration_secs > 0).then(|| {
+        let device_id = device_id.clone();
+        // Read when it fires, so a retry's script is the one stopped
+        let script_group = ctx.state.script_group.clone();
+        WipeTimer::start(Duration::from_secs(config.max_duration_secs), move || {
+            let pgid = *script_group.lock().unwrap();
+            stop_wipe_processes(&device_id, pgid);
+        })
+    });
+
+    let battery_watch = (ctx.settings.battery_poll_secs > 0).then(|| {
+        BatteryWatch::start(
+            ctx.runner.clone(),
+            events.clone(),
+            device_id.clone(),
+            Duration::from_secs(ctx.settings.battery_poll_secs),
+            ctx.settings.battery_warn_percent,
+        )
+    });
+
+    let policy = ctx.settings.on_verify_failure;
+    let mut retries = 0;
+    // Most written in any single pass; each pass overwrites the same free space
+    let mut overwritten_bytes: u64 = 0;
+    let (succeeded, verification) = loop {
+        let output = process
+            .take_stdout()
+            .map(|stdout| {
+                stream_wipe_output(&*events, &emit_progress, stdout, passes, quick, size_mb)
+            })
+            .unwrap_or_default();
+        overwritten_bytes = overwritten_bytes.max(output.overwritten_bytes);
+        if output.truncated_lines > 0 {
+            audit(
+                ctx.audit_log,
+                "output-truncated",
+                Some(&device_id),
+                format!(
+                    "{} script output lines cut to {} bytes",
+                    output.truncated_lines, MAX_LINE_BYTES
+                ),
+            );
+        }
+
+        // Wait for completion
+        if !process.wait_success()? {
+            break (false, None);
+        }
+
+        let confirmed = output.passes_confirmed;
+        let action = verify_failure_action(policy, confirmed >= passes, retries);
+        let verification = build_verification(policy, passes, confirmed, retries, action);
+        emit_progress(WipeProgress {
+            pass: output.passes_confirmed.min(passes),
+            total_passes: passes,
+            percent: 100.0,
+            bytes_written: 0,
+            message: verification.detail.clone(),
+            phase: "verifying".to_string(),
+            rate: None,
+            estimated: false,
+        });
+
+        if action != VerifyAction::Retry {
+            break (action != VerifyAction::Fail, Some(verification));
+        }
+        retries += 1;
+        audit(ctx.audit_log, "wipe-retry", Some(&device_id), verification.detail.clone());
+        match ctx.state.launch(spawn) {
+            Ok(next) => process = next,
+            Err(_) => break (false, Some(verification)),
+        }
+    };
+    ctx.state.script_exited();
+    let timed_out = timer.map(WipeTimer::finish).unwrap_or(false);
+    if let Some(watch) = battery_watch {
+        warnings.extend(watch.stop());
+    }
+
+    // Clear wipe state
+    ctx.state.release(&device_id, true);
+    ctx.state.clear_progress(&device_id);
+    ctx.state.data_only.lock().unwrap().remove(&device_id);
+    let passes_completed = ctx.state.take_passes_completed(&device_id).min(passes);
+    let phase_durations = phase_durations(&ctx.state.take_phases(&device_id), unix_timestamp_ms());
+
+    let event = match (timed_out, succeeded) {
+        (true, _) => "wipe-timeout",
+        (false, true) => "wipe-completed",
+        (false, false) => "wipe-failed",
+    };
+    audit(
+        ctx.audit_log,
+        event,
+        Some(&device_id),
+        format!(
+            "mode={} passes={}{}",
+            config.mode,
+            passes,
+            if config.skip_factory_reset { " factory_reset=skipped" } else { "" }
+        ),
+    );
+
+    // Snapshots taken for this wipe, plus before/after if storage was readable at the start
+    let mut storage_snapshots = ctx.state.take_snapshots(&device_id);
+    if let Some(before) = &storage {
+        if !storage_snapshots.iter().any(|s| s.label == "before") {
+            let snapshot = StorageSnapshot {
+                label: "before".to_string(),
+                taken_at: started_at,
+                storage: before.clone(),
+            };
+            storage_snapshots.insert(0, snapshot);
+        }
+        let after = read_synced_storage_info_with(&*ctx.runner, &device_id, DEFAULT_MOUNT_PATH);
+        if let Ok(after) = after {
+            storage_snapshots.push(StorageSnapshot {
+                label: "after".to_string(),
+                taken_at: unix_timestamp(),
+                storage: after,
+            });
+        }
+    }
+
+    let mut session = WipeSession {
+        session_id: session_id.clone(),
+        device,
+        serial,
+        mode: config.mode.clone(),
+        passes,
+        size_mb: config.size_mb,
+        started_at,
+        finished_at: unix_timestamp(),
+        success: succeeded && !timed_out,
+        verification: verification.clone(),
+        operator: ctx.settings.operator.clone(),
+        free_space_coverage: storage.as_ref().map(|info| {
+            free_space_coverage(
+                info.available_mb * 1024 * 1024,
+                overwritten_bytes,
+                succeeded && !timed_out,
+            )
+        }),
+        coverage: storage
+            .map(|info| {
+                let capacity = info.physical_capacity_bytes.unwrap_or(info.total_mb * 1024 * 1024);
+                estimate_coverage(capacity, overwritten_bytes)
+            }),
+        warnings: Vec::new(),
+        storage_snapshots,
+        factory_reset_skipped: config.skip_factory_reset,
+        passes_completed: Some(passes_completed),
+        app_version: Some(APP_VERSION.to_string()),
+        script_version,
+        phase_durations,
+        batch_id: ctx.batch_id.clone(),
+    };
+    warnings.extend(outcome_warnings(&session));
+    session.warnings = warnings;
+    let _ = ctx.sessions.save(&session);
+
+    if timed_out {
+        let message = format!(
+            "Wipe exceeded the maximum duration of {} seconds and was aborted.",
+            config.max_duration_secs
+        );
+        emit_event(
+            &*events,
+            "wipe-timeout",
+            serde_json::json!({
+                "message": message,
+                "max_duration_secs": config.max_duration_secs,
+                "passes_completed": passes_completed,
+                "session_id": session_id
+            }),
+        );
+        let _ = ctx.sessions.save_events(&session_id, &recording.take());
+        return Err(message);
+    }
+
+    // Emit completion event
+    emit_event(
+        &*events,
+        "wipe-complete",
+        wipe_complete_payload(&session, config.keep_temp_files),
+    );
+    let _ = ctx.sessions.save_events(&session_id, &recording.take());
+
+    match (succeeded, verification) {
+        (true, Some(v)) if !v.passed => Ok(format!("Wipe completed with a warning. {}", v.detail)),
+        (true, _) => Ok(format!(
+            "Wipe completed successfully! {} passes of {} mode.",
+            passes, config.mode
+        )),
+        (false, Some(v)) => Err(format!("Wipe passes not confirmed. {}", v.detail)),
+        (false, None) => Err("Wipe failed. Check device connection and try again.".to_string()),
+    }
+}
+
+/// What one run of a wipe script reported on stdout
+#[derive(Debug, Default)]
+struct WipeOutput {
+    overwritten_bytes: u64, // Most written in any single pass
+    truncated_lines: u32,
+    passes_confirmed: u32, // Highest pass the script reported as complete
+}
+
+/// Fills in the write rate on real progress and extrapolates from it while the script is
+/// quiet, so a long pass doesn't leave the bar frozen between updates. An estimate never
+/// goes below the last real value or past the end of the pass it belongs to.
+#[derive(Default)]
+struct ProgressInterpolator {
+    last: Option<(WipeProgress, Instant)>,
+    rate: Option<f32>,
+}
+
+impl ProgressInterpolator {
+    fn on_progress(&mut self, mut progress: WipeProgress, now: Instant) -> WipeProgress {
+        let same_pass = self.last.as_ref().is_some_and(|(last, _)| {
+            last.pass == progress.pass && last.phase == "writing" && progress.phase == "writing"
+        });
+        if !same_pass {
+            self.rate = None;
+        } else if let Some((last, at)) = &self.last {
+            let elapsed = now.duration_since(*at).as_secs_f32();
+            if progress.percent > last.percent && elapsed > 0.0 {
+                self.rate = Some((progress.percent - last.percent) / elapsed);
+            }
+        }
+        progress.rate = self.rate;
+        self.last = Some((progress.clone(), now));
+        progress
+    }
+
+    fn estimate(&self, now: Instant) -> Option<WipeProgress> {
+        let (last, at) = self.last.as_ref()?;
+        let rate = self.rate.filter(|r| *r > 0.0)?;
+        let pass_end = last.pass as f32 / last.total_passes.max(1) as f32 * 100.0;
+        let elapsed = now.duration_since(*at).as_secs_f32();
+        let percent = (last.percent + rate * elapsed).min(pass_end).max(last.percent);
+        Some(WipeProgress {
+            percent,
+            rate: Some(rate),
+            estimated: true,
+            ..last.clone()
+        })
+    }
+}
+
+/// Forward a script's progress lines to the frontend as they arrive. Lines are read on
+/// their own thread so a slow emitter never stalls the script's output; the queue drops
+/// superseded updates instead.
+fn stream_wipe_output(
+    events: &dyn EventSink,
+    emit_progress: &dyn Fn(WipeProgress),
+    stdout: Box<dyn std::io::Read + Send>,
+    passes: u32,
+    quick: bool,
+    size_mb: u32,
+) -> WipeOutput {
+    let queue = Arc::new(ProgressQueue::new(PROGRESS_QUEUE_CAPACITY));
+    let reader_queue = queue.clone();
+
+    let reader = std::thread::spawn(move || {
+        let mut output = WipeOutput::default();
+        for line in BoundedLines::new(BufReader::new(stdout), MAX_LINE_BYTES) {
+            output.truncated_lines += line.truncated as u32;
+            // Parse progress from line
+            if let Some(progress) = parse_progress_line(&line.text, passes) {
+                // Quick mode doesn't report sizes; a finished pass wrote one chunk
+                let pass_bytes = if quick && progress.phase == "complete" {
+                    size_mb as u64 * 1024 * 1024
+                } else {
+                    progress.bytes_written
+                };
+                output.overwritten_bytes = output.overwritten_bytes.max(pass_bytes);
+                if progress.phase == "complete" {
+                    output.passes_confirmed = output.passes_confirmed.max(progress.pass);
+                }
+                reader_queue.push(progress);
+            }
+        }
+        reader_queue.close();
+        output
+    });
+
+    let mut mode = ProgressModeTracker::new(Instant::now(), PROGRESS_GRACE_PERIOD);
+    let mut interpolator = ProgressInterpolator::default();
+    let emit_mode = |mode: &str| {
+        emit_event(events, "progress-mode", serde_json::json!({ "mode": mode }));
+    };
+
+    loop {
+        match queue.next(Duration::from_secs(1)) {
+            QueueItem::Progress(progress) => {
+                if let Some(m) = mode.on_progress() {
+                    emit_mode(m);
+                }
+                emit_progress(interpolator.on_progress(progress, Instant::now()));
+            }
+            QueueItem::Idle => {
+                if let Some(m) = mode.on_idle(Instant::now()) {
+                    emit_mode(m);
+                }
+                // Estimates aren't recorded as the device's progress, only shown
+                if let Some(estimate) = interpolator.estimate(Instant::now()) {
+                    emit_event(events, "wipe-progress", estimate);
+                }
+            }
+            QueueItem::Closed => break,
+        }
+    }
+    reader.join().unwrap_or_default()
+}
+
+/// Script runs added by the retry policy
+const MAX_VERIFY_RETRIES: u32 = 1;
+
+/// What the workflow does with a verification result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VerifyAction {
+    Accept,
+    Warn,
+    Retry,
+    Fail,
+}
+
+fn verify_failure_action(policy: VerifyFailurePolicy, passed: bool, retries: u32) -> VerifyAction {
+    match (passed, policy) {
+        (true, _) => VerifyAction::Accept,
+        (false, VerifyFailurePolicy::Warn) => VerifyAction::Warn,
+        (false, VerifyFailurePolicy::Retry) if retries < MAX_VERIFY_RETRIES => VerifyAction::Retry,
+        (false, _) => VerifyAction::Fail,
+    }
+}
+
+fn build_verification(
+    policy: VerifyFailurePolicy,
+    passes: u32,
+    passes_confirmed: u32,
+    retries: u32,
+    action: VerifyAction,
+) -> WipeVerification {
+    let attempts = retries + 1;
+    let detail = match action {
+        VerifyAction::Accept => format!("The wipe script confirmed all {} passes.", passes),
+        _ => {
+            let next = match action {
+                VerifyAction::Warn => "Keeping the result (policy: warn).",
+                VerifyAction::Retry => "Running the wipe again (policy: retry).",
+                _ => "Marking the wipe as failed.",
+            };
+            format!(
+                "The wipe script confirmed only {} of {} passes after {} {}. {}",
+                passes_confirmed.min(passes),
+                passes,
+                attempts,
+                if attempts == 1 { "run" } else { "runs" },
+                next
+            )
+        }
+    };
+
+    WipeVerification {
+        passed: action == VerifyAction::Accept,
+        passes_confirmed,
+        policy,
+        attempts,
+        detail,
+    }
+}
+
+/// Abort a running wipe operation
+#[tauri::command]
+async fn abort_wipe(
+    window: tauri::Window,
+    state: State<'_, WipeState>,
+    audit_log: State<'_, AuditLog>,
+    abort_behavior: Option<AbortBehavior>,
+) -> Result<String, String> {
+    abort_wipe_with(
+        &state,
+        &audit_log,
+        &window,
+        &SystemRunner,
+        abort_behavior.unwrap_or_default(),
+    )
+}
+
+/// Latest progress of the device's active wipe, for callers that poll instead of
+/// listening for wipe-progress events. None when no wipe is running on it.
+#[tauri::command]
+async fn get_current_progress(
+    state: State<'_, WipeState>,
+    device_id: String,
+) -> Result<Option<WipeProgress>, String> {
+    Ok(state.current_progress(&device_id))
+}
+
+/// Stop the running wipe and report the cleanup (and any chained reset) as events
+fn abort_wipe_with(
+    state: &WipeState,
+    audit_log: &AuditLog,
+    events: &dyn EventSink,
+    runner: &dyn CommandRunner,
+    abort_behavior: AbortBehavior,
+) -> Result<String, String> {
+    // Get the device ID
+    let device_id = {
+        let dev_lock = state.device_id.lock().unwrap();
+        dev_lock.clone()
+    };
+
+    let device_id = match (device_id, state.last_finished.lock().unwrap().clone()) {
+        (Some(id), _) => id,
+        (None, Some(finished)) => {
+            return Err(format!(
+                "The wipe on {} has already finished; there is nothing to abort.",
+                finished
+            ))
+        }
+        (None, None) => return Err("No wipe operation in progress.".to_string()),
+    };
+    DEVICE_WAITS.cancel(&device_id);
+
+    // Not started yet: nothing has been written, so there's nothing to stop or clean up.
+    // run_wipe sees the cancel before spawning the script, and frees the device.
+    if state.cancel_launch() {
+        audit(audit_log, "wipe-aborted", Some(&device_id), "before the script started".into());
+        let message = "Wipe cancelled before it started. Nothing was overwritten.";
+        emit_event(
+            events,
+            "wipe-aborted",
+            serde_json::json!({ "message": message, "before_start": true }),
+        );
+        return Ok(message.to_string());
+    }
+
+    // A data-only wipe never chains into a reset, even when aborted. Nor does one whose
+    // id now belongs to a different device.
+    let identity = match abort_behavior {
+        AbortBehavior::CleanupAndReset => state.guard_identity(runner, &device_id),
+        AbortBehavior::CleanupOnly => Ok(()),
+    };
+    let data_only = state.data_only.lock().unwrap().remove(&device_id);
+    let abort_behavior = if data_only || identity.is_err() {
+        AbortBehavior::CleanupOnly
+    } else {
+        abort_behavior
+    };
+    let script_group = state.script_group();
+    let (cleanup, mut reset) = abort_with(runner, &device_id, script_group, abort_behavior);
+    if let Err(e) = identity {
+        reset = Some(Err(e));
+    }
+
+    // Clear wipe state
+    {
+        let mut dev_lock = state.device_id.lock().unwrap();
+        *dev_lock = None;
+    }
+    state.clear_progress(&device_id);
+
+    audit(
+        audit_log,
+        "wipe-aborted",
+        Some(&device_id),
+        format!(
+            "aborted by user; dd_stopped={} temp_removed={}",
+            cleanup.dd_stopped, cleanup.temp_removed
+        ),
+    );
+
+    // Emit abort event
+    emit_event(events, "cleanup-result", &cleanup);
+    let payload = abort_payload(&cleanup);
+    emit_event(events, "wipe-aborted", &payload);
+
+    let mut message = if cleanup.dd_stopped && cleanup.temp_removed {
+        "Wipe aborted. Temporary files cleaned up.".to_string()
+    } else {
+        payload["message"].as_str().unwrap_or_default().to_string()
+    };
+
+    match reset {
+        Some(Ok(result)) => {
+            audit(audit_log, "factory-reset", Some(&device_id), result.summary.clone());
+            message = format!("{} {}", message, result.summary);
+            emit_event(events, "factory-reset", &result);
+        }
+        Some(Err(e)) => message = format!("{} Factory reset could not be opened: {}", message, e),
+        None => {}
+    }
+
+    Ok(message)
+}
+
+/// Check a device is ready for the reset screen to be opened again: no wipe running,
+/// connected and authorized, booted, and not on the lock screen
+fn check_reset_preconditions(
+    runner: &dyn CommandRunner,
+    state: &WipeState,
+    device_id: &str,
+) -> Result<(), String> {
+    if let Some(active) = state.device_id.lock().unwrap().as_deref() {
+        return Err(format!(
+            "A wipe is still running on {}. Wait for it to finish or abort it first.",
+            active
+        ));
+    }
+
+    match read_lifecycle_state(runner, device_id)? {
+        DeviceLifecycleState::Online => {}
+        DeviceLifecycleState::Absent | DeviceLifecycleState::Offline => {
+            return Err(format!("Device {} is not connected. Reconnect it first.", device_id))
+        }
+        DeviceLifecycleState::Unauthorized => {
+            return Err("Allow USB debugging on the phone, then try again.".to_string())
+        }
+        DeviceLifecycleState::Rebooting => {
+            return Err("The device is still starting up. Try again once it has booted.".to_string())
+        }
+        DeviceLifecycleState::Recovery => {
+            return Err("The device is in recovery mode. Reboot it to Android first.".to_string())
+        }
+    }
+
+    let window = runner.adb_shell(device_id, &["dumpsys", "window"])?;
+    if parse_keyguard_locked(&window.stdout) == Some(true) {
+        return Err("Unlock the phone, then try again.".to_string());
+    }
+    Ok(())
+}
+
+/// Open the factory reset screen again (e.g. after it was dismissed) without re-wiping
+#[tauri::command]
+async fn retrigger_factory_reset(
+    state: State<'_, WipeState>,
+    locks: State<'_, DeviceLocks>,
+    device_id: String,
+    is_final: bool,
+) -> Result<FactoryResetResult, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    check_reset_preconditions(&SystemRunner, &state, &device_id)?;
+    let _device_lock = locks.acquire(&device_id)?;
+    open_factory_reset_with(&SystemRunner, &device_id, is_final)
+}
+
+/// How long poll_reset_started waits for the user to confirm the reset by default
+const RESET_WATCH_TIMEOUT: Duration = Duration::from_secs(180);
+const RESET_WATCH_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Progress of a reset as seen from the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResetWatch {
+    Waiting,              // Still up on the reset screen (or the user backed out)
+    Rebooting,            // Dropped off adb; not seen back yet
+    Confirmed,            // Came back reset
+    RebootedWithoutReset, // Came back with setup already complete
+}
+
+/// Fold one observation into the watch. A reset device comes back either in the setup
+/// wizard (`user_setup_complete` 0) or unauthorized, since the reset also clears the adb
+/// keys. `setup_complete` is only read while the device is online.
+fn next_reset_watch(
+    watch: ResetWatch,
+    state: DeviceLifecycleState,
+    setup_complete: Option<bool>,
+) -> ResetWatch {
+    use DeviceLifecycleState::*;
+    match (watch, state) {
+        (ResetWatch::Confirmed | ResetWatch::RebootedWithoutReset, _) => watch,
+        (_, Absent | Offline | Rebooting | Recovery) => ResetWatch::Rebooting,
+        (ResetWatch::Rebooting, Unauthorized) => ResetWatch::Confirmed,
+        (ResetWatch::Rebooting, Online) => match setup_complete {
+            Some(false) => ResetWatch::Confirmed,
+            Some(true) => ResetWatch::RebootedWithoutReset,
+            None => ResetWatch::Rebooting,
+        },
+        (ResetWatch::Waiting, _) => ResetWatch::Waiting,
+    }
+}
+
+/// Watch for the device to reboot into a reset state after the reset screen was opened,
+/// emitting `reset-confirmed` once it has. Stops at `timeout` (or the PollLimit cap) with
+/// whatever was seen, saying it timed out.
+fn poll_reset_started_with(
+    runner: &dyn CommandRunner,
+    events: &dyn EventSink,
+    device_id: &str,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<ResetStartCheck, String> {
+    let started = Instant::now();
+    let mut limit = PollLimit::new(device_id, timeout);
+    let mut watch = ResetWatch::Waiting;
+    loop {
+        let state = read_lifecycle_state(runner, device_id)?;
+        let setup_complete = (state == DeviceLifecycleState::Online)
+            .then(|| {
+                runner
+                    .adb_shell(device_id, &["settings", "get", "secure", "user_setup_complete"])
+                    .ok()
+                    .filter(|out| out.success)
+                    .and_then(|out| match out.stdout.trim() {
+                        "1" => Some(true),
+                        "0" => Some(false),
+                        _ => None,
+                    })
+            })
+            .flatten();
+        watch = next_reset_watch(watch, state, setup_complete);
+
+        let done = matches!(watch, ResetWatch::Confirmed | ResetWatch::RebootedWithoutReset);
+        if done || !limit.next(poll_interval) {
+            break;
+        }
+    }
+
+    let mut message = match watch {
+        ResetWatch::Confirmed => "Factory reset confirmed: the device rebooted into setup.",
+        ResetWatch::RebootedWithoutReset => {
+            "The device rebooted but was not reset. Open the reset screen and try again."
+        }
+        ResetWatch::Rebooting => {
+            "The device rebooted and hasn't reconnected. A reset turns USB debugging off, so \
+             check the phone's screen to confirm it is erasing."
+        }
+        ResetWatch::Waiting => {
+            "The device hasn't rebooted. The reset may not have been confirmed on the phone."
+        }
+    }
+    .to_string();
+    if matches!(watch, ResetWatch::Waiting | ResetWatch::Rebooting) {
+        let outcome = if limit.cancelled() { "Stopped waiting" } else { "Timed out" };
+        message = format!("{} after {}s. {}", outcome, started.elapsed().as_secs(), message);
+    }
+    let check = ResetStartCheck {
+        confirmed: watch == ResetWatch::Confirmed,
+        rebooted: watch != ResetWatch::Waiting,
+        message,
+    };
+    if check.confirmed {
+        emit_event(events, "reset-confirmed", &check);
+    }
+    Ok(check)
+}
+
+/// After a factory reset (once the new owner's setup has re-enabled USB debugging), check
+/// that no account survived. Emits `frp-clear` when the device is clean for resale and
+/// `frp-risk` otherwise, including when the accounts couldn't be read.
+fn verify_no_accounts_with(
+    runner: &dyn CommandRunner,
+    events: &dyn EventSink,
+    device_id: &str,
+) -> AccountCheck {
+    let accounts = runner
+        .adb_shell(device_id, &["dumpsys", "account"])
+        .ok()
+        .filter(|out| out.success)
+        .and_then(|out| parse_dumpsys_accounts(&out.stdout));
+
+    let mut check = build_account_check(accounts);
+    check.message = match check.status.as_str() {
+        "none" => "No accounts remain after the reset. The device is clean for resale.".to_string(),
+        "signed_in" if check.frp_risk => "A Google account is still attached after the reset. \
+             The next owner will be locked out by Factory Reset Protection: sign in, remove the \
+             account in Settings > Accounts, then reset again."
+            .to_string(),
+        "signed_in" => format!(
+            "{} account(s) still present after the reset. Remove them before handing the \
+             device over.",
+            check.accounts.len()
+        ),
+        _ => "Could not read accounts on the device, so it can't be confirmed clean for \
+              resale. Unlock it and allow USB debugging, then check again."
+            .to_string(),
+    };
+    let event = if check.status == "none" { "frp-clear" } else { "frp-risk" };
+    emit_event(events, event, &check);
+    check
+}
+
+/// Closes the loop on FRP: confirm no account is left on a reset device
+#[tauri::command]
+async fn verify_no_accounts(
+    window: tauri::Window,
+    device_id: String,
+) -> Result<AccountCheck, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    Ok(verify_no_accounts_with(&SystemRunner, &window, &device_id))
+}
+
+/// Follow-up to run_factory_reset: wait to see whether the user actually confirmed the
+/// reset, which the reset screen itself can't tell us
+#[tauri::command]
+async fn poll_reset_started(
+    window: tauri::Window,
+    device_id: String,
+    timeout_secs: Option<u64>,
+) -> Result<ResetStartCheck, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    let timeout = timeout_secs.map(Duration::from_secs).unwrap_or(RESET_WATCH_TIMEOUT);
+    poll_reset_started_with(
+        &SystemRunner,
+        &window,
+        &device_id,
+        timeout,
+        RESET_WATCH_POLL_INTERVAL,
+    )
+}
+
+/// Stop the wipe and, for CleanupAndReset, chain into the factory reset screen. The
+/// aborted wipe still holds the device lock, so the reset runs under it.
+fn abort_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    script_group: Option<u32>,
+    behavior: AbortBehavior,
+) -> (CleanupResult, Option<Result<FactoryResetResult, String>>) {
+    let cleanup = stop_wipe_processes_with(runner, device_id, script_group);
+    let reset = match behavior {
+        AbortBehavior::CleanupOnly => None,
+        AbortBehavior::CleanupAndReset => Some(open_factory_reset_with(runner, device_id, true)),
+    };
+    (cleanup, reset)
+}
+
+/// Trigger factory reset via ADB (opens settings screen)
+#[tauri::command]
+async fn run_factory_reset(
+    state: State<'_, WipeState>,
+    locks: State<'_, DeviceLocks>,
+    settings: State<'_, SettingsState>,
+    device_id: String,
+    is_final: bool,
+    dry: Option<bool>,
+    confirmation_token: Option<String>,
+) -> Result<FactoryResetResult, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    if dry.unwrap_or(false) {
+        return dry_run_factory_reset_with(&SystemRunner, &device_id, is_final);
+    }
+    let token = confirmation_token.as_deref();
+    confirm_destructive_with(&SystemRunner, &settings.get(), &device_id, token)?;
+    let _device_lock = locks.acquire(&device_id)?;
+    state.guard_identity(&SystemRunner, &device_id)?;
+
+    open_factory_reset_with(&SystemRunner, &device_id, is_final)
+}
+
+/// Reset screen intents in order of specificity - some are blocked on certain devices
+const RESET_INTENTS: [(&str, &str); 4] = [
+    // Most direct - but often requires system permission
+    ("android.settings.MASTER_CLEAR", "Factory Reset"),
+    // Backup & Reset settings - works on some devices
+    ("android.settings.BACKUP_AND_RESET_SETTINGS", "Backup & Reset"),
+    // Privacy settings - contains reset on some devices
+    ("android.settings.PRIVACY_SETTINGS", "Privacy Settings"),
+    // Internal storage - close to reset on Samsung
+    ("android.settings.INTERNAL_STORAGE_SETTINGS", "Storage Settings"),
+];
+
+/// Opened when every reset intent is blocked
+const SETTINGS_COMPONENT: &str = "com.android.settings/.Settings";
+
+/// Brand and model, which the reset instructions are tailored to
+fn reset_device_identity(runner: &dyn CommandRunner, device_id: &str) -> (String, String) {
+    let prop = |name: &str| {
+        runner
+            .adb_shell(device_id, &["getprop", name])
+            .map(|out| out.stdout.trim().to_string())
+            .unwrap_or_default()
+    };
+    (prop("ro.product.brand"), prop("ro.product.model"))
+}
+
+/// How long a started reset screen gets to reach the foreground
+const FOREGROUND_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+const FOREGROUND_CHECK_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Component of the resumed activity in `dumpsys activity activities` output, from
+/// `mResumedActivity: ActivityRecord{1a2b3c u0 com.android.settings/.Settings t12}` (or
+/// `ResumedActivity:` / `topResumedActivity=` on newer releases)
+fn parse_resumed_activity(dumpsys: &str) -> Option<String> {
+    dumpsys
+        .lines()
+        .find(|l| {
+            let l = l.trim_start();
+            ["mResumedActivity", "ResumedActivity", "topResumedActivity"]
+                .iter()
+                .any(|key| l.starts_with(key))
+        })?
+        .split_whitespace()
+        .find(|word| word.contains('/'))
+        .map(|component| component.trim_end_matches('}').to_string())
+}
+
+/// The package half of a `pkg/Class` component
+fn component_package(component: &str) -> Option<&str> {
+    component.split_once('/').map(|(package, _)| package)
+}
+
+/// Whether the resumed activity belongs to the package the intent resolved to. Settings
+/// often forwards the intent to another of its activities (SubSettings, a trampoline), so
+/// any activity in that package counts. When the intent couldn't be resolved up front, any
+/// Settings activity counts.
+fn is_expected_activity(resumed: &str, expected: Option<&str>) -> bool {
+    let Some(package) = component_package(resumed) else {
+        return false;
+    };
+    match expected.and_then(component_package) {
+        Some(expected) => package == expected,
+        None => package.contains("settings"),
+    }
+}
+
+/// After `am start` reports success, wait for the expected screen to come up. Some devices
+/// accept the intent and then show nothing. Returns why it didn't, or None if it did or
+/// if the foreground can't be read on this device (trust `am start` then).
+fn check_intent_opened(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    intent: &str,
+    timeout: Duration,
+    poll: Duration,
+) -> Option<String> {
+    let argv = ["cmd", "package", "resolve-activity", "--brief", "-a", intent];
+    let expected = runner
+        .adb_shell(device_id, &argv)
+        .ok()
+        .filter(|out| out.success)
+        .and_then(|out| parse_resolved_activity(&out.stdout));
+
+    let mut limit = PollLimit::new(device_id, timeout);
+    let mut last_seen = None;
+    loop {
+        let resumed = runner
+            .adb_shell(device_id, &["dumpsys", "activity", "activities"])
+            .ok()
+            .filter(|out| out.success)
+            .and_then(|out| parse_resumed_activity(&out.stdout));
+        match resumed {
+            None if last_seen.is_none() => return None,
+            Some(resumed) if is_expected_activity(&resumed, expected.as_deref()) => return None,
+            Some(resumed) => last_seen = Some(resumed),
+            None => {}
+        }
+        if !limit.next(poll) {
+            break;
+        }
+    }
+    Some(format!(
+        "Accepted but nothing opened (foreground: {})",
+        last_seen.unwrap_or_else(|| "unknown".to_string())
+    ))
+}
+
+/// Open the best available reset screen on the device, trying intents in order
+fn open_factory_reset_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    is_final: bool,
+) -> Result<FactoryResetResult, String> {
+    open_factory_reset_checked(
+        runner,
+        device_id,
+        is_final,
+        FOREGROUND_CHECK_TIMEOUT,
+        FOREGROUND_CHECK_POLL_INTERVAL,
+    )
+}
+
+fn open_factory_reset_checked(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    is_final: bool,
+    foreground_timeout: Duration,
+    foreground_poll: Duration,
+) -> Result<FactoryResetResult, String> {
+    // Instructions are tailored to the device, so look it up once up front
+    let (brand, model) = reset_device_identity(runner, device_id);
+
+    let mut blocked = Vec::new();
+
+    for (intent, name) in RESET_INTENTS {
+        let output = runner.adb_shell(device_id, &["am", "start", "-a", intent]);
+
+        let blocked_reason = match output {
+            Ok(out) => intent_blocked_reason(&out.stdout, &out.stderr),
+            Err(e) => Some(format!("adb failed: {}", e)),
+        };
+        let blocked_reason = blocked_reason.or_else(|| {
+            check_intent_opened(runner, device_id, intent, foreground_timeout, foreground_poll)
+        });
+
+        match blocked_reason {
+            None => {
+                return Ok(build_factory_reset_result(
+                    intent, name, false, is_final, brand, model, blocked,
+                ))
+            }
+            Some(blocked_reason) => blocked.push(BlockedIntent {
+                intent: intent.to_string(),
+                blocked_reason,
+            }),
+        }
+    }
+
+    // Fallback: just open main Settings
+    let output = runner
+        .adb_shell(device_id, &["am", "start", "-n", SETTINGS_COMPONENT])
+        .map_err(|e| format!("Failed to open settings: {}", e))?;
+
+    if output.success {
+        Ok(build_factory_reset_result(
+            SETTINGS_COMPONENT,
+            "Settings",
+            true,
+            is_final,
+            brand,
+            model,
+            blocked,
+        ))
+    } else {
+        Err("Could not open settings. Please manually navigate to Settings > General management > Reset.".to_string())
+    }
+}
+
+/// Dry run of open_factory_reset_with: ask the package manager which reset intents
+/// resolve on the device (`cmd package resolve-activity`, Android 7+) and report what a
+/// real run would open, without starting anything. An intent that resolves can still be
+/// refused with a Permission Denial when actually started.
+fn dry_run_factory_reset_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    is_final: bool,
+) -> Result<FactoryResetResult, String> {
+    let (brand, model) = reset_device_identity(runner, device_id);
+
+    let mut resolutions = Vec::new();
+    for (intent, _) in RESET_INTENTS {
+        let argv = ["cmd", "package", "resolve-activity", "--brief", "-a", intent];
+        let output = runner
+            .adb_shell(device_id, &argv)
+            .map_err(|e| format!("Failed to query intents: {}", e))?;
+        let activity = parse_resolved_activity(&output.stdout).filter(|_| output.success);
+        resolutions.push(IntentResolution {
+            intent: intent.to_string(),
+            resolvable: activity.is_some(),
+            activity,
+        });
+    }
+
+    // Same order as a real run: unresolvable intents ahead of the first one that resolves
+    // are the ones it would get past
+    let first = resolutions.iter().position(|r| r.resolvable);
+    let blocked = resolutions[..first.unwrap_or(resolutions.len())]
+        .iter()
+        .map(|r| BlockedIntent {
+            intent: r.intent.clone(),
+            blocked_reason: "Intent not available on this device".to_string(),
+        })
+        .collect();
+    let (intent, screen, fallback) = match first {
+        Some(i) => (RESET_INTENTS[i].0, RESET_INTENTS[i].1, false),
+        None => (SETTINGS_COMPONENT, "Settings", true),
+    };
+
+    let available = resolutions.iter().filter(|r| r.resolvable).count();
+    let mut result =
+        build_factory_reset_result(intent, screen, fallback, is_final, brand, model, blocked);
+    result.summary = format!(
+        "Dry run: nothing was opened. {} of {} reset intents resolve; a reset would open {}.",
+        available,
+        resolutions.len(),
+        screen
+    );
+    result.dry_run = true;
+    result.resolutions = resolutions;
+    Ok(result)
+}
+
+/// Round trips timed by ping_device
+const PING_SAMPLES: u32 = 5;
+
+/// Min/avg/max over the successful round trips, or None if there were none
+fn summarize_latency(samples: &[Duration], failed: u32) -> Option<PingStats> {
+    let ms: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+    if ms.is_empty() {
+        return None;
+    }
+
+    Some(PingStats {
+        samples: ms.len() as u32,
+        failed,
+        min_ms: ms.iter().copied().fold(f64::INFINITY, f64::min),
+        avg_ms: ms.iter().sum::<f64>() / ms.len() as f64,
+        max_ms: ms.iter().copied().fold(0.0, f64::max),
+    })
+}
+
+/// Time `count` trivial shell round trips to the device
+fn ping_device_with(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+    count: u32,
+) -> Result<PingStats, String> {
+    let mut samples = Vec::new();
+    let mut failed = 0;
+    for _ in 0..count {
+        let started = Instant::now();
+        match runner.adb_shell(device_id, &["echo", "ping"]) {
+            Ok(out) if out.success && out.stdout.trim() == "ping" => {
+                samples.push(started.elapsed())
+            }
+            _ => failed += 1,
+        }
+    }
+
+    summarize_latency(&samples, failed)
+        .ok_or_else(|| format!("Device did not respond to any of {} pings.", count))
+}
+
+/// Measure adb round-trip latency to diagnose flaky USB or wireless connections
+#[tauri::command]
+async fn ping_device(device_id: String) -> Result<PingStats, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    ping_device_with(&SystemRunner, &device_id, PING_SAMPLES)
+}
+
+/// Lifecycle state of one device: its `adb devices` entry, plus `sys.boot_completed` once
+/// adb reports it as a device
+fn read_lifecycle_state(
+    runner: &dyn CommandRunner,
+    device_id: &str,
+) -> Result<DeviceLifecycleState, String> {
+    let output = runner.run("adb", &["devices".to_string()])?;
+    if !output.success {
+        return Err(format!("Failed to list devices: {}", output.stderr.trim()));
+    }
+    let adb_state = parse_adb_device_states(&output.stdout)
+        .into_iter()
+        .find(|(id, _)| id == device_id)
+        .map(|(_, state)| state);
+
+    let boot_completed = adb_state.as_deref() == Some("device")
+        && runner
+            .adb_shell(device_id, &["getprop", "sys.boot_completed"])
+            .is_ok_and(|out| out.success && out.stdout.trim() == "1");
+
+    Ok(lifecycle_state(adb_state.as_deref(), boot_completed))
+}
+
+/// Poll where a device is in the reboot/reset lifecycle
+#[tauri::command]
+async fn get_device_lifecycle_state(device_id: String) -> Result<DeviceLifecycleState, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    read_lifecycle_state(&SystemRunner, &device_id)
+}
+
+/// How long a powered-off device may take to drop off adb
+const POWER_OFF_TIMEOUT: Duration = Duration::from_secs(30);
+const POWER_OFF_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Check the result of `reboot -p`. The device often goes down before adb hears back, so
+/// a closed connection (or a bare non-zero exit) means it's working, not that it failed;
+/// the adb devices poll afterwards decides.
+fn check_power_off_command(result: Result<CommandOutput, String>) -> Result<(), String> {
+    let output = result?;
+    let stderr = output.stderr.trim();
+    let dropped = ["closed", "connection reset", "protocol fault"];
+    if output.success || stderr.is_empty() || dropped.iter().any(|m| stderr.contains(m)) {
+        Ok(())
+    } else {
+        Err(format!("Power off failed: {}", stderr))
+    }
+}
+
+/// Power the device off and wait for it to drop off adb, which is what a real power-off
+/// looks like from the host. Still listed after `timeout` means it didn't go down (or
+/// booted straight back up, e.g. into charging mode).
+fn power_off_with(
+    runner: &dyn CommandRunner,
+    events: &dyn EventSink,
+    device_id: &str,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<String, String> {
+    check_power_off_command(runner.adb_shell(device_id, &["reboot", "-p"]))?;
+
+    let mut limit = PollLimit::new(device_id, timeout);
+    loop {
+        let state = read_lifecycle_state(runner, device_id)?;
+        if state == DeviceLifecycleState::Absent {
+            emit_event(events, "device-powered-off", serde_json::json!({ "device_id": device_id }));
+            return Ok("Device powered off. It is safe to disconnect.".to_string());
+        }
+        if !limit.next(poll_interval) {
+            if limit.cancelled() {
+                return Err("Stopped waiting for the device to power off.".to_string());
+            }
+            return Err(format!(
+                "Timed out: device is still connected ({:?}) {} seconds after power off. It \
+                 may have rebooted instead; hold the power button to shut it down.",
+                state,
+                timeout.as_secs()
+            ));
+        }
+    }
+}
+
+/// Power a wiped device off so it ships cold
+#[tauri::command]
+async fn power_off_device(
+    window: tauri::Window,
+    state: State<'_, WipeState>,
+    locks: State<'_, DeviceLocks>,
+    audit_log: State<'_, AuditLog>,
+    device_id: String,
+) -> Result<String, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+    if state.device_id.lock().unwrap().as_deref() == Some(device_id.as_str()) {
+        return Err("A wipe is still running on this device. Abort it first.".to_string());
+    }
+    let _device_lock = locks.acquire(&device_id)?;
+
+    let result = power_off_with(
+        &SystemRunner,
+        &window,
+        &device_id,
+        POWER_OFF_TIMEOUT,
+        POWER_OFF_POLL_INTERVAL,
+    );
+    let detail = match &result {
+        Ok(_) => "confirmed".to_string(),
+        Err(e) => e.clone(),
+    };
+    audit(&audit_log, "device-power-off", Some(&device_id), detail);
+    result
+}
+
+/// Check if device is still connected (for polling after reset)
+#[tauri::command]
+async fn check_device_connected(
+    window: tauri::Window,
+    known: State<'_, KnownDevices>,
+    device_id: String,
+) -> Result<bool, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+
+    let output = Command::new("adb")
+        .arg("devices")
+        .traced()
+        .output()
+        .map_err(|e| format!("Failed to check devices: {}", e))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let listed = parse_adb_device_states(&stdout);
+
+    // A remembered device may be back under a new id, or waiting for authorization again
+    for change in known.reassociate(&listed, |id| get_device_prop(id, "ro.serialno")) {
+        emit_event(&window, "device-reassociated", &change);
+    }
+    let current_id = known.current_id(&device_id).unwrap_or(device_id);
+
+    Ok(parse_adb_devices(&stdout).iter().any(|(id, _)| id == &current_id))
+}
+
+/// Brands get_instructions has specific steps for; everything else gets the generic ones.
+/// Keep in step with its match arms.
+const SUPPORTED_BRANDS: [&str; 6] = ["samsung", "google", "oneplus", "motorola", "nothing", "cmf"];
+
+/// Brand keys with specific reset instructions, e.g. for a brand picker
+#[tauri::command]
+fn get_supported_brands() -> Vec<String> {
+    SUPPORTED_BRANDS.iter().map(|b| b.to_string()).collect()
+}
+
+/// Get device-specific factory reset instructions
+#[tauri::command]
+fn get_instructions(brand: String, model: String) -> Vec<String> {
+    let brand_lower = brand.to_lowercase();
+    let model_lower = model.to_lowercase();
+
+    match brand_lower.as_str() {
+        "samsung" => {
+            if model_lower.contains("s24") || model_lower.contains("s25") {
+                vec![
+                    "1. Go to Settings > General management > Reset".to_string(),
+                    "2. Tap 'Factory data reset'".to_string(),
+                    "3. Scroll down and review the information".to_string(),
+                    "4. Tap 'Reset' at the bottom".to_string(),
+                    "5. Enter your PIN/password if prompted".to_string(),
+                    "6. Tap 'Delete all' to confirm".to_string(),
+                    "".to_string(),
+                    "Note: One UI may require Samsung account verification.".to_string(),
+                ]
+            } else if model_lower.contains("a55") || model_lower.contains("a54") {
+                vec![
+                    "1. Go to Settings > General management > Reset".to_string(),
+                    "2. Tap 'Factory data reset'".to_string(),
+                    "3. Review and tap 'Reset'".to_string(),
+                    "4. Enter your PIN and tap 'Delete all'".to_string(),
+                ]
+            } else {
+                vec![
+                    "1. Go to Settings > General management > Reset".to_string(),
+                    "2. Tap 'Factory data reset'".to_string(),
+                    "3. Tap 'Reset' and confirm with your PIN".to_string(),
+                    "4. Tap 'Delete all' to complete".to_string(),
+                ]
+            }
+        }
+        "google" => vec![
+            "1. Go to Settings > System > Reset options".to_string(),
+            "2. Tap 'Erase all data (factory reset)'".to_string(),
+            "3. Tap 'Erase all data' to confirm".to_string(),
+            "4. Enter your PIN if prompted".to_string(),
+            "5. Wait for the device to restart".to_string(),
+        ],
+        "oneplus" => vec![
+            "1. Go to Settings > System > Reset options".to_string(),
+            "2. Tap 'Erase all data (factory reset)'".to_string(),
+            "3. Tap 'Reset phone'".to_string(),
+            "4. Enter your PIN and confirm".to_string(),
+            "5. Device will reboot and reset".to_string(),
+        ],
+        "motorola" => {
+            if model_lower.contains("edge") {
+                vec![
+                    "1. Go to Settings > System > Reset options".to_string(),
+                    "2. Tap 'Erase all data (factory reset)'".to_string(),
+                    "3. Tap 'Erase all data'".to_string(),
+                    "4. Enter your PIN to confirm".to_string(),
+                ]
+            } else {
+                vec![
+                    "1. Go to Settings > System > Advanced > Reset options".to_string(),
+                    "2. Tap 'Erase all data (factory reset)'".to_string(),
+                    "3. Confirm and enter your PIN".to_string(),
+                ]
+            }
+        }
+        "nothing" | "cmf" => vec![
+            "1. Go to Settings > System > Reset options".to_string(),
+            "2. Tap 'Erase all data (factory reset)'".to_string(),
+            "3. Tap 'Erase all data' and confirm".to_string(),
+            "4. Enter your PIN if prompted".to_string(),
+        ],
+        _ => vec![
+            "1. Go to Settings > System (or General Management)".to_string(),
+            "2. Find 'Reset' or 'Reset options'".to_string(),
+            "3. Select 'Factory data reset' or 'Erase all data'".to_string(),
+            "4. Follow on-screen prompts to confirm".to_string(),
+            "5. Enter your PIN/password if requested".to_string(),
+            "".to_string(),
+            "Note: Steps may vary by manufacturer and Android version.".to_string(),
+        ],
+    }
+}
+
+/// Revoke ADB debugging on device (optional security step)
+#[tauri::command]
+async fn revoke_adb(device_id: String) -> Result<String, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+
+    let output = adb_shell(&device_id, &["settings", "put", "global", "adb_enabled", "0"])
+        .traced()
+        .output()
+        .map_err(|e| format!("Failed to revoke ADB: {}", e))?;
+
+    if output.status.success() {
+        Ok("ADB debugging disabled on device. You may need to re-enable it for future use.".to_string())
+    } else {
+        Err("Failed to disable ADB debugging. Device may require root access.".to_string())
+    }
+}
+
+/// Roll the audit log to a timestamped archive now
+#[tauri::command]
+async fn rotate_log(audit_log: State<'_, AuditLog>) -> Result<Option<String>, String> {
+    let archive = audit_log.rotate()?;
+    Ok(archive.and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string())))
+}
+
+/// List rotated audit log archives, oldest first
+#[tauri::command]
+async fn list_log_archives(audit_log: State<'_, AuditLog>) -> Result<Vec<LogArchive>, String> {
+    audit_log.list_archives()
+}
+
+/// Get the current app settings
+#[tauri::command]
+fn get_settings(settings: State<'_, SettingsState>) -> AppSettings {
+    settings.get()
+}
+
+/// Persist new app settings and apply them
+#[tauri::command]
+async fn update_settings(
+    settings_state: State<'_, SettingsState>,
+    audit_log: State<'_, AuditLog>,
+    mut settings: AppSettings,
+) -> Result<AppSettings, String> {
+    settings.operator = settings.operator.as_ref().map(validate_operator).transpose()?;
+    settings.quick_chunk_bounds = settings.quick_chunk_bounds.validate()?;
+    audit_log.set_max_bytes(settings.audit_log_max_bytes);
+    COMMAND_TRACER.set_enabled(settings.command_trace);
+    let exposed =
+        EVENT_BROADCAST.configure(settings.event_websocket, &settings.event_websocket_addr)?;
+    if let Some(warning) = exposed {
+        audit(&audit_log, "event-websocket-exposed", None, warning);
+    }
+    settings_state.save(settings)?;
+    Ok(settings_state.get())
+}
+
+/// Error a certificate/report generation returns when cancel_generation stops it
+const GENERATION_CANCELLED: &str = "Generation cancelled.";
+
+/// Cancellation flags of the certificate and report generations in progress. Each
+/// generation gets its own token, so cancelling stops the ones running and a generation
+/// started after the cancel is unaffected.
+#[derive(Default)]
+struct ArtifactGeneration {
+    running: Mutex<Vec<Weak<AtomicBool>>>,
+}
+
+impl ArtifactGeneration {
+    /// A token for a new generation, live for as long as the generation holds it
+    fn begin(&self) -> GenerationToken {
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut running = self.running.lock().unwrap();
+        running.retain(|generation| generation.strong_count() > 0);
+        running.push(Arc::downgrade(&flag));
+        GenerationToken(flag)
+    }
+
+    /// Cancel every generation in progress; returns how many there were
+    fn cancel(&self) -> usize {
+        let running = self.running.lock().unwrap();
+        running
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(|flag| flag.store(true, Ordering::SeqCst))
+            .count()
+    }
+}
+
+/// One generation's cancellation flag, checked between sessions, pages and rows
+struct GenerationToken(Arc<AtomicBool>);
+
+impl GenerationToken {
+    fn check(&self) -> Result<(), String> {
+        if self.0.load(Ordering::SeqCst) {
+            return Err(GENERATION_CANCELLED.to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Pass a generation's result through, emitting `generation-cancelled` if it was cancelled
+fn finish_generation<T>(
+    events: &dyn EventSink,
+    artifact: &str,
+    result: Result<T, String>,
+) -> Result<T, String> {
+    if matches!(&result, Err(e) if e == GENERATION_CANCELLED) {
+        emit_event(events, "generation-cancelled", serde_json::json!({ "artifact": artifact }));
+    }
+    result
+}
+
+/// Stop the certificate/report generations in progress; each returns GENERATION_CANCELLED
+#[tauri::command]
+fn cancel_generation(generation: State<'_, ArtifactGeneration>) {
+    generation.cancel();
+}
+
+/// Export the signed JSON certificate for a wipe session
+#[tauri::command]
+async fn generate_certificate(
+    window: tauri::Window,
+    sessions: State<'_, SessionStore>,
+    signer: State<'_, SigningKey>,
+    generation: State<'_, ArtifactGeneration>,
+    session_id: String,
+    path: String,
+) -> Result<WipeCertificate, String> {
+    let path = validate_export_path(&path, "json")?;
+    let token = generation.begin();
+    let mut certificate = build_certificate(&sessions.load(&session_id)?);
+    sign_certificate(&mut certificate, &signer)?;
+    finish_generation(&window, "certificate", token.check())?;
+
+    let json = serde_json::to_string_pretty(&certificate)
+        .map_err(|e| format!("Failed to serialize certificate: {}", e))?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write certificate: {}", e))?;
+    Ok(certificate)
+}
+
+/// Export a printable PDF view of a wipe session's certificate
+#[tauri::command]
+async fn generate_certificate_pdf(
+    window: tauri::Window,
+    sessions: State<'_, SessionStore>,
+    signer: State<'_, SigningKey>,
+    generation: State<'_, ArtifactGeneration>,
+    session_id: String,
+    path: String,
+) -> Result<String, String> {
+    let path = validate_export_path(&path, "pdf")?;
+    let token = generation.begin();
+    let mut certificate = build_certificate(&sessions.load(&session_id)?);
+    sign_certificate(&mut certificate, &signer)?;
+    let pdf = render_certificate_pdf(&certificate, &token);
+    let pdf = finish_generation(&window, "certificate-pdf", pdf)?;
+
+    fs::write(&path, pdf).map_err(|e| format!("Failed to write PDF: {}", e))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Signed certificates for every stored wipe of one device, oldest first
+fn find_certificates_with(
+    sessions: &SessionStore,
+    signer: &SigningKey,
+    generation: &GenerationToken,
+    serial: &str,
+) -> Result<Vec<WipeCertificate>, String> {
+    let serial = sanitize_device_id(serial.trim())?;
+    sessions
+        .find_by_serial(&serial)?
+        .iter()
+        .map(|session| {
+            generation.check()?;
+            let mut certificate = build_certificate(session);
+            sign_certificate(&mut certificate, signer)?;
+            Ok(certificate)
+        })
+        .collect()
+}
+
+/// Look up all wipe certificates for a device serial, with their start and completion times
+#[tauri::command]
+async fn find_certificates(
+    window: tauri::Window,
+    sessions: State<'_, SessionStore>,
+    signer: State<'_, SigningKey>,
+    generation: State<'_, ArtifactGeneration>,
+    serial: String,
+) -> Result<Vec<WipeCertificate>, String> {
+    let token = generation.begin();
+    let result = find_certificates_with(&sessions, &signer, &token, &serial);
+    finish_generation(&window, "certificates", result)
+}
+
+/// The events a past wipe emitted, in order, without emitting anything
+#[tauri::command]
+async fn get_session_events(
+    sessions: State<'_, SessionStore>,
+    session_id: String,
+) -> Result<Vec<SessionEvent>, String> {
+    sessions.load_events(&session_id)
+}
+
+/// Emit a past wipe's events again as `session-replay` events, so the UI can step through
+/// the run. They go to this window only, not the WebSocket feed. Returns how many events
+/// were replayed.
+#[tauri::command]
+async fn replay_session(
+    window: tauri::Window,
+    sessions: State<'_, SessionStore>,
+    session_id: String,
+) -> Result<usize, String> {
+    let recorded = sessions.load_events(&session_id)?;
+    Ok(replay_events(&WindowOnly(&window), &recorded))
+}
+
+/// Verify the signature of a previously exported certificate JSON file
+#[tauri::command]
+async fn verify_certificate(
+    signer: State<'_, SigningKey>,
+    json_path: String,
+) -> Result<CertificateVerification, String> {
+    verify_certificate_file(Path::new(&json_path), Some(&signer.verifying_key()))
+}
+
+/// Stop the adb server, but only if this app started it. Returns whether it was stopped.
+#[tauri::command]
+async fn shutdown_adb(adb_server: State<'_, AdbServer>) -> Result<bool, String> {
+    adb_server.shutdown(&SystemRunner)
+}
+
+/// Export every recorded wipe as CSV or JSON, for the user's own inventory
+#[tauri::command]
+async fn export_wipe_history(
+    sessions: State<'_, SessionStore>,
+    format: String,
+    path: String,
+) -> Result<String, String> {
+    let format = format.to_lowercase();
+    if format != "csv" && format != "json" {
+        return Err("Invalid export format. Must be 'csv' or 'json'.".to_string());
+    }
+    let path = validate_export_path(&path, &format)?;
+
+    let entries: Vec<HistoryEntry> = sessions.list()?.iter().map(history_entry).collect();
+    let contents = if format == "csv" {
+        history_to_csv(&entries)
+    } else {
+        history_to_json(&entries)?
+    };
+
+    fs::write(&path, contents).map_err(|e| format!("Failed to write history: {}", e))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Export one summary of a finished batch (CSV, JSON or PDF): each device with its
+/// result, timestamps and the certificate to look up for it
+#[tauri::command]
+async fn export_batch_report(
+    window: tauri::Window,
+    batches: State<'_, BatchStore>,
+    sessions: State<'_, SessionStore>,
+    generation: State<'_, ArtifactGeneration>,
+    batch_id: String,
+    format: String,
+    path: String,
+) -> Result<String, String> {
+    let format = format.to_lowercase();
+    if !["csv", "json", "pdf"].contains(&format.as_str()) {
+        return Err("Invalid export format. Must be 'csv', 'json' or 'pdf'.".to_string());
+    }
+    let path = validate_export_path(&path, &format)?;
+    let token = generation.begin();
+
+    let report = batches.load(&batch_id)?;
+    let entries = batch_report_entries(&report, &sessions.list()?);
+    let contents = match format.as_str() {
+        "csv" => batch_report_to_csv(&entries).into_bytes(),
+        "json" => batch_report_to_json(&report, &entries)?.into_bytes(),
+        _ => {
+            let pdf = render_batch_report_pdf(&report, &entries, &token);
+            finish_generation(&window, "batch-report", pdf)?
+        }
+    };
+
+    fs::write(&path, contents).map_err(|e| format!("Failed to write batch report: {}", e))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Clean up any temporary wipe files on device
+#[tauri::command]
+async fn cleanup_wipe_files(device_id: String) -> Result<String, String> {
+    let device_id = sanitize_device_id(&device_id)?;
+
+    let output = adb_shell(&device_id, &["rm", "-rf", "/sdcard/wipe_temp", "/sdcard/secure_wipe_*"])
+        .traced()
+        .output()
+        .map_err(|e| format!("Failed to cleanup: {}", e))?;
+
+    if output.status.success() {
+        Ok("Temporary wipe files cleaned up.".to_string())
+    } else {
+        // Not critical if cleanup fails
+        Ok("Cleanup attempted. Some files may remain.".to_string())
+    }
+}
+
+// ============================================================================
+// App Setup
+// ============================================================================
+
+/// Longest any single exit cleanup command may run
+const EXIT_COMMAND_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Longest all exit cleanup together may take, so a wedged device can't hang closing
+const EXIT_CLEANUP_DEADLINE: Duration = Duration::from_secs(8);
+
+/// Cleanup any running wipe processes and temp files. Best effort: commands that time
+/// out are skipped.
+fn cleanup_on_exit(runner: &dyn CommandRunner, state: &WipeState) {
+    // Get the device ID if a wipe was in progress
+    let device_id = {
+        let dev_lock = state.device_id.lock().unwrap();
+        dev_lock.clone()
+    };
+
+    // Kill the running wipe script, if any
+    if let Some(pgid) = state.script_group() {
+        kill_process_group(runner, pgid);
+    }
+
+    // If we have a device ID, clean up device-side processes and files
+    if let Some(device_id) = device_id {
+        // Kill dd process on device
+        let _ = runner.adb_shell(&device_id, &["pkill", "-f", "dd if=/dev/urandom"]);
+
+        // Clean up temp files
+        let _ = runner.adb_shell(&device_id, &["rm", "-rf", "/sdcard/wipe_temp/"]);
+    }
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_shell::init())
+        .manage(ScriptCapabilityCache::new())
+        .manage(AdbServer::new())
+        .manage(KnownDevices::new())
+        .manage(WipeState::default())
+        .manage(BatchState::default())
+        .manage(ArtifactGeneration::default())
+        .setup(|app| {
+            let data_dir = app.path().app_data_dir()?;
+            fs::create_dir_all(&data_dir)?;
+
+            let settings = SettingsState::load(&data_dir);
+            let audit_log = AuditLog::new(data_dir.join("logs"), settings.get().audit_log_max_bytes);
+            let app_handle = app.handle().clone();
+            COMMAND_TRACER.set_sink(Box::new(move |trace| {
+                let _ = app_handle.emit("command-trace", trace);
+            }));
+            COMMAND_TRACER.set_enabled(settings.get().command_trace);
+            let current = settings.get();
+            match EVENT_BROADCAST.configure(current.event_websocket, &current.event_websocket_addr)
+            {
+                Ok(None) => {}
+                Ok(Some(warning)) => audit(&audit_log, "event-websocket-exposed", None, warning),
+                Err(e) => audit(&audit_log, "event-websocket-failed", None, e),
+            }
+            app.manage(settings);
+            app.manage(audit_log);
+
+            let locks = DeviceLocks::new(data_dir.join("locks"));
+            locks.clean_stale();
+            app.manage(locks);
+            app.manage(SessionStore::new(data_dir.join("sessions")));
+            app.manage(BatchStore::new(data_dir.join("batches")));
+            app.manage(load_or_create_signing_key(&data_dir)?);
+
+            // Started before any other adb command can start it implicitly, so whether this
+            // app owns the server is always known. check_adb_status retries if this fails.
+            let adb_handle = app.handle().clone();
+            std::thread::spawn(move || {
+                let port = adb_server_port_override();
+                let server = adb_handle.state::<AdbServer>();
+                let _ = server.ensure_started(&SystemRunner, port.as_deref());
+            });
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            check_adb_status,
+            check_adb,
+            refresh_device,
+            get_storage_info,
+            detect_sdcard,
+            get_battery_status,
+            check_wipe_supported,
+            prepare_device,
+            restore_device,
+            check_accounts,
+            get_encryption_status,
+            get_script_capabilities,
+            run_wipe,
+            run_batch_wipe,
+            run_batch_wipe_file,
+            abort_device_in_batch,
+            continue_batch,
+            export_batch_report,
+            cancel_generation,
+            get_session_events,
+            replay_session,
+            abort_wipe,
+            get_current_progress,
+            run_factory_reset,
+            retrigger_factory_reset,
+            poll_reset_started,
+            verify_no_accounts,
+            check_device_connected,
+            remember_device,
+            get_device_lifecycle_state,
+            ping_device,
+            snapshot_storage,
+            try_adb_root,
+            power_off_device,
+            get_instructions,
+            get_supported_brands,
+            revoke_adb,
+            cleanup_wipe_files,
+            list_device_wipe_processes,
+            probe_entropy_rate,
+            kill_device_wipe_processes,
+            rotate_log,
+            list_log_archives,
+            get_settings,
+            update_settings,
+            generate_certificate,
+            generate_certificate_pdf,
+            find_certificates,
+            verify_certificate,
+            export_wipe_history,
+            shutdown_adb,
+        ])
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::CloseRequested { .. } = event {
+                // Cleanup when window is closed
+                let runner = DeadlineRunner::new(EXIT_COMMAND_TIMEOUT, EXIT_CLEANUP_DEADLINE);
+                if let Some(state) = window.try_state::<WipeState>() {
+                    cleanup_on_exit(&runner, &state);
+                }
+                // After device cleanup, which still needs the server
+                let kill_adb = window
+                    .try_state::<SettingsState>()
+                    .is_some_and(|settings| settings.get().kill_adb_server_on_exit);
+                if let (true, Some(adb_server)) = (kill_adb, window.try_state::<AdbServer>()) {
+                    let _ = adb_server.shutdown(&runner);
+                }
+            }
+        })
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh, empty scratch directory for filesystem tests
+    fn test_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!(
+            "securewipe-test-{}-{}-{}",
+            name,
+            std::process::id(),
+            nanos
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Read every audit entry across archives (oldest first) and the live log
+    fn read_all_audit_entries(log: &AuditLog) -> Vec<AuditEntry> {
+        let mut files: Vec<PathBuf> = log
+            .list_archives()
+            .unwrap()
+            .iter()
+            .map(|a| log.dir.join(&a.file_name))
+            .collect();
+        files.push(log.path());
+
+        files
+            .iter()
+            .filter_map(|f| fs::read_to_string(f).ok())
+            .flat_map(|content| {
+                content
+                    .lines()
+                    .map(|l| serde_json::from_str::<AuditEntry>(l).unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sanitize_device_id_valid() {
+        assert!(sanitize_device_id("emulator-5554").is_ok());
+        assert!(sanitize_device_id("192.168.1.1:5555").is_ok());
+        assert!(sanitize_device_id("RFXXXXXXXX").is_ok());
+        assert!(sanitize_device_id("device_123").is_ok());
+    }
+
+    #[test]
+    fn test_sanitize_device_id_invalid() {
+        assert!(sanitize_device_id("").is_err());
+        assert!(sanitize_device_id("device; rm -rf /").is_err());
+        assert!(sanitize_device_id("$(whoami)").is_err());
+        assert!(sanitize_device_id(&"a".repeat(100)).is_err());
+    }
+
+    #[test]
+    fn test_parse_adb_devices() {
+        let output = "List of devices attached\n\
+                      emulator-5554\tdevice\n\
+                      192.168.1.1:5555\tdevice\n\
+                      RF123456\toffline\n";
+
+        let devices = parse_adb_devices(output);
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].0, "emulator-5554");
+        assert_eq!(devices[1].0, "192.168.1.1:5555");
+    }
+
+    #[test]
+    fn test_parse_adb_devices_no_permissions() {
+        let output = "List of devices attached\n\
+                      0123456789ABCDEF\tno permissions (user in plugdev group; are your udev \
+                      rules wrong?); see [http://developer.android.com/tools/device.html]\n";
+
+        assert!(parse_adb_devices(output).is_empty());
+        let listed = parse_adb_device_states(output);
+        assert_eq!(listed, [("0123456789ABCDEF".to_string(), "no permissions".to_string())]);
+
+        let error = no_permissions_error(&listed, "linux").unwrap();
+        assert!(error.contains("0123456789ABCDEF"));
+        assert!(error.contains("udev") && error.contains("plugdev"));
+        assert!(!no_permissions_error(&listed, "macos").unwrap().contains("plugdev"));
+
+        let authorized = parse_adb_device_states("List of devices attached\nRF123456\tdevice\n");
+        assert_eq!(no_permissions_error(&authorized, "linux"), None);
+    }
+
+    #[test]
+    fn test_parse_adb_devices_recovery() {
+        let output = "List of devices attached\n\
+                      RF123456\trecovery\n\
+                      R5CT40ABCDE\tsideload\n";
+
+        // Not usable for a wipe, but connected: reported, not "no device"
+        assert!(parse_adb_devices(output).is_empty());
+        let recovery = parse_recovery_devices(output);
+        assert_eq!(
+            recovery,
+            [
+                ("RF123456".to_string(), "recovery".to_string()),
+                ("R5CT40ABCDE".to_string(), "sideload".to_string()),
+            ]
+        );
+        let error = recovery_mode_error(&parse_adb_device_states(output)).unwrap();
+        assert!(error.contains("RF123456") && error.contains("recovery mode (recovery)"));
+        assert_eq!(lifecycle_state(Some("sideload"), false), DeviceLifecycleState::Recovery);
+
+        let normal = parse_adb_device_states("List of devices attached\nRF123456\tdevice\n");
+        assert_eq!(recovery_mode_error(&normal), None);
+    }
+
+    #[test]
+    fn test_parse_adb_devices_long() {
+        let output = "List of devices attached\n\
+                      RF123456               device usb:1-1 product:dm3qsqw model:SM_S918U \
+                      device:dm3q transport_id:3\n\
+                      192.168.1.20:5555      device product:panther model:Pixel_7 \
+                      device:panther transport_id:12\n\
+                      R5CT40ABCDE            unauthorized usb:1-2 transport_id:4\n\
+                      emulator-5554\tdevice\n";
+
+        let listed = parse_adb_devices_long(output);
+        assert_eq!(listed.len(), 4);
+        assert_eq!(
+            listed[0],
+            AdbDeviceListing {
+                id: "RF123456".to_string(),
+                state: "device".to_string(),
+                product: Some("dm3qsqw".to_string()),
+                model: Some("SM S918U".to_string()),
+                device: Some("dm3q".to_string()),
+                transport_id: Some(3),
+            }
+        );
+        assert_eq!(listed[1].model.as_deref(), Some("Pixel 7"));
+        assert_eq!(listed[1].transport_id, Some(12));
+        // Unauthorized devices don't report their model; plain output has no fields at all
+        assert_eq!((listed[2].state.as_str(), listed[2].model.as_ref()), ("unauthorized", None));
+        assert_eq!(listed[2].transport_id, Some(4));
+        assert_eq!((listed[3].product.as_ref(), listed[3].transport_id), (None, None));
+
+        // The long format still lists the same ids and states
+        let ids: Vec<String> = parse_adb_devices(output).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, ["RF123456", "192.168.1.20:5555", "emulator-5554"]);
+    }
+
+    #[test]
+    fn test_parse_adb_devices_empty() {
+        let output = "List of devices attached\n\n";
+        let devices = parse_adb_devices(output);
+        assert!(devices.is_empty());
+    }
+
+    #[test]
+    fn test_storage_measurement_syncs_first() {
+        let runner = RecordingRunner::new(vec![
+            ("shell sync", ok("")),
+            ("shell df", ok(DF_OUTPUT)),
+            ("cat /proc/partitions", failed("Permission denied")),
+            ("shell mount", ok("")),
+        ]);
+        let info = read_synced_storage_info_with(&runner, "RF123456", "/sdcard").unwrap();
+        assert!(info.available_mb > 0);
+        let commands = runner.commands();
+        let sync = commands.iter().position(|c| c.ends_with("shell sync")).unwrap();
+        let df = commands.iter().position(|c| c.contains("shell df")).unwrap();
+        assert!(sync < df);
+    }
+
+    #[test]
+    fn test_parse_df_output() {
+        // Real Samsung S24 output format (1K-blocks, not MB)
+        let output = "Filesystem     1K-blocks    Used Available Use% Mounted on\n\
+                      /dev/fuse      483563724 3229496 480203156   1% /storage/emulated\n";
+
+        let info = parse_df_output(output).unwrap();
+        // Values are converted from KB to MB (divided by 1024)
+        assert_eq!(info.total_mb, 483563724 / 1024);  // ~472230 MB
+        assert_eq!(info.used_mb, 3229496 / 1024);     // ~3153 MB
+        assert_eq!(info.available_mb, 480203156 / 1024); // ~468948 MB
+        assert_eq!(info.percent_used, 1);
+    }
+
+    #[test]
+    fn test_parse_proc_partitions() {
+        // UFS device: three LUNs plus partitions and virtual block devices
+        let output = "major minor  #blocks  name\n\
+                      \n\
+                      \x20  1        0       8192 ram0\n\
+                      \x20  7        0     102400 loop0\n\
+                      \x20  8        0  124993536 sda\n\
+                      \x20  8        1       8192 sda1\n\
+                      \x20  8       34  110000000 sda34\n\
+                      \x20  8       16       8192 sdb\n\
+                      \x20  8       32       8192 sdc\n\
+                      \x20253        0    2097152 zram0\n\
+                      \x20254        5  110000000 dm-5\n";
+        assert_eq!(parse_proc_partitions(output, &[]), Some((124993536 + 8192 + 8192) * 1024));
+
+        // eMMC: boot and rpmb areas don't count
+        let emmc = "major minor  #blocks  name\n\
+                    \x20179        0   61071360 mmcblk0\n\
+                    \x20179        1     131072 mmcblk0p1\n\
+                    \x20179       32       4096 mmcblk0boot0\n\
+                    \x20179       96        512 mmcblk0rpmb\n";
+        assert_eq!(parse_proc_partitions(emmc, &[]), Some(61071360 * 1024));
+
+        // An SD card (mmcblk1) is another whole mmc disk, but not internal storage
+        let with_card = format!(
+            "{}\x20179       64   62333952 mmcblk1\n\x20179       65   62332928 mmcblk1p1\n",
+            emmc
+        );
+        let volumes = "emulated;0 mounted null\npublic:179,65 mounted 1234-ABCD\n";
+        let card = parse_public_volume_devices(volumes);
+        assert_eq!(card, [(179, 65)]);
+        assert_eq!(parse_proc_partitions(&with_card, &card), Some(61071360 * 1024));
+        // A card formatted without a partition table is its own volume
+        assert_eq!(parse_proc_partitions(&with_card, &[(179, 64)]), Some(61071360 * 1024));
+
+        let nvme = "259 0 250059096 nvme0n1\n259 1 524288 nvme0n1p1\n";
+        assert_eq!(parse_proc_partitions(nvme, &[]), Some(250059096 * 1024));
+
+        // Permission denied (no root) or nothing recognizable
+        let denied = "cat: /proc/partitions: Permission denied";
+        assert_eq!(parse_proc_partitions(denied, &[]), None);
+    }
+
+    #[test]
+    fn test_parse_df_output_invalid() {
+        let output = "Error: device not found\n";
+        assert!(parse_df_output(output).is_err());
+    }
+
+    #[test]
+    fn test_parse_battery_status() {
+        let output = "Current Battery Service state:\n  AC powered: false\n  USB powered: true\n  \
+                      Wireless powered: false\n  status: 2\n  health: 2\n  present: true\n  \
+                      level: 85\n  scale: 100\n  voltage: 4301\n";
+        let status = parse_battery_status(output).unwrap();
+        assert_eq!(status.level, 85);
+        assert!(status.charging);
+        assert_eq!(status.power_source.as_deref(), Some("USB"));
+
+        let unplugged = "  AC powered: false\n  USB powered: false\n  status: 3\n  level: 40\n";
+        let status = parse_battery_status(unplugged).unwrap();
+        assert!(!status.charging);
+        assert_eq!(status.power_source, None);
+
+        assert!(parse_battery_status("error: device offline").is_err());
+    }
+
+    #[test]
+    fn test_battery_monitor_unplug_transition() {
+        let reading = |level, charging| BatteryStatus {
+            level,
+            charging,
+            power_source: charging.then(|| "USB".to_string()),
+        };
+        let mut monitor = BatteryMonitor::new(30);
+
+        assert_eq!(monitor.check(&reading(25, true)), None);
+        // Charging -> discharging below the threshold warns once
+        let warning = monitor.check(&reading(24, false)).unwrap();
+        assert!(warning.starts_with("Charger disconnected at 24% battery"));
+        assert_eq!(monitor.check(&reading(23, false)), None);
+
+        // Plugged back in and pulled again: warns again
+        assert_eq!(monitor.check(&reading(23, true)), None);
+        assert!(monitor.check(&reading(23, false)).is_some());
+
+        // Unplugging above the threshold is fine
+        let mut monitor = BatteryMonitor::new(30);
+        monitor.check(&reading(80, true));
+        assert_eq!(monitor.check(&reading(79, false)), None);
+    }
+
+    #[test]
+    fn test_low_battery_warning_attached() {
+        let recorder = EventRecorder::default();
+        let mut monitor = BatteryMonitor::new(30);
+        let mut warnings = Vec::new();
+        let reading = |level: u8| BatteryStatus {
+            level,
+            charging: false,
+            power_source: None,
+        };
+        record_battery_reading(&mut monitor, reading(40), &recorder, &mut warnings);
+        record_battery_reading(&mut monitor, reading(25), &recorder, &mut warnings);
+        record_battery_reading(&mut monitor, reading(24), &recorder, &mut warnings);
+        assert_eq!(
+            recorder.sequence(),
+            ["battery-status", "battery-warning", "battery-status", "battery-status"]
+        );
+
+        // The wipe carried on and succeeded: the warning rides along with the result
+        let mut session = sample_session();
+        warnings.extend(outcome_warnings(&session));
+        session.warnings = warnings;
+        let codes: Vec<WarningCode> = session.warnings.iter().map(|w| w.code).collect();
+        assert_eq!(codes, [WarningCode::LowBattery, WarningCode::WearLeveling]);
+        assert!(session.warnings[0].message.contains("25%"));
+        let payload = wipe_complete_payload(&session, false);
+        assert_eq!(payload["success"], true);
+        assert_eq!(payload["warnings"][0]["code"], "low_battery");
+
+        let mut partial = sample_session();
+        partial.free_space_coverage = Some(free_space_coverage(1 << 34, 1 << 30, true));
+        let codes: Vec<WarningCode> = outcome_warnings(&partial).iter().map(|w| w.code).collect();
+        assert_eq!(codes, [WarningCode::PartialCoverage, WarningCode::WearLeveling]);
+        partial.success = false;
+        assert!(outcome_warnings(&partial).is_empty());
+    }
+
+    #[test]
+    fn test_battery_watch_during_wipe() {
+        // The charger is pulled at 20% partway through the wipe
+        let battery = |powered: bool| {
+            ok(&format!("Current Battery Service state:\n  AC powered: {}\n  level: 20\n", powered))
+        };
+        let mut fixture = WipeFixture::new("battery-watch");
+        fixture.runner = Arc::new(RecordingRunner::sequenced(vec![(
+            "dumpsys battery",
+            vec![battery(true), battery(false)],
+        )]));
+        let recorder = Arc::new(EventRecorder::default());
+        let watched = recorder.clone();
+        let mut spawn = spawn_each(vec![ScriptedProcess {
+            output: Some(QUICK_RUN_OUTPUT),
+            // Keep the script running until the watch has seen the unplug
+            on_finish: Box::new(move || {
+                let deadline = Instant::now() + Duration::from_secs(10);
+                while !watched.sequence().iter().any(|e| e == "battery-warning") {
+                    assert!(Instant::now() < deadline, "no battery warning");
+                    std::thread::sleep(Duration::from_millis(20));
+                }
+                true
+            }),
+        }]);
+        let mut ctx = fixture.ctx(VerifyFailurePolicy::Warn);
+        ctx.settings.battery_poll_secs = 1;
+        assert!(execute_wipe(&ctx, recorder.clone(), sample_run(), &mut spawn).is_ok());
+        drop(spawn);
+
+        let events = recorder.sequence();
+        let statuses = events.iter().filter(|e| *e == "battery-status").count();
+        assert!(statuses >= 2);
+        let session = &fixture.sessions.list().unwrap()[0];
+        let low = session.warnings.iter().find(|w| w.code == WarningCode::LowBattery).unwrap();
+        assert!(low.message.starts_with("Charger disconnected at 20% battery"));
+    }
+
+    #[test]
+    fn test_adb_shell_args() {
+        assert_eq!(
+            adb_shell_args("RF123456", &["pkill", "-f", "dd if=/dev/urandom"]),
+            ["-s", "RF123456", "shell", "pkill -f 'dd if=/dev/urandom'"]
+        );
+        // Globs are left for the device shell to expand
+        let argv = ["rm", "-rf", "/sdcard/wipe_temp", "/sdcard/secure_wipe_*"];
+        assert_eq!(
+            adb_shell_args("emulator-5554", &argv),
+            ["-s", "emulator-5554", "shell", "rm -rf /sdcard/wipe_temp /sdcard/secure_wipe_*"]
+        );
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote("a;reboot"), "'a;reboot'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn test_event_websocket_rebroadcast() {
+        static BROADCASTER: EventBroadcaster = EventBroadcaster::new();
+        assert_eq!(BROADCASTER.configure(true, "127.0.0.1:0"), Ok(None));
+        let addr = BROADCASTER.listening.lock().unwrap().as_ref().unwrap().0;
+        let url = format!("ws://{}/", addr);
+        let connect = || {
+            let (client, response) = tungstenite::connect(&url).unwrap();
+            assert_eq!(response.status(), 101);
+            client
+        };
+        let mut clients = [connect(), connect()];
+
+        // Each client is registered by its own thread just after its handshake
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while BROADCASTER.clients.lock().unwrap().len() < 2 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let payload = serde_json::json!({ "percent": 42.5, "message": "Pass 1 of 3" });
+        BROADCASTER.broadcast("wipe-progress", &payload);
+        for client in &mut clients {
+            let text = client.read().unwrap().into_text().unwrap();
+            let message: serde_json::Value = serde_json::from_str(&text).unwrap();
+            assert_eq!(message["event"], "wipe-progress");
+            assert_eq!(message["payload"], payload);
+        }
+
+        // Turning it off disconnects the clients and closes the listener
+        assert_eq!(BROADCASTER.configure(false, "127.0.0.1:0"), Ok(None));
+        for client in &mut clients {
+            assert!(!matches!(client.read(), Ok(tungstenite::Message::Text(_))));
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while std::net::TcpStream::connect(addr).is_ok() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(std::net::TcpStream::connect(addr).is_err());
+
+        // Listening beyond loopback works, with a warning
+        let exposed = BROADCASTER.configure(true, "0.0.0.0:0").unwrap().unwrap();
+        assert!(exposed.contains("anyone on the network"));
+        assert_eq!(BROADCASTER.configure(false, "0.0.0.0:0"), Ok(None));
+    }
+
+    #[test]
+    fn test_progress_interpolation_bounds() {
+        let real = |pass: u32, percent: f32| WipeProgress {
+            pass,
+            total_passes: 3,
+            percent,
+            bytes_written: 0,
+            message: format!("Pass {} of 3", pass),
+            phase: "writing".to_string(),
+            rate: None,
+            estimated: false,
+        };
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut interpolator = ProgressInterpolator::default();
+
+        // One data point gives no rate, so nothing to extrapolate from
+        assert_eq!(interpolator.on_progress(real(1, 10.0), at(0)).rate, None);
+        assert!(interpolator.estimate(at(5)).is_none());
+
+        // 10% -> 20% in 10s is 1%/s
+        let progress = interpolator.on_progress(real(1, 20.0), at(10));
+        assert_eq!(progress.rate, Some(1.0));
+        assert!(!progress.estimated);
+
+        let estimate = interpolator.estimate(at(15)).unwrap();
+        assert!(estimate.estimated);
+        assert_eq!((estimate.pass, estimate.percent), (1, 25.0));
+
+        // Capped at the end of pass 1 (33.3%) however long the script stays quiet
+        for secs in [11, 20, 60, 3600] {
+            let estimate = interpolator.estimate(at(secs)).unwrap();
+            assert!(estimate.percent >= 20.0 && estimate.percent <= 100.0 / 3.0 + 0.01);
+        }
+
+        // A new pass restarts the rate instead of carrying the old one across
+        assert_eq!(interpolator.on_progress(real(2, 34.0), at(40)).rate, None);
+        assert!(interpolator.estimate(at(45)).is_none());
+    }
+
+    #[test]
+    fn test_parse_mount_fs_type() {
+        let toybox = "/dev/block/dm-0 on / type ext4 (ro,seclabel,relatime)\n\
+                      tmpfs on /dev type tmpfs (rw,seclabel,nosuid,relatime,mode=755)\n\
+                      /dev/block/dm-5 on /data type f2fs (rw,lazytime,seclabel,nosuid)\n\
+                      /dev/fuse on /storage/emulated type fuse (rw,lazytime,nosuid)\n";
+        assert_eq!(parse_mount_fs_type(toybox, "/data").as_deref(), Some("f2fs"));
+        assert_eq!(parse_mount_fs_type(toybox, "/").as_deref(), Some("ext4"));
+        assert_eq!(parse_mount_fs_type(toybox, "/data/media"), None);
+
+        let proc_mounts = "/dev/block/sda20 /data ext4 rw,seclabel,noatime 0 0\n\
+                           /data/media /mnt/runtime/default/emulated sdcardfs rw,nosuid 0 0\n";
+        assert_eq!(parse_mount_fs_type(proc_mounts, "/data").as_deref(), Some("ext4"));
+        assert_eq!(parse_mount_fs_type("", "/data"), None);
+
+        // Shared storage reports the filesystem underneath the FUSE view
+        let runner = FakeRunner(vec![("shell mount", ok(toybox))]);
+        assert_eq!(read_fs_type(&runner, "RF123456", "/sdcard").as_deref(), Some("f2fs"));
+        assert!(fs_type_caveat("f2fs").is_some());
+        assert_eq!(fs_type_caveat("ext4"), None);
+    }
+
+    #[test]
+    fn test_check_user_data_path() {
+        assert_eq!(check_user_data_path(WIPE_TEMP_DIR).unwrap(), "/sdcard/wipe_temp");
+        assert_eq!(check_user_data_path("/sdcard").unwrap(), "/sdcard");
+        assert_eq!(
+            check_user_data_path("/storage//emulated/0/./wipe_temp/").unwrap(),
+            "/storage/emulated/0/wipe_temp"
+        );
+        assert!(check_user_data_path("/data/media/0/wipe_temp").is_ok());
+
+        for system in ["/system", "/vendor/lib", "/data", "/data/data/app", "/", "/mnt/vendor"] {
+            let error = check_user_data_path(system).unwrap_err();
+            assert!(error.starts_with("Refusing to wipe system path"), "{}", error);
+        }
+        // Prefix of a root name, not a path inside it
+        assert!(check_user_data_path("/sdcardx").is_err());
+        assert!(check_user_data_path("/data/mediaserver").is_err());
+        assert!(check_user_data_path("/sdcard/../system").is_err());
+    }
+
+    #[test]
+    fn test_resolved_wipe_path_must_stay_in_user_storage() {
+        let resolves_to = |target: &str| {
+            FakeRunner(vec![("readlink -f", ok(&format!("{}\n", target)))])
+        };
+
+        let runner = resolves_to("/storage/emulated/0/wipe_temp");
+        assert_eq!(
+            resolve_device_path_with(&runner, "RF123456", WIPE_TEMP_DIR).unwrap(),
+            "/storage/emulated/0/wipe_temp"
+        );
+        assert!(check_resolved_user_data_path_with(&runner, "RF123456", WIPE_TEMP_DIR).is_ok());
+        let runner = resolves_to("/mnt/user/0/emulated/0/wipe_temp");
+        assert!(check_resolved_user_data_path_with(&runner, "RF123456", WIPE_TEMP_DIR).is_ok());
+
+        // A tampered link pointing at a system partition
+        let runner = resolves_to("/system/wipe_temp");
+        let error =
+            check_resolved_user_data_path_with(&runner, "RF123456", WIPE_TEMP_DIR).unwrap_err();
+        assert!(error.contains("resolves to /system/wipe_temp"));
+        let runner = resolves_to("/mnt/user");
+        assert!(check_resolved_user_data_path_with(&runner, "RF123456", "/sdcard").is_err());
+
+        // Unresolvable paths are refused rather than trusted
+        let runner = FakeRunner(vec![("readlink", failed("No such file or directory"))]);
+        assert!(check_resolved_user_data_path_with(&runner, "RF123456", WIPE_TEMP_DIR).is_err());
+        assert!(resolve_device_path_with(&resolves_to(""), "RF123456", WIPE_TEMP_DIR).is_err());
+    }
+
+    #[test]
+    fn test_clear_app_data() {
+        let listing = "package:com.example.notes\n\
+                       package:org.mozilla.firefox\n\
+                       package:com.corp.mdm\n\
+                       package:bad;rm -rf /\n\
+                       \n";
+        assert_eq!(
+            parse_package_list(listing),
+            ["com.example.notes", "org.mozilla.firefox", "com.corp.mdm"]
+        );
+        assert!(parse_package_list("").is_empty());
+        assert_eq!(pm_clear_args("com.example.notes"), ["pm", "clear", "com.example.notes"]);
+
+        let runner = FakeRunner(vec![
+            ("pm list packages -3", ok(listing)),
+            ("pm clear com.example.notes", ok("Success\n")),
+            ("pm clear org.mozilla.firefox", ok("Success\n")),
+            ("pm clear com.corp.mdm", ok("Failed\n")),
+        ]);
+        let result = clear_app_data_with(&runner, "RF123456").unwrap();
+        assert_eq!(result.cleared, ["com.example.notes", "org.mozilla.firefox"]);
+        assert_eq!(result.failed, ["com.corp.mdm"]);
+
+        let dir = test_dir("clear-app-data");
+        let audit_log = AuditLog::new(dir.join("logs"), DEFAULT_AUDIT_LOG_MAX_BYTES);
+        let events = EventRecorder::default();
+        let warning = clear_app_data_step(&runner, &events, &audit_log, "RF123456").unwrap();
+        assert_eq!(warning.code, WarningCode::AppDataRemains);
+        assert!(warning.message.contains("com.corp.mdm"));
+        assert_eq!(events.sequence(), ["app-data-cleared", "wipe-advisory"]);
+
+        let refused = FakeRunner(vec![("pm list packages -3", failed("pm: not found"))]);
+        let warning = clear_app_data_step(&refused, &events, &audit_log, "RF123456").unwrap();
+        assert!(warning.message.contains("pm: not found"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_entropy_rate_classification() {
+        let mb = 1024 * 1024;
+        let fast = classify_entropy_rate(16 * mb, 0.1);
+        assert_eq!(fast.mb_per_sec, 160.0);
+        assert!(!fast.slow && fast.message.is_none());
+
+        let slow = classify_entropy_rate(16 * mb, 2.0);
+        assert_eq!(slow.mb_per_sec, 8.0);
+        assert!(slow.slow);
+        assert!(slow.message.unwrap().contains("8.0 MB/s"));
+
+        assert!(!classify_entropy_rate(16 * mb, 0.8).slow); // exactly 20 MB/s
+        assert!(classify_entropy_rate(16 * mb, 0.81).slow);
+        assert!(!classify_entropy_rate(16 * mb, 0.0).slow);
+
+        let toybox = "16+0 records in\n16+0 records out\n\
+                      16777216 bytes (16 M) copied, 1.600 s, 10 M/s\n";
+        assert_eq!(parse_dd_summary(toybox), Some((16 * mb, 1.6)));
+        let gnu = "16777216 bytes (17 MB, 16 MiB) copied, 0.0912 s, 184 MB/s";
+        assert_eq!(parse_dd_summary(gnu), Some((16 * mb, 0.0912)));
+        assert_eq!(parse_dd_summary("dd: /dev/urandom: Permission denied"), None);
+
+        let runner = FakeRunner(vec![("dd if=/dev/urandom", failed(toybox))]);
+        assert!(probe_entropy_rate_with(&runner, "RF123456").is_err());
+        let runner = FakeRunner(vec![(
+            "dd if=/dev/urandom",
+            CommandOutput {
+                success: true,
+                stdout: String::new(),
+                stderr: toybox.to_string(),
+            },
+        )]);
+        assert!(probe_entropy_rate_with(&runner, "RF123456").unwrap().slow);
+    }
+
+    #[test]
+    fn test_adbd_restart_tolerance() {
+        let tolerant = |replies: Vec<(&'static str, Vec<CommandOutput>)>| AdbdRestartTolerant {
+            inner: RecordingRunner::sequenced(replies),
+            timeout: Duration::from_millis(50),
+            poll: Duration::ZERO,
+        };
+
+        // adbd drops mid-command, get-state flaps back to "device", the command is rerun
+        let runner = tolerant(vec![
+            ("dumpsys account", vec![failed("error: closed"), ok("Accounts: 0\n")]),
+            ("get-state", vec![failed("error: device offline"), ok("device\n")]),
+        ]);
+        let out = runner.adb_shell("RF123456", &["dumpsys", "account"]).unwrap();
+        assert_eq!(out.stdout, "Accounts: 0\n");
+
+        // adbd never comes back: the original failure is reported
+        let runner = tolerant(vec![
+            ("dumpsys account", vec![failed("error: device 'RF123456' not found")]),
+            ("get-state", vec![failed("error: device 'RF123456' not found")]),
+        ]);
+        let out = runner.adb_shell("RF123456", &["dumpsys", "account"]).unwrap();
+        assert!(out.stderr.contains("not found"));
+
+        // Ordinary failures are not retried (get-state is never asked)
+        let runner = tolerant(vec![("settings", vec![failed("Permission denial")])]);
+        assert!(!runner.adb_shell("RF123456", &["settings", "put"]).unwrap().success);
+        assert_eq!(runner.inner.commands().len(), 1);
+
+        // try_adb_root: production build refuses, debuggable build restarts as root
+        let refused = FakeRunner(vec![
+            ("root", ok("adbd cannot run as root in production builds\n")),
+            ("id -u", ok("2000\n")),
+        ]);
+        let result = try_adb_root_with(&refused, "RF123456", Duration::ZERO, Duration::ZERO);
+        assert!(result.is_ok_and(|r| !r.root && !r.restarted));
+        let debuggable = FakeRunner(vec![
+            ("root", ok("restarting adbd as root\n")),
+            ("get-state", ok("device\n")),
+            ("id -u", ok("0\n")),
+        ]);
+        let result = try_adb_root_with(&debuggable, "RF123456", Duration::ZERO, Duration::ZERO);
+        assert!(result.is_ok_and(|r| r.root && r.restarted));
+    }
+
+    fn quick_config(size_mb: u32) -> WipeConfig {
+        WipeConfig {
+            mode: "quick".to_string(),
+            passes: 1,
+            size_mb: Some(size_mb),
+            double_reset: false,
+            expect_serial: None,
+            expect_model: None,
+            max_duration_secs: 0,
+            keep_temp_files: false,
+            pattern: None,
+            emulator_test: false,
+            skip_factory_reset: false,
+            clear_app_data: false,
+        }
+    }
+
+    #[test]
+    fn test_prepare_and_restore_device() {
+        let runner = FakeRunner(vec![
+            ("echo ok", ok("ok\n")),
+            ("settings get global stay_on_while_plugged_in", ok("0\n")),
+            ("settings get system screen_off_timeout", ok("null\n")),
+            ("settings put", ok("")),
+            ("mkdir -p /sdcard/wipe_temp", ok("")),
+            ("df /sdcard", ok(DF_OUTPUT)),
+        ]);
+        let report = prepare_device_with(&runner, "RF123456", &quick_config(1024));
+        assert!(report.ready, "{:?}", report.steps);
+        let names: Vec<&str> = report.steps.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["authorization", "keep_awake", "screen_timeout", "temp_dir", "free_space"]
+        );
+        assert_eq!(report.restore.stay_on_while_plugged_in.as_deref(), Some("0"));
+        assert_eq!(report.restore.screen_off_timeout.as_deref(), Some("null"));
+
+        let teardown = FakeRunner(vec![
+            ("settings put global stay_on_while_plugged_in 0", ok("")),
+            ("settings delete system screen_off_timeout", ok("")),
+        ]);
+        restore_device_with(&teardown, "RF123456", &report.restore).unwrap();
+        // Nothing recorded, nothing run
+        restore_device_with(&FakeRunner(vec![]), "RF123456", &DeviceRestore::default()).unwrap();
+    }
+
+    #[test]
+    fn test_prepare_device_failed_step() {
+        // The timeout can't be changed (e.g. a managed device); the rest still runs
+        let runner = FakeRunner(vec![
+            ("echo ok", ok("ok\n")),
+            ("settings get global", ok("0\n")),
+            ("settings put global", ok("")),
+            ("settings get system", ok("60000\n")),
+            ("settings put system", failed("java.lang.SecurityException: Permission denial")),
+            ("mkdir -p", ok("")),
+            ("df /sdcard", ok(DF_OUTPUT)),
+        ]);
+        let report = prepare_device_with(&runner, "RF123456", &quick_config(1024));
+        assert!(!report.ready);
+        let failed_steps: Vec<&str> =
+            report.steps.iter().filter(|s| !s.ok).map(|s| s.name.as_str()).collect();
+        assert_eq!(failed_steps, ["screen_timeout"]);
+        assert!(report.steps[2].detail.contains("SecurityException"));
+        assert_eq!(report.restore.screen_off_timeout, None);
+        assert_eq!(report.restore.stay_on_while_plugged_in.as_deref(), Some("0"));
+
+        // Unauthorized: nothing else is attempted
+        let locked = FakeRunner(vec![("echo ok", failed("error: device unauthorized."))]);
+        let report = prepare_device_with(&locked, "RF123456", &quick_config(1024));
+        assert_eq!(report.steps.len(), 1);
+        assert!(!report.ready);
+    }
+
+    #[test]
+    fn test_parse_wipe_processes() {
+        let ps = "  PID ARGS\n\
+                  1 init second_stage\n\
+                  8123 sh -c for pass in $(seq 1 3); do dd if=/dev/urandom bs=1048576; done\n\
+                  8140 dd if=/dev/urandom bs=1048576 count=64\n\
+                  8141 grep -v -e records -e copied\n\
+                  9002 /system/bin/dd if=/dev/urandom of=/sdcard/wipe_temp/wipe_pass_2.bin\n\
+                  9100 dd if=/dev/zero of=/sdcard/test.bin\n\
+                  9200 pkill -f dd if=/dev/urandom\n";
+        let found = parse_wipe_processes(ps);
+        let pids: Vec<u32> = found.iter().map(|p| p.pid).collect();
+        assert_eq!(pids, [8140, 9002]);
+        assert_eq!(found[0].command, "dd if=/dev/urandom bs=1048576 count=64");
+        assert!(parse_wipe_processes("  PID ARGS\n").is_empty());
+
+        // Nothing lingering: no kill is sent
+        let idle = FakeRunner(vec![("ps -A", ok("  PID ARGS\n    1 init\n"))]);
+        let result = kill_wipe_processes_with(&idle, "RF123456").unwrap();
+        assert!(result.found.is_empty() && result.killed.is_empty());
+    }
+
+    #[test]
+    fn test_statfs_cross_check() {
+        // toybox: counts in 4K fundamental blocks
+        let toybox = "  File: \"/sdcard\"\n    ID: 8d0b8b8e2f2c Namelen: 255     Type: fuseblk\n\
+                      Block size: 4096    Fundamental block size: 4096\n\
+                      Blocks: Total: 28812309   Free: 17392581   Available: 17392581\n\
+                      Inodes: Total: 7200768    Free: 7013486\n";
+        assert_eq!(parse_stat_f_output(toybox), Some((112548, 67939)));
+        // GNU stat, where the block size and fundamental block size differ
+        let gnu = "  File: \"/data\"\n    ID: 1234 Namelen: 255     Type: ext2/ext3\n\
+                   Block size: 4096       Fundamental block size: 1024\n\
+                   Blocks: Total: 1048576    Free: 524288     Available: 262144\n";
+        assert_eq!(parse_stat_f_output(gnu), Some((1024, 256)));
+        assert_eq!(parse_stat_f_output("stat: '/sdcard': No such file or directory"), None);
+
+        // DF_OUTPUT: 112548MB total, 67939MB available
+        let df = parse_df_output(DF_OUTPUT).unwrap();
+        let check = reconcile_storage(&df, 112548, 67939);
+        assert!(!check.discrepancy, "{}", check.detail);
+        // A few hundred MB of drift is inside the 2% tolerance; half the free space isn't
+        assert!(!reconcile_storage(&df, 112548, 67500).discrepancy);
+        let check = reconcile_storage(&df, 112548, 33000);
+        assert!(check.discrepancy);
+        assert_eq!(
+            check.detail,
+            "df and statfs disagree: available 67939MB (df) vs 33000MB (statfs)."
+        );
+
+        let runner = FakeRunner(vec![("stat -f /sdcard", ok(toybox))]);
+        let check = cross_check_statfs(&runner, "RF123456", "/sdcard", &df).unwrap();
+        assert_eq!((check.statfs_total_mb, check.discrepancy), (112548, false));
+        let unsupported = FakeRunner(vec![("stat -f", failed("stat: Unknown option f"))]);
+        assert!(cross_check_statfs(&unsupported, "RF123456", "/sdcard", &df).is_none());
+    }
+
+    #[test]
+    fn test_spaces_and_unicode_in_device_args() {
+        // A storage path with a space reaches df as a single word
+        let path = validate_device_path("/storage/My Card").unwrap();
+        assert_eq!(
+            adb_shell_args("RF123456", &["df", &path]),
+            ["-s", "RF123456", "shell", "df '/storage/My Card'"]
+        );
+        let runner = FakeRunner(vec![
+            ("df '/storage/My Card'", ok(DF_OUTPUT)),
+            ("/proc/partitions", failed("")),
+        ]);
+        assert!(read_storage_info_with(&runner, "RF123456", &path).is_ok());
+        assert!(validate_device_path("/storage/Música").is_ok());
+        assert!(validate_device_path("/storage/My Card'; reboot").is_err());
+
+        // Model names with spaces and non-ASCII compare whole, and quote as one word
+        let device = DeviceInfo {
+            id: "RF123456".to_string(),
+            model: "Galaxy S24 Ultra".to_string(),
+            brand: "samsung".to_string(),
+            android_version: "14".to_string(),
+            usb_speed: None,
+            ab_device: false,
+            bootloader: None,
+            baseband: None,
+        };
+        let mut config: WipeConfig = serde_json::from_str(
+            r#"{"mode": "quick", "passes": 1, "size_mb": 64, "double_reset": false}"#,
+        )
+        .unwrap();
+        config.expect_model = Some("galaxy s24 ultra".to_string());
+        assert!(check_expected_identity(&config, &device, "RF123456").is_ok());
+        config.expect_model = Some("Galaxy S24".to_string());
+        assert!(check_expected_identity(&config, &device, "RF123456").is_err());
+        assert_eq!(shell_quote("Téléphone d'Ana"), "'Téléphone d'\\''Ana'");
+    }
+
+    /// Canned outputs keyed by a substring of the command line; anything else fails
+    struct FakeRunner(Vec<(&'static str, CommandOutput)>);
+
+    impl CommandRunner for FakeRunner {
+        fn run(&self, program: &str, args: &[String]) -> Result<CommandOutput, String> {
+            let line = format!("{} {}", program, args.join(" "));
+            self.0
+                .iter()
+                .find(|(pattern, _)| line.contains(pattern))
+                .map(|(_, out)| out.clone())
+                .ok_or_else(|| format!("unexpected command: {}", line))
+        }
+    }
+
+    /// FakeRunner that also records every command line it's asked to run. A command can
+    /// be given a sequence of outputs, replied in turn with the last one repeating.
+    struct RecordingRunner {
+        replies: Mutex<Vec<(&'static str, Vec<CommandOutput>)>>,
+        commands: Mutex<Vec<String>>,
+    }
+
+    impl RecordingRunner {
+        fn new(replies: Vec<(&'static str, CommandOutput)>) -> Self {
+            Self::sequenced(replies.into_iter().map(|(p, out)| (p, vec![out])).collect())
+        }
+
+        fn sequenced(replies: Vec<(&'static str, Vec<CommandOutput>)>) -> Self {
+            Self {
+                replies: Mutex::new(replies),
+                commands: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn commands(&self) -> Vec<String> {
+            self.commands.lock().unwrap().clone()
+        }
+
+        /// How many of the recorded commands contain `pattern`
+        fn count(&self, pattern: &str) -> usize {
+            self.commands.lock().unwrap().iter().filter(|c| c.contains(pattern)).count()
+        }
+    }
+
+    impl CommandRunner for RecordingRunner {
+        fn run(&self, program: &str, args: &[String]) -> Result<CommandOutput, String> {
+            let line = format!("{} {}", program, args.join(" "));
+            self.commands.lock().unwrap().push(line.clone());
+            let mut replies = self.replies.lock().unwrap();
+            let (_, queue) = replies
+                .iter_mut()
+                .find(|(pattern, _)| line.contains(pattern))
+                .ok_or_else(|| format!("unexpected command: {}", line))?;
+            Ok(if queue.len() > 1 { queue.remove(0) } else { queue[0].clone() })
+        }
+    }
+
+    fn ok(stdout: &str) -> CommandOutput {
+        CommandOutput {
+            success: true,
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+        }
+    }
+
+    fn failed(stderr: &str) -> CommandOutput {
+        CommandOutput {
+            success: false,
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+        }
+    }
+
+    /// Records emitted events in order
+    #[derive(Default)]
+    struct EventRecorder(Mutex<Vec<(String, serde_json::Value)>>);
+
+    impl EventSink for EventRecorder {
+        fn emit_json(&self, event: &str, payload: serde_json::Value) {
+            self.0.lock().unwrap().push((event.to_string(), payload));
+        }
+    }
+
+    impl EventRecorder {
+        /// Event names, with the phase appended for wipe-progress
+        fn sequence(&self) -> Vec<String> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(event, payload)| match payload["phase"].as_str() {
+                    Some(phase) if event == "wipe-progress" => format!("{}:{}", event, phase),
+                    _ => event.clone(),
+                })
+                .collect()
+        }
+    }
+
+    /// A wipe script that prints canned output, then runs `on_finish` for its exit status
+    struct ScriptedProcess<'a> {
+        output: Option<&'static str>,
+        on_finish: Box<dyn FnMut() -> bool + 'a>,
+    }
+
+    impl WipeProcess for ScriptedProcess<'_> {
+        fn take_stdout(&mut self) -> Option<Box<dyn std::io::Read + Send>> {
+            self.output
+                .take()
+                .map(|out| Box::new(out.as_bytes()) as Box<dyn std::io::Read + Send>)
+        }
+
+        fn wait_success(&mut self) -> Result<bool, String> {
+            Ok((self.on_finish)())
+        }
+    }
+
+    /// Spawner handing out the given scripts in order, one per (re)try
+    fn spawn_each<'a>(
+        scripts: Vec<ScriptedProcess<'a>>,
+    ) -> impl FnMut() -> Result<Box<dyn WipeProcess + 'a>, String> {
+        let mut scripts = scripts.into_iter();
+        move || {
+            let script = scripts.next().ok_or("no more scripts")?;
+            Ok(Box::new(script) as Box<dyn WipeProcess + 'a>)
+        }
+    }
+
+    /// Managed state for execute_wipe, in a scratch directory
+    struct WipeFixture {
+        dir: PathBuf,
+        state: WipeState,
+        audit_log: AuditLog,
+        sessions: SessionStore,
+        runner: Arc<dyn CommandRunner + Send + Sync>, // Answers the wipe's adb; none by default
+    }
+
+    impl WipeFixture {
+        fn new(name: &str) -> Self {
+            let dir = test_dir(name);
+            Self {
+                state: WipeState {
+                    device_id: Mutex::new(Some("RF123456".to_string())),
+                    ..WipeState::default()
+                },
+                audit_log: AuditLog::new(dir.join("logs"), DEFAULT_AUDIT_LOG_MAX_BYTES),
+                sessions: SessionStore::new(dir.join("sessions")),
+                runner: Arc::new(FakeRunner(vec![])),
+                dir,
+            }
+        }
+
+        fn ctx(&self, on_verify_failure: VerifyFailurePolicy) -> WipeContext<'_> {
+            WipeContext {
+                runner: self.runner.clone(),
+                state: &self.state,
+                audit_log: &self.audit_log,
+                sessions: &self.sessions,
+                settings: AppSettings {
+                    battery_poll_secs: 0,
+                    on_verify_failure,
+                    ..AppSettings::default()
+                },
+                batch_id: None,
+            }
+        }
+    }
+
+    impl Drop for WipeFixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// Output of a quick two-pass run that finished every pass
+    const QUICK_RUN_OUTPUT: &str = "=== PASS 1 of 2 ===\nWriting 64MB of random data...\n\
+        Deleting pass 1 data...\nPass 1 complete\n=== PASS 2 of 2 ===\n\
+        Deleting pass 2 data...\nPass 2 complete\nRemoving temporary files...\n\
+        Passes completed: 2\n";
+
+    /// A quick two-pass run on a sample device, ready for execute_wipe
+    fn sample_run() -> WipeRun {
+        let config: WipeConfig = serde_json::from_str(
+            r#"{"mode": "quick", "passes": 2, "size_mb": 64, "double_reset": false}"#,
+        )
+        .unwrap();
+        WipeRun {
+            device_id: "RF123456".to_string(),
+            device: sample_session().device,
+            serial: "RF123456".to_string(),
+            config: config.validate_and_clamp(ChunkSizeBounds::default()).unwrap(),
+            raw_streaming: true,
+            storage: None,
+            session_id: "1765548202000-RF123456".to_string(),
+            started_at: 1765548202,
+            warnings: Vec::new(),
+            script_version: Some("2.2.0".to_string()),
+        }
+    }
+
+    const DF_OUTPUT: &str = "Filesystem     1K-blocks     Used Available Use% Mounted on\n\
+                             /dev/fuse      115249236 45678912  69570324  40% /storage/emulated";
+
+    #[test]
+    fn test_detect_sdcard() {
+        let volumes = "private mounted null\n\
+                       emulated;0 mounted null\n\
+                       public:8,1 mounted 5C7A-11F3\n\
+                       public:179,65 mounted 1234-ABCD\n";
+        let cards = parse_sm_volumes(volumes);
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].path.as_deref(), Some("/storage/1234-ABCD"));
+        assert_eq!(cards[0].volume_id.as_deref(), Some("public:179,65"));
+        // Internal storage only
+        assert!(parse_sm_volumes("private mounted null\nemulated;0 mounted null\n").is_empty());
+        // Inserted but not mounted (e.g. unsupported filesystem): present, no path
+        let unmounted = parse_sm_volumes("public:179,1 unmountable null\n");
+        assert!(unmounted[0].present && !unmounted[0].mounted);
+        assert_eq!(unmounted[0].path, None);
+
+        let with_card = format!("{}public:179,1 unmounted null\n", volumes);
+        let runner = FakeRunner(vec![("sm list-volumes", ok(&with_card))]);
+        let card = detect_sdcard_with(&runner, "RF123456").unwrap();
+        assert_eq!(card.path.as_deref(), Some("/storage/1234-ABCD"));
+        let runner = FakeRunner(vec![("sm list-volumes", ok("emulated;0 mounted null\n"))]);
+        assert!(!detect_sdcard_with(&runner, "RF123456").unwrap().present);
+
+        // Without sm, a UUID-named directory under /storage is the card
+        let runner = FakeRunner(vec![
+            ("sm list-volumes", failed("/system/bin/sh: sm: not found")),
+            ("ls /storage", ok("1234-ABCD\nemulated\nself\n")),
+        ]);
+        let card = detect_sdcard_with(&runner, "RF123456").unwrap();
+        assert_eq!((card.path.as_deref(), card.volume_id), (Some("/storage/1234-ABCD"), None));
+        assert_eq!(parse_storage_listing("emulated\nself\n"), None);
+    }
+
+    #[test]
+    fn test_check_wipe_supported() {
+        let runner = FakeRunner(vec![
+            ("echo ok", ok("ok\n")),
+            ("sh -c", ok("writable\n")),
+            ("which dd", ok("/system/bin/dd\n")),
+            ("df /sdcard", ok(DF_OUTPUT)),
+        ]);
+        let support = check_wipe_supported_with(&runner, "RF123456");
+        assert!(support.supported, "{:?}", support.reasons);
+
+        // Locked device: no shell, nothing else probed
+        let locked = FakeRunner(vec![("echo ok", failed("error: device unauthorized."))]);
+        let support = check_wipe_supported_with(&locked, "RF123456");
+        assert!(!support.supported);
+        assert_eq!(support.reasons.len(), 1);
+        assert!(support.reasons[0].starts_with("No shell access"));
+
+        // Read-only storage without dd
+        let read_only = FakeRunner(vec![
+            ("echo ok", ok("ok\n")),
+            ("sh -c", failed("mkdir: '/sdcard/wipe_temp': Read-only file system")),
+            ("which dd", failed("")),
+            ("df /sdcard", ok(DF_OUTPUT)),
+        ]);
+        let support = check_wipe_supported_with(&read_only, "RF123456");
+        assert!(!support.supported);
+        assert_eq!(support.reasons.len(), 2);
+        assert_eq!(
+            support.reasons[0],
+            "/sdcard/wipe_temp is not writable: mkdir: '/sdcard/wipe_temp': Read-only file system"
+        );
+        assert!(support.reasons[1].contains("dd is not available"));
+    }
+
+    #[test]
+    fn test_wipe_complete_payload_keep_temp_files() {
+        let session = sample_session();
+
+        let payload = wipe_complete_payload(&session, false);
+        assert_eq!(payload["success"], true);
+        assert_eq!(payload["session_id"], "1765548202000-RF123456");
+        assert_eq!(payload["temp_files_kept"], false);
+        assert!(payload["temp_files_path"].is_null());
+        assert!(payload["temp_files_note"].is_null());
+
+        let payload = wipe_complete_payload(&session, true);
+        assert_eq!(payload["temp_files_kept"], true);
+        assert_eq!(payload["temp_files_path"], "/sdcard/wipe_temp");
+        assert!(payload["temp_files_note"].as_str().unwrap().contains("/sdcard/wipe_temp"));
+    }
+
+    #[test]
+    fn test_abort_cleanup_result() {
+        let clean = FakeRunner(vec![
+            ("kill -TERM -- -4321", ok("")),
+            ("pkill", ok("")),
+            ("rm -rf", ok("")),
+            ("pgrep", ok("stopped\n")),
+            ("test -e", ok("removed\n")),
+        ]);
+        let cleanup = stop_wipe_processes_with(&clean, "RF123456", Some(4321));
+        assert_eq!(cleanup, CleanupResult { dd_stopped: true, temp_removed: true });
+        let payload = abort_payload(&cleanup);
+        assert_eq!(payload["message"], "Wipe operation aborted and cleaned up.");
+        assert_eq!(payload["cleanup"]["temp_removed"], true);
+
+        // Device dropped off mid-cleanup: nothing can be confirmed
+        let offline = FakeRunner(vec![("pkill", ok(""))]);
+        let cleanup = stop_wipe_processes_with(&offline, "RF123456", None);
+        assert_eq!(cleanup, CleanupResult { dd_stopped: false, temp_removed: false });
+
+        let leftovers = FakeRunner(vec![
+            ("pkill", ok("")),
+            ("rm -rf", failed("rm: /sdcard/wipe_temp/chunk: Device or resource busy")),
+            ("pgrep", ok("stopped\n")),
+            ("test -e", ok("present\n")),
+        ]);
+        let payload = abort_payload(&stop_wipe_processes_with(&leftovers, "RF123456", None));
+        assert_eq!(payload["cleanup"]["dd_stopped"], true);
+        assert_eq!(payload["cleanup"]["temp_removed"], false);
+        assert!(payload["message"].as_str().unwrap().contains("temporary files may remain"));
+
+        // Only the script's own process group is killed, along with what it started
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            let mut script = Command::new("sh")
+                .args(["-c", "sleep 60 & wait"])
+                .process_group(0)
+                .spawn()
+                .unwrap();
+            let mut bystander = Command::new("sleep").arg("60").spawn().unwrap();
+            kill_process_group(&SystemRunner, script.id());
+            assert!(!script.wait().unwrap().success());
+            assert_eq!(bystander.try_wait().unwrap(), None);
+            bystander.kill().unwrap();
+            bystander.wait().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_storage_info_mount_path() {
+        assert_eq!(validate_device_path("/sdcard").unwrap(), "/sdcard");
+        assert!(validate_device_path("/storage/emulated/0").is_ok());
+        assert!(validate_device_path("sdcard").is_err());
+        assert!(validate_device_path("/sdcard/../data").is_err());
+        assert!(validate_device_path("/sdcard; reboot").is_err());
+        assert!(validate_device_path("/sdcard/$(id)").is_err());
+
+        // The FakeRunner only answers df for the requested path
+        let runner = FakeRunner(vec![("shell df /storage/emulated/0", ok(DF_OUTPUT))]);
+        let info = read_storage_info_with(&runner, "RF123456", "/storage/emulated/0").unwrap();
+        assert_eq!(info.total_mb, 115249236 / 1024);
+        assert!(read_storage_info_with(&runner, "RF123456", "/sdcard").is_err());
+    }
+
+    #[test]
+    fn test_parse_dumpsys_accounts() {
+        let output = "User UserInfo{0:Owner:c13} :\n\
+                      \x20 Accounts: 2\n\
+                      \x20   Account {name=jane.doe@gmail.com, type=com.google}\n\
+                      \x20   Account {name=jane, doe, type=com.osp.app.signin}\n\
+                      \n\
+                      \x20 AccountId, Action_type, timestamp, UID, TableName, Key\n";
+        let accounts = parse_dumpsys_accounts(output).unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].name, "jane.doe@gmail.com");
+        assert_eq!(accounts[0].account_type, "com.google");
+        // Commas in the name don't confuse the type
+        assert_eq!(accounts[1].name, "jane, doe");
+        assert_eq!(accounts[1].account_type, "com.osp.app.signin");
+
+        let check = build_account_check(Some(accounts));
+        assert_eq!(check.status, "signed_in");
+        assert!(check.frp_risk);
+
+        let none = parse_dumpsys_accounts("User UserInfo{0:Owner:c13} :\n  Accounts: 0\n");
+        let check = build_account_check(none);
+        assert_eq!(check.status, "none");
+        assert!(!check.frp_risk);
+
+        // Unreadable state is unknown, never "no accounts"
+        assert_eq!(parse_dumpsys_accounts("Permission Denial: can't dump AccountsManager"), None);
+        assert_eq!(build_account_check(None).status, "unknown");
+    }
+
+    #[test]
+    fn test_wipe_script_runs_from_writable_dir() {
+        let scripts_dir = Path::new("/opt/SecureWipe/scripts");
+        let args = vec!["-d".to_string(), "RF123456".to_string()];
+        let work_dir = script_work_dir().unwrap();
+        // Made once per run, and private to this user
+        assert_eq!(script_work_dir().unwrap(), work_dir);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&work_dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        let cmd = wipe_script_command(
+            &HostPriority::Normal,
+            scripts_dir,
+            "quick_wipe.sh",
+            &args,
+            &work_dir,
+        );
+
+        assert_eq!(cmd.get_current_dir(), Some(work_dir.as_path()));
+        let argv: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
+        assert_eq!(argv[0], scripts_dir.join("quick_wipe.sh").as_os_str());
+        assert_eq!(argv[1..], ["-d", "RF123456"]);
+
+        let probe = work_dir.join(format!("write-probe-{}", std::process::id()));
+        fs::write(&probe, "log").unwrap();
+        fs::remove_file(probe).unwrap();
+    }
+
+    #[test]
+    fn test_verify_no_accounts_after_reset() {
+        let verify = |output: CommandOutput| {
+            let runner = FakeRunner(vec![("dumpsys account", output)]);
+            let recorder = EventRecorder::default();
+            let check = verify_no_accounts_with(&runner, &recorder, "RF123456");
+            (check, recorder.sequence())
+        };
+
+        let (check, events) = verify(ok("User UserInfo{0:Owner:c13} :\n  Accounts: 0\n"));
+        assert_eq!(check.status, "none");
+        assert!(check.message.contains("clean for resale"));
+        assert_eq!(events, ["frp-clear"]);
+
+        let signed_in = "User UserInfo{0:Owner:c13} :\n\
+                         \x20 Accounts: 1\n\
+                         \x20   Account {name=jane.doe@gmail.com, type=com.google}\n";
+        let (check, events) = verify(ok(signed_in));
+        assert!(check.frp_risk);
+        assert!(check.message.contains("Factory Reset Protection"));
+        assert_eq!(events, ["frp-risk"]);
+
+        // Not readable is not clean
+        let (check, events) = verify(failed("error: device unauthorized."));
+        assert_eq!(check.status, "unknown");
+        assert_eq!(events, ["frp-risk"]);
+    }
+
+    #[test]
+    fn test_parse_script_help() {
+        let help = "Usage: quick_wipe.sh -d DEVICE_ID [OPTIONS]\n\
+                    \n\
+                    Required:\n\
+                    \x20 -d DEVICE     Target device serial (from 'adb devices')\n\
+                    \n\
+                    Options:\n\
+                    \x20 -p, --passes N    Number of overwrite passes (default: 3, max: 20)\n\
+                    \x20 -s, --size MB     Size in MB to write per pass (default: 1024)\n\
+                    \x20 --dry-run         Show what would be done without writing any data\n\
+                    \x20 --yes, -y         Skip confirmation prompt (for automation)\n\
+                    \x20 --raw             Plain unbuffered output (used by the desktop app)\n\
+                    \n\
+                    Examples:\n\
+                    \x20 ./quick_wipe.sh -d RF12345 --passes 5         # 5 passes x 1GB\n";
+        let caps = parse_script_help("quick_wipe.sh", help);
+        assert_eq!(
+            caps.flags,
+            ["-d", "-p", "--passes", "-s", "--size", "--dry-run", "--yes", "-y", "--raw"]
+        );
+        assert!(!caps.flags.iter().any(|f| f == "--target"));
+
+        // An older script without --raw in its help
+        let old = parse_script_help("full_wipe.sh", "Options:\n  -p, --passes N    Passes\n");
+        assert_eq!(old.flags, ["-p", "--passes"]);
+
+        // Cached after the first probe
+        let cache = ScriptCapabilityCache::new();
+        assert!(cache.get_or_probe("quick_wipe.sh", || Ok(caps.clone())).is_ok());
+        let cached = cache.get_or_probe("quick_wipe.sh", || Err("probed twice".to_string()));
+        assert_eq!(cached.unwrap(), caps);
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        let with_ansi = "\x1b[0;32mPass 2 complete\x1b[0m";
+        assert_eq!(strip_ansi(with_ansi), "Pass 2 complete");
+
+        let no_ansi = "Pass 2 complete";
+        assert_eq!(strip_ansi(no_ansi), "Pass 2 complete");
+    }
+
+    #[test]
+    fn test_parse_progress_line() {
+        // Test "Pass N complete" with ANSI codes
+        let line = "\x1b[0;32mPass 2 complete\x1b[0m";
+        let progress = parse_progress_line(line, 3).unwrap();
+        assert_eq!(progress.pass, 2);
+        assert_eq!(progress.total_passes, 3);
+        assert!((progress.percent - 66.67).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_parse_progress_line_final() {
+        // Test "Passes completed: N" format (final summary)
+        let line = "\x1b[0;32mPasses completed: 3\x1b[0m";
+        let progress = parse_progress_line(line, 3).unwrap();
+        assert_eq!(progress.pass, 3);
+        assert_eq!(progress.total_passes, 3);
+        assert_eq!(progress.percent, 100.0);
+        assert_eq!(progress.phase, "complete");
+    }
+
+    #[test]
+    fn test_parse_progress_line_wording_variations() {
+        // Casing and spacing
+        let progress = parse_progress_line("pass  2   COMPLETE", 3).unwrap();
+        assert_eq!(progress.pass, 2);
+        assert_eq!(progress.phase, "complete");
+
+        let progress = parse_progress_line("Progress: PASS 1 - 100MB / 400MB ( 25 % )", 2).unwrap();
+        assert!((progress.percent - 12.5).abs() < 0.1);
+        assert_eq!(progress.phase, "writing");
+
+        // Decimal comma
+        let progress = parse_progress_line("Pass 1 - 12,5%", 1).unwrap();
+        assert!((progress.percent - 12.5).abs() < 0.1);
+
+        // "done" and the full wipe's final summary
+        let progress = parse_progress_line("PASS_COMPLETE: Pass 3 done - wrote 100MB", 3).unwrap();
+        assert_eq!(progress.phase, "complete");
+        assert!((progress.percent - 100.0).abs() < 0.1);
+        let progress = parse_progress_line("WIPE_COMPLETE: All 4 passes finished", 4).unwrap();
+        assert_eq!((progress.pass, progress.phase.as_str()), (4, "complete"));
+        let progress = parse_progress_line("passes completed : 2", 3).unwrap();
+        assert_eq!(progress.pass, 2);
+        assert_eq!(progress.percent, 100.0);
+
+        // Pass lines without progress, and "passes" without a number
+        assert!(parse_progress_line("Syncing pass 1...", 3).is_none());
+        assert!(parse_progress_line("Number of passes: 3", 3).is_none());
+    }
+
+    #[test]
+    fn test_parse_progress_line_cleanup() {
+        let progress = parse_progress_line("Cleaning up pass 2...", 3).unwrap();
+        assert_eq!((progress.pass, progress.phase.as_str()), (2, "cleanup"));
+        assert!((progress.percent - 66.67).abs() < 1.0);
+        let progress = parse_progress_line("Deleting pass 1 data...", 2).unwrap();
+        assert_eq!(progress.phase, "cleanup");
+
+        // The final sweep keeps the bar full instead of looking frozen
+        let progress = parse_progress_line("Removing temporary files...", 3).unwrap();
+        assert_eq!((progress.pass, progress.phase.as_str()), (3, "cleanup"));
+        assert_eq!(progress.percent, 100.0);
+
+        // The interrupt trap's message isn't wipe progress
+        assert!(parse_progress_line("Interrupted! Cleaning up temporary files", 3).is_none());
+    }
+
+    #[test]
+    fn test_parse_progress_line_quick_chunks() {
+        // Second of three passes, a quarter of its chunk written: (1 + 0.25) / 3
+        let line = "Pass 2: chunk 4 of 16, 256MB written, 51200KB free";
+        let progress = parse_progress_line(line, 3).unwrap();
+        assert_eq!((progress.pass, progress.phase.as_str()), (2, "writing"));
+        assert!((progress.percent - 41.67).abs() < 0.1);
+        assert_eq!(progress.bytes_written, 256 * 1024 * 1024);
+        assert_eq!(progress.message, line);
+
+        // Steps climb steadily within a pass instead of jumping at its end
+        let percents: Vec<f32> = (1..=4)
+            .map(|c| format!("Pass 1: chunk {} of 4, {}MB written, 900KB free", c, c * 64))
+            .map(|l| parse_progress_line(&l, 2).unwrap().percent)
+            .collect();
+        assert_eq!(percents, [12.5, 25.0, 37.5, 50.0]);
+
+        // Colored terminal output and an odd last step still parse
+        let colored = "\x1b[0;33mPass 1: chunk 3 of 3, 150MB written, KB free\x1b[0m";
+        let progress = parse_progress_line(colored, 1).unwrap();
+        assert_eq!((progress.percent, progress.bytes_written), (100.0, 150 * 1024 * 1024));
+        assert!(parse_progress_line("Pass 1: chunk 1 of 0, 0MB written", 1).is_none());
+        // The announcement before the steps is not progress
+        assert!(parse_progress_line("Writing 1024MB of random data...", 3).is_none());
+    }
+
+    #[test]
+    fn test_bounded_lines_truncates_giant_line() {
+        use std::io::Read;
+        // 64MB without a newline, generated as it's read
+        let giant = std::io::repeat(b'x').take(64 * 1024 * 1024);
+        let input = giant.chain(&b"\r\nPass 1 complete\n\xff tail"[..]);
+        let mut lines = BoundedLines::new(BufReader::new(input), MAX_LINE_BYTES);
+
+        let first = lines.next().unwrap();
+        assert!(first.truncated);
+        assert_eq!(first.text.len(), MAX_LINE_BYTES);
+        // The stream carries on normally after the long line
+        let second = lines.next().unwrap();
+        assert_eq!((second.text.as_str(), second.truncated), ("Pass 1 complete", false));
+        assert!(parse_progress_line(&second.text, 1).is_some());
+        // Invalid UTF-8 and a missing final newline don't end the stream early
+        assert_eq!(lines.next().unwrap().text, "\u{fffd} tail");
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn test_parse_progress_line_no_match() {
+        let line = "Starting wipe operation...";
+        assert!(parse_progress_line(line, 3).is_none());
+    }
+
+    #[test]
+    fn test_parse_progress_line_full_wipe_format() {
+        // Test "PROGRESS: Pass N - XMB / YMB (Z%)" format from full_wipe.sh
+        let line = "\x1b[1;33mPROGRESS: Pass 1 - 256MB / 50000MB (0%)\x1b[0m";
+        let progress = parse_progress_line(line, 3).unwrap();
+        assert_eq!(progress.pass, 1);
+        assert_eq!(progress.total_passes, 3);
+        // Pass 1 at 0% with 3 passes = ((0 + 0) / 3) * 100 = 0%
+        assert!(progress.percent < 1.0);
+        assert_eq!(progress.phase, "writing");
+
+        // Test mid-pass progress
+        let line2 = "PROGRESS: Pass 2 - 25000MB / 50000MB (50%)";
+        let progress2 = parse_progress_line(line2, 3).unwrap();
+        assert_eq!(progress2.pass, 2);
+        // Pass 2 at 50% with 3 passes = ((1 + 0.5) / 3) * 100 = 50%
+        assert!((progress2.percent - 50.0).abs() < 1.0);
+        assert_eq!(progress2.bytes_written, 25000 * 1024 * 1024);
+
+        let done = parse_progress_line("PASS_COMPLETE: Pass 2 done - wrote 49800MB", 3).unwrap();
+        assert_eq!(done.bytes_written, 49800 * 1024 * 1024);
+        assert_eq!(parse_progress_line("Pass 1 complete", 3).unwrap().bytes_written, 0);
+    }
+
+    #[test]
+    fn test_free_space_coverage() {
+        const MB: u64 = 1024 * 1024;
+        // full_wipe.sh's 95% fill of 20000MB free
+        let full = free_space_coverage(20000 * MB, 19000 * MB, true);
+        assert!(full.full_coverage);
+        assert_eq!(full.covered_percent, 95.0);
+        assert_eq!(full.partial_reason, None);
+
+        // Quick mode's fixed 1024MB chunk
+        let quick = free_space_coverage(20000 * MB, 1024 * MB, true);
+        assert!(!quick.full_coverage);
+        assert!(quick.partial_reason.unwrap().contains("Only 1024MB of the 20000MB"));
+
+        // Aborted after writing nearly everything is still partial
+        let aborted = free_space_coverage(20000 * MB, 19500 * MB, false);
+        assert!(!aborted.full_coverage);
+        assert!(aborted.partial_reason.unwrap().contains("did not complete"));
+
+        // Writing more than was free (space freed mid-wipe) caps at 100%
+        assert_eq!(free_space_coverage(1000 * MB, 1500 * MB, true).covered_percent, 100.0);
+
+        let cert = build_certificate(&sample_session());
+        let payload = serde_json::to_value(&cert).unwrap();
+        assert_eq!(payload["free_space_coverage"]["full_coverage"], true);
+        assert_eq!(payload["free_space_coverage"]["free_before_bytes"], 1024 * MB);
+    }
+
+    #[test]
+    fn test_estimate_coverage() {
+        const MB: u64 = 1024 * 1024;
+        // 128GB data partition, 100GB of free space overwritten
+        let coverage = estimate_coverage(131072 * MB, 102400 * MB);
+        assert_eq!(coverage.unreachable_bytes, 28672 * MB);
+        assert!((coverage.unreachable_percent - 21.875).abs() < 0.001);
+        assert_eq!(
+            describe_coverage(&coverage),
+            "102400MB of 131072MB overwritten; 28672MB (21.9%) unreachable by overwrite"
+        );
+
+        // Writes past the reported capacity never produce a negative gap
+        let over = estimate_coverage(1000, 1500);
+        assert_eq!(over.overwritten_bytes, 1000);
+        assert_eq!(over.unreachable_bytes, 0);
+
+        assert_eq!(estimate_coverage(0, 0).unreachable_percent, 0.0);
+    }
+
+    #[test]
+    fn test_wipe_config_validation() {
+        let config = WipeConfig {
+            mode: "quick".to_string(),
+            passes: 25, // Over limit
+            size_mb: Some(50), // Under limit
+            double_reset: false,
+            expect_serial: None,
+            expect_model: None,
+            max_duration_secs: 0,
+            keep_temp_files: false,
+            pattern: None,
+            emulator_test: false,
+            skip_factory_reset: false,
+            clear_app_data: false,
+        };
+        let clamped = config.validate_and_clamp(ChunkSizeBounds::default()).unwrap();
+
+        // Passes should clamp to 20
+        assert_eq!(clamped.passes, 20);
+
+        // Size should clamp to minimum 64
+        assert_eq!(clamped.size_mb, Some(64));
+        assert_eq!(clamped.pattern.as_deref(), Some("random"));
+    }
+
+    #[test]
+    fn test_wipe_script_args_raw_only_when_supported() {
+        let config: WipeConfig = serde_json::from_str(
+            r#"{"mode": "quick", "passes": 2, "size_mb": 512, "double_reset": false}"#,
+        )
+        .unwrap();
+        let caps = |help: &str| parse_script_help("quick_wipe.sh", help);
+
+        let current = caps("  -d DEVICE  Device id\n  -p N  Passes\n  --raw  Unbuffered output\n");
+        let (args, raw) = wipe_script_args("ABC123", &config, Some(&current));
+        assert!(raw);
+        assert!(args.contains(&"--raw".to_string()));
+        assert_eq!(&args[args.len() - 2..], ["-s", "512"]);
+
+        let old = caps("  -d DEVICE  Device id\n  -p N  Passes\n");
+        let (args, raw) = wipe_script_args("ABC123", &config, Some(&old));
+        assert!(!raw);
+        assert!(!args.contains(&"--raw".to_string()));
+        // Capabilities that couldn't be probed are treated like an old script
+        assert!(!wipe_script_args("ABC123", &config, None).1);
+    }
+
+    #[test]
+    fn test_storage_snapshots_in_summary() {
+        let mut fixture = WipeFixture::new("storage-snapshots");
+        // Read again once the wipe is done
+        let after = DF_OUTPUT.replace("69570324", "69000000");
+        fixture.runner = Arc::new(FakeRunner(vec![("sync", ok("")), ("df", ok(&after))]));
+        let snapshot = |label: &str, available_mb| StorageSnapshot {
+            label: label.to_string(),
+            taken_at: 1765548202,
+            storage: StorageInfo {
+                available_mb,
+                ..parse_df_output(DF_OUTPUT).unwrap()
+            },
+        };
+        fixture.state.add_snapshot("RF123456", snapshot("before", 40960));
+        // Another device's snapshot stays with that device
+        fixture.state.add_snapshot("OTHER000", snapshot("before", 100));
+        let mut spawn = spawn_each(vec![ScriptedProcess {
+            output: Some(QUICK_RUN_OUTPUT),
+            on_finish: Box::new(|| {
+                fixture.state.add_snapshot("RF123456", snapshot("during", 12));
+                true
+            }),
+        }]);
+
+        let recorder = Arc::new(EventRecorder::default());
+        let ctx = fixture.ctx(VerifyFailurePolicy::Warn);
+        let run = WipeRun {
+            storage: Some(parse_df_output(DF_OUTPUT).unwrap()),
+            ..sample_run()
+        };
+        assert!(execute_wipe(&ctx, recorder.clone(), run, &mut spawn).is_ok());
+        drop(spawn);
+
+        let session = &fixture.sessions.list().unwrap()[0];
+        let labels: Vec<&str> =
+            session.storage_snapshots.iter().map(|s| s.label.as_str()).collect();
+        assert_eq!(labels, ["before", "during", "after"]);
+        assert_eq!(session.storage_snapshots[2].storage.available_mb, 69000000 / 1024);
+        let events = recorder.0.lock().unwrap();
+        let (_, complete) = events.iter().find(|(e, _)| e == "wipe-complete").unwrap();
+        assert_eq!(
+            complete["storage_summary"],
+            "before 40960 MB free, during 12 MB free, after 67382 MB free"
+        );
+        assert_eq!(complete["storage_snapshots"].as_array().unwrap().len(), 3);
+        assert_eq!(build_certificate(session).storage_snapshots.len(), 3);
+        // Handed to the session, not kept for the next wipe
+        assert!(fixture.state.take_snapshots("RF123456").is_empty());
+        assert_eq!(fixture.state.take_snapshots("OTHER000").len(), 1);
+
+        assert_eq!(validate_snapshot_label(" After ").unwrap(), "after");
+        assert!(validate_snapshot_label("").is_err());
+        assert!(validate_snapshot_label("before; rm").is_err());
+    }
+
+    #[test]
+    fn test_wipe_event_sequence() {
+        let fixture = WipeFixture::new("events-run");
+        let recorder = Arc::new(EventRecorder::default());
+        let mut spawn = spawn_each(vec![ScriptedProcess {
+            output: Some(QUICK_RUN_OUTPUT),
+            on_finish: Box::new(|| true),
+        }]);
+
+        let ctx = fixture.ctx(VerifyFailurePolicy::Warn);
+        assert!(execute_wipe(&ctx, recorder.clone(), sample_run(), &mut spawn).is_ok());
+        assert_eq!(
+            recorder.sequence(),
+            [
+                "wipe-progress:starting",
+                "wipe-progress:cleanup",
+                "wipe-progress:complete",
+                "wipe-progress:cleanup",
+                "wipe-progress:complete",
+                "wipe-progress:cleanup",
+                "wipe-progress:complete",
+                "wipe-progress:verifying",
+                "wipe-complete",
+            ]
+        );
+        let events = recorder.0.lock().unwrap();
+        assert_eq!(events[8].1["success"], true);
+        assert_eq!(events[8].1["verification"]["passed"], true);
+        assert!(fixture.state.device_id.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_abort_before_start_and_after_finish() {
+        let fixture = WipeFixture::new("abort-races");
+        let recorder = Arc::new(EventRecorder::default());
+        let runner = FakeRunner(vec![]);
+        let abort = || {
+            let reset = AbortBehavior::CleanupAndReset;
+            abort_wipe_with(&fixture.state, &fixture.audit_log, &*recorder, &runner, reset)
+        };
+        // The fixture starts as a running wipe; free the slot as if none had started
+        *fixture.state.device_id.lock().unwrap() = None;
+        assert_eq!(abort().unwrap_err(), "No wipe operation in progress.");
+
+        // run_wipe has claimed the device but not spawned the script yet
+        fixture.state.claim("RF123456");
+        assert!(abort().unwrap().contains("before it started"));
+        // Nothing to stop, so no cleanup was attempted
+        assert_eq!(recorder.sequence(), ["wipe-aborted"]);
+        let mut spawned = false;
+        let mut spawn = || -> Result<Box<dyn WipeProcess>, String> {
+            spawned = true;
+            Err("spawned".to_string())
+        };
+        let ctx = fixture.ctx(VerifyFailurePolicy::Warn);
+        let result = execute_wipe(&ctx, recorder.clone(), sample_run(), &mut spawn);
+        assert_eq!(result.unwrap_err(), WIPE_CANCELLED_BEFORE_START);
+        assert!(!spawned);
+        assert!(fixture.state.device_id.lock().unwrap().is_none());
+
+        // The wipe ran to completion and cleared the device id before the abort arrived
+        fixture.state.claim("RF123456");
+        let mut spawn = spawn_each(vec![ScriptedProcess {
+            output: Some(QUICK_RUN_OUTPUT),
+            on_finish: Box::new(|| true),
+        }]);
+        assert!(execute_wipe(&ctx, recorder.clone(), sample_run(), &mut spawn).is_ok());
+        assert!(abort().unwrap_err().contains("RF123456 has already finished"));
+
+        // The next wipe starts with a clean slate
+        fixture.state.claim("RF123456");
+        assert!(fixture.state.cancel_launch());
+        assert!(fixture.state.last_finished.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_replay_session_events() {
+        let fixture = WipeFixture::new("replay-session");
+        let live = Arc::new(EventRecorder::default());
+        let mut spawn = spawn_each(vec![ScriptedProcess {
+            output: Some(QUICK_RUN_OUTPUT),
+            on_finish: Box::new(|| true),
+        }]);
+        let ctx = fixture.ctx(VerifyFailurePolicy::Warn);
+        assert!(execute_wipe(&ctx, live.clone(), sample_run(), &mut spawn).is_ok());
+
+        let session_id = &fixture.sessions.list().unwrap()[0].session_id;
+        let stored = fixture.sessions.load_events(session_id).unwrap();
+        let names: Vec<&str> = stored.iter().map(|e| e.event.as_str()).collect();
+        assert_eq!(names.first(), Some(&"wipe-progress"));
+        assert_eq!(names.last(), Some(&"wipe-complete"));
+        assert!(stored.windows(2).all(|pair| pair[0].at_ms <= pair[1].at_ms));
+
+        let replayed = EventRecorder::default();
+        assert_eq!(replay_events(&replayed, &stored), stored.len());
+        // Only session-replay events, wrapping the sequence the wipe emitted live
+        let replayed = replayed.0.lock().unwrap();
+        assert!(replayed.iter().all(|(name, _)| name == "session-replay"));
+        let inner = EventRecorder::default();
+        for (_, wrapped) in replayed.iter() {
+            let name = wrapped["event"].as_str().unwrap();
+            inner.emit_json(name, wrapped["payload"].clone());
+        }
+        assert_eq!(inner.sequence(), live.sequence());
+        assert_eq!(replayed[0].1["at_ms"], stored[0].at_ms);
+        drop(replayed);
+
+        // Sessions from before events were kept have none; unknown sessions are an error
+        let mut older = sample_session();
+        older.session_id = "1765000000000-RF123456".to_string();
+        fixture.sessions.save(&older).unwrap();
+        assert!(fixture.sessions.load_events(&older.session_id).unwrap().is_empty());
+        assert!(fixture.sessions.load_events("1765000000001-NONE").is_err());
+        assert_eq!(fixture.sessions.list().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_encryption_advisory() {
+        let device = |state: &str, crypto_type: &str| {
+            FakeRunner(vec![("ro.crypto.state", ok(state)), ("ro.crypto.type", ok(crypto_type))])
+        };
+        let advisories = |runner: &FakeRunner, passes: u32| {
+            let recorder = EventRecorder::default();
+            advise_on_passes(runner, &recorder, "RF123456", passes);
+            recorder.sequence()
+        };
+
+        let fbe = device("encrypted\n", "file\n");
+        assert_eq!(
+            read_encryption_status(&fbe, "RF123456"),
+            EncryptionStatus {
+                state: "encrypted".to_string(),
+                encryption_type: Some("file".to_string()),
+            }
+        );
+        assert_eq!(advisories(&fbe, 7), ["wipe-advisory"]);
+        assert!(advisories(&fbe, 1).is_empty());
+
+        // Full-disk encryption, no encryption, or props that can't be read: no advice
+        assert!(advisories(&device("encrypted\n", "block\n"), 7).is_empty());
+        assert!(advisories(&device("unencrypted\n", ""), 7).is_empty());
+        assert!(advisories(&FakeRunner(vec![]), 7).is_empty());
+        assert_eq!(read_encryption_status(&FakeRunner(vec![]), "RF123456").state, "unknown");
+    }
+
+    #[test]
+    fn test_find_script_permissions() {
+        let dir = test_dir("script-access");
+        let (empty, broken, good) = (dir.join("empty"), dir.join("broken"), dir.join("good"));
+        for d in [&empty, &broken, &good] {
+            fs::create_dir_all(d).unwrap();
+        }
+        fs::write(good.join("quick_wipe.sh"), "#!/bin/bash\n").unwrap();
+        // Found, but can't be read as a script
+        fs::create_dir_all(broken.join("quick_wipe.sh")).unwrap();
+
+        let err = find_script_in(vec![empty.clone()], "quick_wipe.sh").unwrap_err();
+        assert!(err.contains("not found"));
+        let err = find_script_in(vec![broken.clone(), good.clone()], "quick_wipe.sh").unwrap_err();
+        assert!(err.contains("found but not readable"), "{}", err);
+        assert!(err.contains("broken"));
+        assert_eq!(find_script_in(vec![empty, good.clone()], "quick_wipe.sh"), Ok(good.clone()));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let script = good.join("quick_wipe.sh");
+            fs::set_permissions(&script, fs::Permissions::from_mode(0o000)).unwrap();
+            // Root reads regardless of mode bits, so only check where the mode is enforced
+            if fs::read(&script).is_err() {
+                let err = find_script_in(vec![good], "quick_wipe.sh").unwrap_err();
+                assert!(err.contains("found but not readable"), "{}", err);
+            }
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_host_priority_selection() {
+        use HostPriority::*;
+        let all = |_: &str| true;
+        let none = |_: &str| false;
+        let strings = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+
+        for os in ["linux", "macos", "windows"] {
+            assert_eq!(host_priority(os, false, all), Normal);
+        }
+        assert_eq!(
+            host_priority("linux", true, all),
+            Prefix(strings(&["nice", "-n", "10", "ionice", "-c", "2", "-n", "7"]))
+        );
+        assert_eq!(
+            host_priority("linux", true, |p| p == "ionice"),
+            Prefix(strings(&["ionice", "-c", "2", "-n", "7"]))
+        );
+        // No ionice outside Linux, even if something by that name is on PATH
+        assert_eq!(host_priority("macos", true, all), Prefix(strings(&["nice", "-n", "10"])));
+        assert_eq!(host_priority("linux", true, none), Normal);
+        assert_eq!(host_priority("windows", true, none), CreationFlags(0x4000));
+
+        let cmd = prioritized_command("bash", &host_priority("macos", true, all));
+        assert_eq!(cmd.get_program(), "nice");
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(args, ["-n", "10", "bash"]);
+        assert_eq!(prioritized_command("bash", &CreationFlags(0x4000)).get_program(), "bash");
+    }
+
+    #[test]
+    fn test_batch_selector_resolution() {
+        let chunk = ChunkSizeBounds::default();
+        let device = |id: &str, serial: &str, model: &str| DeviceCandidate {
+            id: id.to_string(),
+            serial: serial.to_string(),
+            model: model.to_string(),
+        };
+        let connected = vec![
+            device("RF123456", "RF123456", "SM-S928U"),
+            device("RF998877", "RF998877", "SM-S921B"),
+            device("192.168.1.20:5555", "35071FDH2003WB", "Pixel 8"),
+        ];
+
+        assert_eq!(resolve_batch_selector("all", &connected).unwrap().len(), 3);
+        assert_eq!(
+            resolve_batch_selector("sm-s92*", &connected).unwrap(),
+            ["RF123456", "RF998877"]
+        );
+        // Serial matches even when the adb id is a network address
+        assert_eq!(
+            resolve_batch_selector("35071FDH*", &connected).unwrap(),
+            ["192.168.1.20:5555"]
+        );
+        assert_eq!(resolve_batch_selector("RF12345?", &connected).unwrap(), ["RF123456"]);
+        let none = resolve_batch_selector("XT*", &connected).unwrap_err();
+        assert!(none.contains("No connected device matches 'XT*'"), "{}", none);
+        assert!(resolve_batch_selector("all", &[]).is_err());
+
+        assert!(glob_match("*8*", "Pixel 8"));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(!glob_match("a*b*c", "aXXbYY"));
+
+        let json = r#"{"select": "SM-S92*", "config": {"mode": "quick", "passes": 1,
+                       "double_reset": false}}"#;
+        let batch = parse_batch_config(json, chunk).unwrap();
+        assert!(batch.devices.is_empty());
+        let resolved = resolve_batch_devices(&batch, chunk, || Ok(connected.clone())).unwrap();
+        assert_eq!(resolved.devices, ["RF123456", "RF998877"]);
+        assert_eq!(resolved.select, None);
+        assert!(resolve_batch_devices(&batch, chunk, || Ok(Vec::new())).is_err());
+        assert!(parse_batch_config(&json.replace("SM-S92*", "$(reboot)"), chunk).is_err());
+    }
+
+    #[test]
+    fn test_batch_config_file() {
+        let chunk = ChunkSizeBounds::default();
+        let dir = test_dir("batch-config");
+        let path = dir.join("batch.json");
+        fs::write(
+            &path,
+            r#"{
+                "devices": ["RF123456", "emulator-5554", "192.168.1.20:5555"],
+                "config": {"mode": "quick", "passes": 50, "size_mb": 8, "double_reset": false},
+                "stop_on_failure": true
+            }"#,
+        )
+        .unwrap();
+
+        let batch = load_batch_config(&path, chunk).unwrap();
+        assert_eq!(batch.devices, ["RF123456", "emulator-5554", "192.168.1.20:5555"]);
+        assert!(batch.stop_on_failure);
+        // The shared config comes out clamped, the same as a single wipe's
+        assert_eq!((batch.config.passes, batch.config.size_mb), (20, Some(64)));
+
+        let config = r#""config": {"mode": "quick", "passes": 1, "double_reset": false}"#;
+        for (devices, error) in [
+            ("[]", "no devices"),
+            (r#"["RF123456", "RF123456"]"#, "more than once"),
+            (r#"["RF123456; reboot"]"#, "Invalid device ID"),
+        ] {
+            let json = format!(r#"{{"devices": {}, {}}}"#, devices, config);
+            let err = parse_batch_config(&json, chunk).unwrap_err();
+            assert!(err.contains(error), "{}", err);
+        }
+        assert!(parse_batch_config(r#"{"devices": ["RF123456"]}"#, chunk).is_err());
+        let both = format!(r#"{{"devices": ["RF123456"], "select": "all", {}}}"#, config);
+        assert!(parse_batch_config(&both, chunk).unwrap_err().contains("use one"));
+        assert!(load_batch_config(&dir.join("missing.json"), chunk).is_err());
+
+        // stop_on_failure skips whatever is left after the first failure
+        let recorder = EventRecorder::default();
+        let mut wipe_one = |device_id: &str, _: &WipeConfig| match device_id {
+            "RF123456" => Ok("Wipe completed successfully!".to_string()),
+            _ => Err("Wipe failed.".to_string()),
+        };
+        let report = run_batch(&batch, &BatchState::default(), &recorder, &mut wipe_one);
+        let skipped: Vec<bool> = report.results.iter().map(|r| r.skipped).collect();
+        assert_eq!(skipped, [false, false, true]);
+        assert_eq!((report.succeeded, report.failed), (1, 2));
+        assert_eq!(
+            recorder.sequence(),
+            ["batch-device", "batch-device", "batch-device", "batch-complete"]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_abort_device_in_batch() {
+        use BatchDeviceStatus::*;
+        let fixture = WipeFixture::new("batch-cancel");
+        let batch = parse_batch_config(
+            r#"{"devices": ["AAA111", "BBB222", "CCC333", "DDD444"],
+                "config": {"mode": "quick", "passes": 1, "double_reset": false},
+                "stop_on_failure": true}"#,
+            ChunkSizeBounds::default(),
+        )
+        .unwrap();
+        // Cancelled before its script starts, so nothing on the device is touched
+        let device = FakeRunner(vec![]);
+        let batch_state = BatchState::default();
+        let recorder = EventRecorder::default();
+        let cancel = |device_id: &str| {
+            let (state, log) = (&fixture.state, &fixture.audit_log);
+            abort_device_in_batch_with(&batch_state, state, log, &recorder, &device, device_id)
+        };
+
+        batch_state.begin(&batch.devices).unwrap();
+        assert!(batch_state.begin(&batch.devices).unwrap_err().contains("already running"));
+        // Cancelled while still queued: never wiped
+        assert!(cancel("CCC333").is_ok());
+        assert!(cancel("EEE555").unwrap_err().contains("not part of the batch"));
+
+        let mut wiped = Vec::new();
+        let mut wipe_one = |device_id: &str, _: &WipeConfig| {
+            wiped.push(device_id.to_string());
+            // As run_wipe does, before its script is started
+            fixture.state.claim(device_id);
+            if device_id == "BBB222" {
+                // Cancelled while being prepared: this wipe never starts, and only this one
+                assert_eq!(batch_state.status("AAA111"), Some(Succeeded));
+                assert!(cancel("BBB222").unwrap().contains("before it started"));
+                assert!(fixture.state.launch_cancelled());
+                assert_eq!(batch_state.status("DDD444"), Some(Pending));
+                return Err("Wipe was aborted.".to_string());
+            }
+            fixture.state.release(device_id, true);
+            if device_id == "DDD444" {
+                // Cancelled as it finished: too late, so it isn't counted as cancelled
+                assert!(cancel("DDD444").unwrap_err().contains("nothing was aborted"));
+                assert_eq!(batch_state.status("DDD444"), Some(Running));
+            }
+            Ok("Wipe completed successfully!".to_string())
+        };
+        let report = run_batch(&batch, &batch_state, &recorder, &mut wipe_one);
+
+        assert_eq!(wiped, ["AAA111", "BBB222", "DDD444"]);
+        let statuses: Vec<_> = batch.devices.iter().map(|id| batch_state.status(id)).collect();
+        assert_eq!(statuses, [Some(Succeeded), Some(Cancelled), Some(Cancelled), Some(Succeeded)]);
+        // The cancelled wipe's failure didn't trip stop_on_failure
+        assert_eq!((report.succeeded, report.failed, report.cancelled), (2, 0, 2));
+        assert!(cancel("DDD444").unwrap_err().contains("DDD444 has already finished (succeeded)"));
+
+        let events = recorder.sequence();
+        let cancelled: Vec<_> = events.iter().filter(|e| *e == "batch-device-cancelled").collect();
+        assert_eq!(cancelled.len(), 2);
+        assert!(events.contains(&"wipe-aborted".to_string()));
+        // A new batch can start once this one is done
+        assert!(batch_state.begin(&batch.devices).is_ok());
+    }
+
+    #[test]
+    fn test_abort_reclaimed_batch_device() {
+        // FFF666 reconnected before its wipe, which resolved it to its new transport id
+        let fixture = WipeFixture::new("batch-cancel-reclaimed");
+        let batch_state = BatchState::default();
+        let recorder = EventRecorder::default();
+        batch_state.begin(&["FFF666".to_string()]).unwrap();
+        assert!(batch_state.start("FFF666"));
+        fixture.state.claim("FFF666");
+        fixture.state.reclaim_as("192.168.1.20:5555");
+        let script = ScriptedProcess {
+            output: None,
+            on_finish: Box::new(|| true),
+        };
+        assert!(fixture.state.launch(&mut spawn_each(vec![script])).is_ok());
+
+        // The batch cancels it by the id it knows, and the running wipe is aborted
+        let (state, log) = (&fixture.state, &fixture.audit_log);
+        let device = FakeRunner(vec![]);
+        let cancelled =
+            abort_device_in_batch_with(&batch_state, state, log, &recorder, &device, "FFF666");
+        assert!(cancelled.is_ok());
+        assert_eq!(batch_state.status("FFF666"), Some(BatchDeviceStatus::Cancelled));
+        assert_eq!(*fixture.state.device_id.lock().unwrap(), None);
+        assert!(recorder.sequence().contains(&"wipe-aborted".to_string()));
+        assert!(!fixture.state.is_claimed_for("FFF666"));
+    }
+
+    #[test]
+    fn test_batch_pauses_between_devices() {
+        let batch = parse_batch_config(
+            r#"{"devices": ["AAA111", "BBB222", "CCC333"],
+                "config": {"mode": "quick", "passes": 1, "double_reset": false},
+                "pause_between_devices": true}"#,
+            ChunkSizeBounds::default(),
+        )
+        .unwrap();
+        let batch_state = BatchState::default();
+        let recorder = EventRecorder::default();
+        batch_state.begin(&batch.devices).unwrap();
+        assert!(batch_state.resume().unwrap_err().contains("isn't waiting"));
+
+        let wait_for = |device_id: &str| {
+            while batch_state.awaiting_next.lock().unwrap().as_deref() != Some(device_id) {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        };
+        let mut wiped = Vec::new();
+        let report = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                // The operator swaps in BBB222 and continues, then gives up on CCC333
+                wait_for("BBB222");
+                assert_eq!(batch_state.resume().unwrap(), "BBB222");
+                wait_for("CCC333");
+                assert!(batch_state.cancel("CCC333", || unreachable!()).is_ok());
+            });
+            let mut wipe_one = |device_id: &str, _: &WipeConfig| {
+                wiped.push(device_id.to_string());
+                Ok("Wipe completed successfully!".to_string())
+            };
+            run_batch(&batch, &batch_state, &recorder, &mut wipe_one)
+        });
+
+        assert_eq!(wiped, ["AAA111", "BBB222"]);
+        assert_eq!((report.succeeded, report.cancelled), (2, 1));
+        assert_eq!(
+            recorder.sequence(),
+            [
+                "batch-device",
+                "batch-awaiting-next",
+                "batch-device",
+                "batch-awaiting-next",
+                "batch-device",
+                "batch-complete"
+            ]
+        );
+        let events = recorder.0.lock().unwrap();
+        assert_eq!(events[1].1["device_id"], "BBB222");
+        assert_eq!(events[1].1["previous"], "AAA111");
+    }
+
+    #[test]
+    fn test_current_progress_tracks_latest_event() {
+        let fixture = WipeFixture::new("events-poll");
+        let recorder = Arc::new(EventRecorder::default());
+        let polled = std::cell::RefCell::new(None);
+        let mut spawn = spawn_each(vec![ScriptedProcess {
+            output: Some(QUICK_RUN_OUTPUT),
+            on_finish: Box::new(|| {
+                // Script done, wipe still active: the last streamed update is current
+                *polled.borrow_mut() = fixture.state.current_progress("RF123456");
+                true
+            }),
+        }]);
+        assert!(fixture.state.current_progress("OTHER000").is_none());
+
+        let ctx = fixture.ctx(VerifyFailurePolicy::Warn);
+        assert!(execute_wipe(&ctx, recorder.clone(), sample_run(), &mut spawn).is_ok());
+        drop(spawn);
+        let polled = polled.into_inner().expect("progress while wiping");
+        let events = recorder.0.lock().unwrap();
+        assert_eq!(serde_json::to_value(&polled).unwrap(), events[6].1);
+        assert_eq!((polled.phase.as_str(), polled.pass), ("complete", 2));
+        // Finished wipes have no current progress
+        assert!(fixture.state.current_progress("RF123456").is_none());
+    }
+
+    #[test]
+    fn test_aborted_wipe_event_sequence() {
+        let fixture = WipeFixture::new("events-abort");
+        let device = FakeRunner(vec![
+            ("pkill", ok("")),
+            ("[d]d if", ok("stopped\n")),
+            ("test -e", ok("removed\n")),
+            ("rm -rf", ok("")),
+        ]);
+        let recorder = Arc::new(EventRecorder::default());
+        let abort = || {
+            abort_wipe_with(
+                &fixture.state,
+                &fixture.audit_log,
+                &*recorder,
+                &device,
+                AbortBehavior::CleanupOnly,
+            )
+        };
+        // The user aborts while the script is still running, which kills it
+        let mut spawn = spawn_each(vec![ScriptedProcess {
+            output: Some("PROGRESS: Pass 1 - 16MB / 64MB (25%)\n"),
+            on_finish: Box::new(|| {
+                assert!(abort().is_ok());
+                false
+            }),
+        }]);
+
+        let ctx = fixture.ctx(VerifyFailurePolicy::Warn);
+        assert!(execute_wipe(&ctx, recorder.clone(), sample_run(), &mut spawn).is_err());
+        assert_eq!(
+            recorder.sequence(),
+            [
+                "wipe-progress:starting",
+                "wipe-progress:writing",
+                "cleanup-result",
+                "wipe-aborted",
+                "wipe-complete",
+            ]
+        );
+        assert_eq!(recorder.0.lock().unwrap()[4].1["success"], false);
+
+        // Nothing left to abort
+        assert!(abort().is_err());
+        assert_eq!(recorder.sequence().len(), 5);
+    }
+
+    #[test]
+    fn test_verify_failure_policies() {
+        use VerifyFailurePolicy::*;
+        assert_eq!(verify_failure_action(Fail, true, 0), VerifyAction::Accept);
+        assert_eq!(verify_failure_action(Warn, false, 0), VerifyAction::Warn);
+        assert_eq!(verify_failure_action(Retry, false, 0), VerifyAction::Retry);
+        assert_eq!(verify_failure_action(Retry, false, MAX_VERIFY_RETRIES), VerifyAction::Fail);
+        assert_eq!(verify_failure_action(Fail, false, 0), VerifyAction::Fail);
+        assert_eq!(AppSettings::default().on_verify_failure, Warn);
+
+        // The script exits cleanly but only ever confirms the first pass
+        let short_run = || ScriptedProcess {
+            output: Some("Pass 1 complete\n"),
+            on_finish: Box::new(|| true),
+        };
+        let run_with = |policy, scripts| {
+            let fixture = WipeFixture::new("verify-policy");
+            let recorder = Arc::new(EventRecorder::default());
+            let mut spawn = spawn_each(scripts);
+            let result = execute_wipe(&fixture.ctx(policy), recorder, sample_run(), &mut spawn);
+            let session = fixture.sessions.load("1765548202000-RF123456").unwrap();
+            (result, session)
+        };
+
+        let (result, session) = run_with(Warn, vec![short_run()]);
+        assert!(result.unwrap().contains("confirmed only 1 of 2 passes"));
+        let verification = session.verification.clone().unwrap();
+        assert!(session.success && !verification.passed);
+        assert_eq!(verification.policy, Warn);
+        // The certificate shows the unconfirmed outcome, as what the script reported
+        let cert = build_certificate(&session);
+        assert_eq!(cert.result, "success");
+        let rows = certificate_rows(&cert);
+        let (_, passes) = rows.iter().find(|(label, _)| label.contains("script-reported")).unwrap();
+        assert!(passes.contains("policy: warn"));
+
+        let (result, session) = run_with(Fail, vec![short_run()]);
+        assert!(result.unwrap_err().contains("Marking the wipe as failed"));
+        assert!(!session.success);
+        assert_eq!(build_certificate(&session).result, "failed");
+
+        // Retry: a second run that finishes every pass passes verification
+        let full_run = ScriptedProcess {
+            output: Some(QUICK_RUN_OUTPUT),
+            on_finish: Box::new(|| true),
+        };
+        let (result, session) = run_with(Retry, vec![short_run(), full_run]);
+        assert!(result.is_ok());
+        let verification = session.verification.unwrap();
+        assert!(session.success && verification.passed);
+        assert_eq!(verification.attempts, 2);
+        // ...and one that still falls short fails
+        let (result, session) = run_with(Retry, vec![short_run(), short_run()]);
+        assert!(result.unwrap_err().contains("after 2 runs"));
+        assert!(!session.success);
+    }
+
+    #[test]
+    fn test_power_off_expected_disappearance() {
+        let gone = "List of devices attached\n";
+        let still_here = "List of devices attached\nRF123456\tdevice\n";
+        let power_off = |reboot: CommandOutput, devices: &'static str| {
+            let runner = FakeRunner(vec![
+                ("reboot -p", reboot),
+                ("adb devices", ok(devices)),
+                ("sys.boot_completed", ok("1\n")),
+            ]);
+            let recorder = EventRecorder::default();
+            let result =
+                power_off_with(&runner, &recorder, "RF123456", Duration::ZERO, Duration::ZERO);
+            (result, recorder.sequence())
+        };
+
+        // The connection closing under `reboot -p` is the power-off working
+        for reboot in [ok(""), failed("error: closed"), failed("")] {
+            let (result, events) = power_off(reboot, gone);
+            assert!(result.is_ok());
+            assert_eq!(events, ["device-powered-off"]);
+        }
+
+        let (result, events) = power_off(failed("error: device unauthorized."), gone);
+        assert!(result.unwrap_err().contains("unauthorized"));
+        assert!(events.is_empty());
+
+        let (result, events) = power_off(ok(""), still_here);
+        assert!(result.unwrap_err().contains("still connected (Online)"));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_reset_reboot_detection() {
+        use DeviceLifecycleState::*;
+        let watch = |observations: &[(DeviceLifecycleState, Option<bool>)]| {
+            observations.iter().fold(ResetWatch::Waiting, |watch, &(state, setup)| {
+                next_reset_watch(watch, state, setup)
+            })
+        };
+
+        // Dropped off, then back in the setup wizard or with its adb keys cleared
+        let into_setup = [
+            (Online, Some(true)),
+            (Absent, None),
+            (Rebooting, None),
+            (Online, Some(false)),
+        ];
+        assert_eq!(watch(&into_setup), ResetWatch::Confirmed);
+        let keys_cleared = [(Online, Some(true)), (Recovery, None), (Unauthorized, None)];
+        assert_eq!(watch(&keys_cleared), ResetWatch::Confirmed);
+        // Never dropped off: the user backed out, however long we wait
+        assert_eq!(watch(&[(Online, Some(true)), (Online, Some(true))]), ResetWatch::Waiting);
+        assert_eq!(watch(&[(Unauthorized, None)]), ResetWatch::Waiting);
+        // A plain reboot comes back with setup already done
+        let plain_reboot = [(Absent, None), (Online, Some(true))];
+        assert_eq!(watch(&plain_reboot), ResetWatch::RebootedWithoutReset);
+        // Unknown setup state while booting isn't an answer yet; a later drop doesn't undo one
+        assert_eq!(watch(&[(Offline, None), (Online, None)]), ResetWatch::Rebooting);
+        let unplugged_after = [(Absent, None), (Unauthorized, None), (Absent, None)];
+        assert_eq!(watch(&unplugged_after), ResetWatch::Confirmed);
+
+        let gone = FakeRunner(vec![("adb devices", ok("List of devices attached\n"))]);
+        let recorder = EventRecorder::default();
+        let check =
+            poll_reset_started_with(&gone, &recorder, "RF123456", Duration::ZERO, Duration::ZERO)
+                .unwrap();
+        assert!(check.rebooted && !check.confirmed);
+        assert!(recorder.sequence().is_empty());
+
+        let revoked = FakeRunner(vec![
+            ("adb devices", ok("List of devices attached\nRF123456\tunauthorized\n")),
+        ]);
+        let recorder = EventRecorder::default();
+        let check =
+            poll_reset_started_with(&revoked, &recorder, "RF123456", Duration::ZERO, Duration::ZERO)
+                .unwrap();
+        // Unauthorized without first dropping off isn't proof of a reset
+        assert!(!check.rebooted && !check.confirmed);
+        assert!(recorder.sequence().is_empty());
+    }
+
+    #[test]
+    fn test_dry_run_factory_reset() {
+        let resolved = |activity: &str| {
+            ok(&format!(
+                "priority=0 preferredOrder=0 match=0x108000 specificIndex=-1 isDefault=true\n{}\n",
+                activity
+            ))
+        };
+        let runner = FakeRunner(vec![
+            ("ro.product.brand", ok("google\n")),
+            ("ro.product.model", ok("Pixel 8\n")),
+            ("-a android.settings.MASTER_CLEAR", ok("No activity found\n")),
+            (
+                "-a android.settings.BACKUP_AND_RESET_SETTINGS",
+                failed("Exception occurred while executing 'resolve-activity'"),
+            ),
+            (
+                "-a android.settings.PRIVACY_SETTINGS",
+                resolved("com.android.settings/.Settings$PrivacySettingsActivity"),
+            ),
+            (
+                "-a android.settings.INTERNAL_STORAGE_SETTINGS",
+                resolved("com.android.settings/.Settings$StorageDashboardActivity"),
+            ),
+        ]);
+
+        let result = dry_run_factory_reset_with(&runner, "RF123456", false).unwrap();
+        assert!(result.dry_run && !result.fallback);
+        let resolvable: Vec<bool> = result.resolutions.iter().map(|r| r.resolvable).collect();
+        assert_eq!(resolvable, [false, false, true, true]);
+        assert_eq!(
+            result.resolutions[2].activity.as_deref(),
+            Some("com.android.settings/.Settings$PrivacySettingsActivity")
+        );
+        // What a real run would try first, past the two that don't resolve
+        assert_eq!(result.intent, "android.settings.PRIVACY_SETTINGS");
+        assert_eq!(result.screen, "Privacy Settings");
+        assert_eq!(result.blocked.len(), 2);
+        assert!(result.summary.contains("2 of 4 reset intents resolve"));
+        assert_eq!(result.instructions, get_instructions("google".into(), "Pixel 8".into()));
+
+        // Nothing resolves: a real run would fall back to the Settings app
+        let none = FakeRunner(vec![("resolve-activity", ok("No activity found\n"))]);
+        let result = dry_run_factory_reset_with(&none, "RF123456", true).unwrap();
+        assert!(result.fallback && result.dry_run);
+        assert_eq!((result.intent.as_str(), result.blocked.len()), (SETTINGS_COMPONENT, 4));
+
+        assert_eq!(parse_resolved_activity("No activity found"), None);
+    }
+
+    #[test]
+    fn test_reset_preconditions() {
+        let idle = WipeState::default();
+        let devices = "List of devices attached\nRF123456\tdevice\nZY22ABCD\tunauthorized\n";
+        let device = |keyguard: &str| {
+            FakeRunner(vec![
+                ("adb devices", ok(devices)),
+                ("sys.boot_completed", ok("1\n")),
+                ("dumpsys window", ok(keyguard)),
+            ])
+        };
+        let unlocked = device("  mShowingDream=false mDreamingLockscreen=false\n");
+        assert!(check_reset_preconditions(&unlocked, &idle, "RF123456").is_ok());
+
+        let locked = device("  mShowingDream=false mDreamingLockscreen=true\n");
+        let err = check_reset_preconditions(&locked, &idle, "RF123456").unwrap_err();
+        assert!(err.contains("Unlock"));
+
+        let err = check_reset_preconditions(&unlocked, &idle, "ZY22ABCD").unwrap_err();
+        assert!(err.contains("Allow USB debugging"));
+        let err = check_reset_preconditions(&unlocked, &idle, "GONE0000").unwrap_err();
+        assert!(err.contains("not connected"));
+
+        let wiping = WipeState {
+            device_id: Mutex::new(Some("RF123456".to_string())),
+            ..WipeState::default()
+        };
+        let err = check_reset_preconditions(&unlocked, &wiping, "RF123456").unwrap_err();
+        assert!(err.contains("still running"));
+
+        assert_eq!(parse_keyguard_locked("mKeyguardShowing=true"), Some(true));
+        assert_eq!(parse_keyguard_locked("Window #1 Window{...}"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_deadline_runner_stops_slow_commands() {
+        let runner = DeadlineRunner::new(Duration::from_millis(200), Duration::from_millis(300));
+        let started = Instant::now();
+
+        let err = runner.run("sleep", &["5".to_string()]).unwrap_err();
+        assert!(err.contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // The overall deadline caps what's left for the next command...
+        let err = runner.run("sleep", &["5".to_string()]).unwrap_err();
+        assert!(err.contains("timed out") || err.contains("deadline passed"));
+        // ...and once it has passed, nothing else is started
+        let err = runner.run("echo", &["hi".to_string()]).unwrap_err();
+        assert!(err.contains("deadline passed"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let quick = DeadlineRunner::new(Duration::from_secs(5), Duration::from_secs(5));
+        assert_eq!(quick.run("echo", &["hi".to_string()]).unwrap().stdout, "hi\n");
+    }
+
+    #[test]
+    fn test_abort_behavior() {
+        let runner = FakeRunner(vec![
+            ("pkill", ok("")),
+            ("[d]d if", ok("stopped\n")),
+            ("test -e", ok("removed\n")),
+            ("rm -rf", ok("")),
+            ("ro.product.brand", ok("samsung\n")),
+            ("ro.product.model", ok("SM-G991B\n")),
+            ("MASTER_CLEAR", ok("Starting: Intent { act=android.settings.MASTER_CLEAR }\n")),
+        ]);
+
+        let (cleanup, reset) = abort_with(&runner, "ABC123", None, AbortBehavior::CleanupOnly);
+        assert!(cleanup.dd_stopped && cleanup.temp_removed);
+        assert!(reset.is_none());
+
+        let (_, reset) = abort_with(&runner, "ABC123", None, AbortBehavior::CleanupAndReset);
+        let reset = reset.unwrap().unwrap();
+        assert_eq!(reset.intent, "android.settings.MASTER_CLEAR");
+        assert_eq!((reset.brand.as_str(), reset.phase.as_str()), ("samsung", "final"));
+
+        assert_eq!(AbortBehavior::default(), AbortBehavior::CleanupOnly);
+        let parsed: AbortBehavior = serde_json::from_str(r#""cleanup_and_reset""#).unwrap();
+        assert_eq!(parsed, AbortBehavior::CleanupAndReset);
+    }
+
+    #[test]
+    fn test_interrupted_wipe_records_passes_completed() {
+        let fixture = WipeFixture::new("passes-completed");
+        // Pass 1 finishes, then the script dies partway through pass 2
+        let mut spawn = spawn_each(vec![ScriptedProcess {
+            output: Some(
+                "=== PASS 1 of 2 ===\nPass 1 complete\n=== PASS 2 of 2 ===\n\
+                 Writing 64MB of random data...\n",
+            ),
+            on_finish: Box::new(|| false),
+        }]);
+        let recorder = Arc::new(EventRecorder::default());
+        let ctx = fixture.ctx(VerifyFailurePolicy::Warn);
+        assert!(execute_wipe(&ctx, recorder.clone(), sample_run(), &mut spawn).is_err());
+        drop(spawn);
+
+        let session = &fixture.sessions.list().unwrap()[0];
+        assert!(!session.success);
+        assert_eq!((session.passes, session.passes_completed), (2, Some(1)));
+        let events = recorder.0.lock().unwrap();
+        let (_, complete) = events.iter().find(|(e, _)| e == "wipe-complete").unwrap();
+        assert_eq!(complete["passes_requested"], 2);
+        assert_eq!(complete["passes_completed"], 1);
+        let cert = build_certificate(session);
+        assert_eq!((cert.passes_requested, cert.passes_completed), (Some(2), Some(1)));
+        // Counted per wipe: the next one starts from zero
+        assert_eq!(fixture.state.take_passes_completed("RF123456"), 0);
+
+        // Records from before the count existed don't claim one
+        let old = WipeSession {
+            passes_completed: None,
+            ..sample_session()
+        };
+        let cert = build_certificate(&old);
+        assert_eq!((cert.passes_requested, cert.passes_completed), (None, None));
+    }
+
+    #[test]
+    fn test_records_carry_versions() {
+        let fixture = WipeFixture::new("versions");
+        let mut spawn = spawn_each(vec![ScriptedProcess {
+            output: Some(QUICK_RUN_OUTPUT),
+            on_finish: Box::new(|| true),
+        }]);
+        let recorder = Arc::new(EventRecorder::default());
+        let ctx = fixture.ctx(VerifyFailurePolicy::Warn);
+        assert!(execute_wipe(&ctx, recorder, sample_run(), &mut spawn).is_ok());
+        drop(spawn);
+
+        let cert = build_certificate(&fixture.sessions.list().unwrap()[0]);
+        assert_eq!(cert.app_version.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!(cert.script_version.as_deref(), Some("2.2.0"));
+        let pdf = render_certificate_pdf(&cert, &ArtifactGeneration::default().begin()).unwrap();
+        assert!(String::from_utf8_lossy(&pdf).contains("wipe script 2.2.0"));
+
+        let audit = fs::read_to_string(fixture.audit_log.path()).unwrap();
+        let entries: Vec<AuditEntry> =
+            audit.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert!(entries.iter().all(|e| e.app_version.as_deref() == Some(APP_VERSION)));
+        assert!(audit.contains("script_version=2.2.0"));
+
+        // The bundled scripts declare the version recorded above
+        let scripts = Path::new(env!("CARGO_MANIFEST_DIR")).join("scripts");
+        for script in ["quick_wipe.sh", "full_wipe.sh"] {
+            let source = fs::read_to_string(scripts.join(script)).unwrap();
+            assert_eq!(parse_script_version(&source).as_deref(), Some("2.3.0"));
+            assert!(source.contains("# Version: 2.3.0"));
+        }
+        assert_eq!(parse_script_version("#!/bin/bash\necho hi\n"), None);
+    }
+
+    #[test]
+    fn test_phase_transitions_timestamped() {
+        let state = WipeState::default();
+        for (phase, at_ms) in [
+            ("starting", 1_000),
+            ("writing", 1_500),
+            ("writing", 30_000), // Still writing: not a transition
+            ("complete", 61_500),
+            ("writing", 62_000),
+            ("verifying", 122_000),
+            ("cleanup", 125_000),
+        ] {
+            state.note_phase("RF123456", phase, at_ms);
+        }
+        state.note_phase("ZY22", "writing", 5_000);
+
+        let transitions = state.take_phases("RF123456");
+        let phases: Vec<&str> = transitions.iter().map(|t| t.phase.as_str()).collect();
+        assert_eq!(phases, ["starting", "writing", "complete", "writing", "verifying", "cleanup"]);
+        assert_eq!(transitions[1].at_ms, 1_500);
+        assert!(state.take_phases("RF123456").is_empty());
+        assert_eq!(state.take_phases("ZY22").len(), 1);
+
+        let durations = phase_durations(&transitions, 126_000);
+        let totals: Vec<(&str, u64)> =
+            durations.iter().map(|d| (d.phase.as_str(), d.duration_ms)).collect();
+        assert_eq!(
+            totals,
+            [
+                ("starting", 500),
+                ("writing", 120_000),
+                ("complete", 500),
+                ("verifying", 3_000),
+                ("cleanup", 1_000)
+            ]
+        );
+        assert_eq!(
+            describe_phase_durations(&durations),
+            "starting 0s, writing 2m 0s, complete 0s, verifying 3s, cleanup 1s"
+        );
+
+        // A real run records its phases in the session and the completion summary
+        let fixture = WipeFixture::new("phase-durations");
+        let mut spawn = spawn_each(vec![ScriptedProcess {
+            output: Some(QUICK_RUN_OUTPUT),
+            on_finish: Box::new(|| true),
+        }]);
+        let recorder = Arc::new(EventRecorder::default());
+        let ctx = fixture.ctx(VerifyFailurePolicy::Warn);
+        assert!(execute_wipe(&ctx, recorder.clone(), sample_run(), &mut spawn).is_ok());
+        drop(spawn);
+        let session = &fixture.sessions.list().unwrap()[0];
+        let recorded: Vec<&str> =
+            session.phase_durations.iter().map(|d| d.phase.as_str()).collect();
+        assert_eq!(recorded[0], "starting");
+        assert!(recorded.contains(&"cleanup") && recorded.ends_with(&["verifying"]));
+        let events = recorder.0.lock().unwrap();
+        let (_, complete) = events.iter().find(|(e, _)| e == "wipe-complete").unwrap();
+        assert!(complete["phase_summary"].as_str().unwrap().contains("verifying"));
+        assert!(fixture.state.take_phases("RF123456").is_empty());
+    }
+
+    #[test]
+    fn test_data_only_wipe_skips_reset() {
+        let fixture = WipeFixture::new("data-only");
+        let mut run = sample_run();
+        run.config.skip_factory_reset = true;
+        let mut spawn = spawn_each(vec![ScriptedProcess {
+            output: Some(QUICK_RUN_OUTPUT),
+            on_finish: Box::new(|| {
+                assert!(fixture.state.data_only.lock().unwrap().contains("RF123456"));
+                true
+            }),
+        }]);
+        let recorder = Arc::new(EventRecorder::default());
+        let ctx = fixture.ctx(VerifyFailurePolicy::Warn);
+        assert!(execute_wipe(&ctx, recorder.clone(), run, &mut spawn).is_ok());
+        drop(spawn);
+
+        let session = &fixture.sessions.list().unwrap()[0];
+        assert!(session.factory_reset_skipped);
+        let events = recorder.0.lock().unwrap();
+        let (_, complete) = events.iter().find(|(e, _)| e == "wipe-complete").unwrap();
+        assert_eq!(complete["factory_reset_skipped"], true);
+        assert!(complete["reset_note"].as_str().unwrap().starts_with("No factory reset"));
+        let audit = fs::read_to_string(fixture.audit_log.path()).unwrap();
+        assert!(audit.contains("factory_reset=skipped"));
+        assert!(fixture.state.data_only.lock().unwrap().is_empty());
+
+        // Aborting a data-only wipe never opens the reset screen, whatever was asked for
+        *fixture.state.device_id.lock().unwrap() = Some("ABC123".to_string());
+        fixture.state.data_only.lock().unwrap().insert("ABC123".to_string());
+        let runner = FakeRunner(vec![
+            ("pkill", ok("")),
+            ("[d]d if", ok("stopped\n")),
+            ("test -e", ok("removed\n")),
+            ("rm -rf", ok("")),
+        ]);
+        let recorder = EventRecorder::default();
+        let reset = AbortBehavior::CleanupAndReset;
+        abort_wipe_with(&fixture.state, &fixture.audit_log, &recorder, &runner, reset).unwrap();
+        assert!(!recorder.sequence().contains(&"factory-reset".to_string()));
+
+        let config = WipeConfig {
+            skip_factory_reset: true,
+            double_reset: true,
+            ..quick_config(64)
+        };
+        let err = config.validate_and_clamp(ChunkSizeBounds::default()).unwrap_err();
+        assert!(err.contains("data-only"));
+    }
+
+    #[test]
+    fn test_identity_guard_stops_changed_device() {
+        let state = WipeState::default();
+        let serial = |value: &str| FakeRunner(vec![("ro.serialno", ok(&format!("{}\n", value)))]);
+        // No workflow on the id: nothing to compare against, nothing is run
+        assert!(state.guard_identity(&FakeRunner(vec![]), "emulator-5554").is_ok());
+
+        state.start_workflow("emulator-5554", "35171FDH2000AB");
+        assert!(state.guard_identity(&serial("35171fdh2000ab"), "emulator-5554").is_ok());
+        let err = state.guard_identity(&serial("R5CT40ABCDE"), "emulator-5554").unwrap_err();
+        assert!(err.starts_with("Device identity changed"));
+        assert!(err.contains("35171FDH2000AB") && err.contains("R5CT40ABCDE"));
+        let offline = FakeRunner(vec![("ro.serialno", failed("error: device offline"))]);
+        let err = state.guard_identity(&offline, "emulator-5554").unwrap_err();
+        assert!(err.contains("Could not confirm"));
+        // A device without a serial was recorded under its id, and still matches
+        state.start_workflow("192.168.1.20:5555", "192.168.1.20:5555");
+        assert!(state.guard_identity(&serial(""), "192.168.1.20:5555").is_ok());
+
+        // Aborting with a reset only cleans up once the id belongs to another device
+        let fixture = WipeFixture::new("identity-guard");
+        *fixture.state.device_id.lock().unwrap() = Some("emulator-5554".to_string());
+        fixture.state.start_workflow("emulator-5554", "35171FDH2000AB");
+        let runner = FakeRunner(vec![
+            ("ro.serialno", ok("R5CT40ABCDE\n")),
+            ("pkill", ok("")),
+            ("[d]d if", ok("stopped\n")),
+            ("test -e", ok("removed\n")),
+            ("rm -rf", ok("")),
+        ]);
+        let recorder = EventRecorder::default();
+        let reset = AbortBehavior::CleanupAndReset;
+        let message =
+            abort_wipe_with(&fixture.state, &fixture.audit_log, &recorder, &runner, reset).unwrap();
+        assert!(message.contains("Device identity changed"));
+        assert!(!recorder.sequence().contains(&"factory-reset".to_string()));
+
+        // Detecting the device again starts over
+        state.end_workflow("emulator-5554");
+        assert!(state.guard_identity(&serial("R5CT40ABCDE"), "emulator-5554").is_ok());
+    }
+
+    #[test]
+    fn test_typed_confirmation() {
+        let runner = FakeRunner(vec![
+            ("ro.product.model", ok("SM-S928U\n")),
+            ("ro.serialno", ok("R5CT40ABCDE\n")),
+        ]);
+        let on = AppSettings {
+            require_typed_confirmation: true,
+            ..AppSettings::default()
+        };
+        let confirm = |token| confirm_destructive_with(&runner, &on, "R5CT40ABCDE", token);
+
+        // Wrong, partial or missing tokens block the operation
+        assert!(confirm(Some("SM-S918U")).unwrap_err().contains("doesn't match"));
+        assert!(confirm(Some("CDE")).is_err());
+        assert!(confirm(Some("")).unwrap_err().contains("Type the device model (SM-S928U)"));
+        assert!(confirm(None).is_err());
+        // The model (any case) or the last 4 of the serial proceed
+        assert!(confirm(Some(" sm-s928u ")).is_ok());
+        assert!(confirm(Some("abcde")).is_err());
+        assert!(confirm(Some("bcde")).is_ok());
+
+        // Off by default: no token needed, the device isn't even asked
+        let off = AppSettings::default();
+        assert!(confirm_destructive_with(&FakeRunner(vec![]), &off, "R5CT40ABCDE", None).is_ok());
+        // A device that reports neither can't be confirmed
+        assert!(check_confirmation_token(Some("abc"), "", "abc").is_err());
+    }
+
+    #[test]
+    fn test_summarize_latency() {
+        let samples = [
+            Duration::from_millis(12),
+            Duration::from_millis(30),
+            Duration::from_millis(18),
+        ];
+        let stats = summarize_latency(&samples, 1).unwrap();
+        assert_eq!((stats.samples, stats.failed), (3, 1));
+        assert!((stats.min_ms - 12.0).abs() < 1e-9);
+        assert!((stats.avg_ms - 20.0).abs() < 1e-9);
+        assert!((stats.max_ms - 30.0).abs() < 1e-9);
+        assert!(summarize_latency(&[], 5).is_none());
+
+        let silent = FakeRunner(vec![("echo ping", failed("error: device offline"))]);
+        let err = ping_device_with(&silent, "ABC123", 3).unwrap_err();
+        assert!(err.contains("any of 3 pings"));
+        let stats = ping_device_with(&FakeRunner(vec![("echo ping", ok("ping\n"))]), "ABC123", 2);
+        assert_eq!(stats.unwrap().samples, 2);
+    }
+
+    #[test]
+    fn test_refresh_device_by_serial() {
+        let known = KnownDevices::new();
+        known.remember("R5CT40ABCDE", "192.168.1.20:5555");
+        let serial_of = |id: &str| match id {
+            "R5CT40ABCDE" => "R5CT40ABCDE".to_string(),
+            "emulator-5554" => "EMULATOR34X1X11X0".to_string(),
+            _ => String::new(),
+        };
+
+        // Was on Wi-Fi, replugged over USB: same phone under a new id
+        let listed = vec![
+            ("emulator-5554".to_string(), "device".to_string()),
+            ("R5CT40ABCDE".to_string(), "device".to_string()),
+        ];
+        let refreshed = refresh_device_id(&known, "192.168.1.20:5555", &listed, serial_of);
+        assert_eq!(refreshed.unwrap(), "R5CT40ABCDE");
+
+        // Still listed under the old id: nothing to refresh
+        let unchanged = refresh_device_id(&known, "emulator-5554", &listed, serial_of);
+        assert_eq!(unchanged.unwrap(), "emulator-5554");
+
+        // Unknown network id with no serial to go on
+        let err = refresh_device_id(&known, "10.0.0.7:5555", &listed, serial_of).unwrap_err();
+        assert!(err.contains("no longer connected"), "{}", err);
+
+        let gone = vec![("emulator-5554".to_string(), "device".to_string())];
+        assert!(refresh_device_id(&known, "192.168.1.20:5555", &gone, serial_of).is_err());
+    }
+
+    #[test]
+    fn test_known_devices_reassociation() {
+        let known = KnownDevices::new();
+        known.remember("RF123456", "RF123456");
+        let serial_of = |id: &str| match id {
+            "192.168.1.20:5555" | "RF123456" => "RF123456".to_string(),
+            _ => "OTHER999".to_string(),
+        };
+        let listing = |entries: &[(&str, &str)]| -> Vec<(String, String)> {
+            entries.iter().map(|(id, state)| (id.to_string(), state.to_string())).collect()
+        };
+
+        // Unchanged
+        assert!(known.reassociate(&listing(&[("RF123456", "device")]), serial_of).is_empty());
+
+        // Back after the reset reboot, but the debugging prompt is pending again
+        let changes = known.reassociate(&listing(&[("RF123456", "unauthorized")]), serial_of);
+        assert_eq!(changes.len(), 1);
+        assert!(!changes[0].authorized);
+        // Authorized again
+        let changes = known.reassociate(&listing(&[("RF123456", "device")]), serial_of);
+        assert!(changes[0].authorized);
+
+        // Reconnected over wireless: the new transport id maps back to the same device
+        let listed = listing(&[("192.168.1.20:5555", "device"), ("ZY22OTHER", "device")]);
+        let changes = known.reassociate(&listed, serial_of);
+        assert_eq!(
+            changes,
+            vec![DeviceReassociation {
+                serial: "RF123456".to_string(),
+                previous_id: "RF123456".to_string(),
+                device_id: "192.168.1.20:5555".to_string(),
+                authorized: true,
+            }]
+        );
+        assert_eq!(known.current_id("RF123456").as_deref(), Some("192.168.1.20:5555"));
+        assert!(known.current_id("ZY22OTHER").is_none());
+        assert!(known.reassociate(&listed, serial_of).is_empty());
+    }
+
+    #[test]
+    fn test_lifecycle_state_mapping() {
+        use DeviceLifecycleState::*;
+        let cases = [
+            (None, true, Absent),
+            (Some("device"), true, Online),
+            (Some("device"), false, Rebooting),
+            (Some("bootloader"), false, Rebooting),
+            (Some("authorizing"), false, Rebooting),
+            (Some("unauthorized"), false, Unauthorized),
+            (Some("no"), false, Unauthorized),
+            (Some("offline"), true, Offline),
+            (Some("recovery"), false, Recovery),
+            (Some("sideload"), false, Recovery),
+            (Some("rescue"), false, Recovery),
+        ];
+        for (adb_state, boot_completed, expected) in cases {
+            assert_eq!(lifecycle_state(adb_state, boot_completed), expected, "{:?}", adb_state);
+        }
+    }
+
+    #[test]
+    fn test_dedupe_devices_by_serial() {
+        let listed: Vec<(String, String)> = [
+            ("192.168.1.20:5555", "device"),
+            ("RF123456", "device"),
+            ("ZY22ABCD", "unauthorized"),
+            ("adb-ZY22ABCD-x1y2z3._adb-tls-connect._tcp", "device"),
+        ]
+        .iter()
+        .map(|(id, state)| (id.to_string(), state.to_string()))
+        .collect();
+        let serial_of = |id: &str| match id {
+            "192.168.1.20:5555" | "RF123456" => "RF123456\n".to_string(),
+            _ => "zy22abcd".to_string(),
+        };
+
+        let devices = dedupe_devices_by_serial(listed, serial_of);
+        let ids: Vec<&str> = devices.iter().map(|(id, _)| id.as_str()).collect();
+        // USB wins over the network entry; the authorized entry over the unauthorized one
+        assert_eq!(ids, ["RF123456", "adb-ZY22ABCD-x1y2z3._adb-tls-connect._tcp"]);
+    }
+
+    #[test]
+    fn test_read_lifecycle_state() {
+        let devices = "List of devices attached\n\
+                       ABC123\tdevice\n\
+                       DEF456\trecovery\n\
+                       GHI789\tno permissions (user in plugdev group)\n";
+        let runner = FakeRunner(vec![
+            ("adb devices", ok(devices)),
+            ("sys.boot_completed", ok("\n")),
+        ]);
+        assert_eq!(read_lifecycle_state(&runner, "ABC123"), Ok(DeviceLifecycleState::Rebooting));
+        assert_eq!(read_lifecycle_state(&runner, "DEF456"), Ok(DeviceLifecycleState::Recovery));
+        assert_eq!(read_lifecycle_state(&runner, "GHI789"), Ok(DeviceLifecycleState::Unauthorized));
+        assert_eq!(read_lifecycle_state(&runner, "XYZ000"), Ok(DeviceLifecycleState::Absent));
+
+        let booted = FakeRunner(vec![
+            ("adb devices", ok(devices)),
+            ("sys.boot_completed", ok("1\n")),
+        ]);
+        assert_eq!(read_lifecycle_state(&booted, "ABC123"), Ok(DeviceLifecycleState::Online));
+        // parse_adb_devices still only returns usable devices
+        assert_eq!(parse_adb_devices(devices).len(), 1);
+    }
+
+    #[test]
+    fn test_adb_server_ownership() {
+        // A server that was already running belongs to someone else
+        let server = AdbServer::new();
+        server.ensure_started(&FakeRunner(vec![("start-server", ok(""))]), None).unwrap();
+        assert!(!server.is_owned());
+        // kill-server isn't stubbed, so reaching it would fail
+        assert_eq!(server.shutdown(&FakeRunner(vec![])), Ok(false));
+
+        // One we launched is ours to stop, exactly once
+        let started = CommandOutput {
+            success: true,
+            stdout: String::new(),
+            stderr: "* daemon not running; starting now at tcp:5037\n\
+                     * daemon started successfully\n"
+                .to_string(),
+        };
+        server.ensure_started(&FakeRunner(vec![("start-server", started)]), None).unwrap();
+        assert!(server.is_owned());
+        // A later check against the now-running server doesn't drop ownership
+        server.ensure_started(&FakeRunner(vec![("start-server", ok(""))]), None).unwrap();
+        let runner = FakeRunner(vec![("kill-server", ok(""))]);
+        assert_eq!(server.shutdown(&runner), Ok(true));
+        assert_eq!(server.shutdown(&runner), Ok(false));
+
+        // A server that won't start is an error for check_adb_status to report, not ownership
+        let busy = failed("could not install *smartsocket* listener: Address already in use");
+        let runner = FakeRunner(vec![("start-server", busy)]);
+        let err = server.ensure_started(&runner, None).unwrap_err();
+        assert!(err.starts_with("Failed to start adb server"));
+        assert!(!server.is_owned());
+        // Opt-in: by default the server is left running on exit
+        assert!(!AppSettings::default().kill_adb_server_on_exit);
+    }
+
+    #[test]
+    fn test_adb_server_conflict_detection() {
+        let restarted = CommandOutput {
+            success: true,
+            stdout: String::new(),
+            stderr: "adb server version (40) doesn't match this client (41); killing...\n\
+                     * daemon started successfully\n"
+                .to_string(),
+        };
+        assert_eq!(parse_adb_version_mismatch(&restarted.stderr), Some((40, 41)));
+        let warning = detect_adb_server_conflict(&restarted, None, None).unwrap();
+        assert!(warning.contains("version 40, this one is 41"));
+        assert!(warning.contains("Android Studio"));
+
+        let server = AdbServer::new();
+        let runner = FakeRunner(vec![("start-server", restarted)]);
+        server.ensure_started(&runner, Some(DEFAULT_ADB_SERVER_PORT)).unwrap();
+        assert!(server.conflict().unwrap().contains("different adb"));
+        server.ensure_started(&FakeRunner(vec![("start-server", ok(""))]), None).unwrap();
+        assert_eq!(server.conflict(), None);
+
+        // Matching versions on the default port are fine
+        assert_eq!(detect_adb_server_conflict(&ok(""), None, Some((41, 41))), None);
+        assert_eq!(detect_adb_server_conflict(&ok(""), Some("5037"), None), None);
+        let port_warning = detect_adb_server_conflict(&ok(""), Some("5039"), None).unwrap();
+        assert!(port_warning.contains("5039"));
+
+        let busy = failed("could not install *smartsocket* listener: Address already in use");
+        assert!(detect_adb_server_conflict(&busy, None, None).unwrap().contains("Port 5037"));
+
+        // Every check asks the running server for its version, before start-server can
+        // quietly replace it. A stand-in server answers host:version with 40 (0x28).
+        assert_eq!(parse_adb_client_version("Android Debug Bridge version 1.0.41\n"), Some(41));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+        let stand_in = std::thread::spawn(move || {
+            use std::io::Read;
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 16];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!(&request, b"000chost:version");
+            stream.write_all(b"OKAY00040028").unwrap();
+        });
+        let runner = FakeRunner(vec![
+            ("adb version", ok("Android Debug Bridge version 1.0.41\n")),
+            ("start-server", ok("")),
+        ]);
+        server.ensure_started(&runner, Some(&port)).unwrap();
+        stand_in.join().unwrap();
+        assert!(server.conflict().unwrap().contains("version 40, this one is 41"));
+        // Nothing listening: no version to compare, only the port override is reported
+        server.ensure_started(&runner, Some(&port)).unwrap();
+        assert!(server.conflict().unwrap().contains(&format!("set to {}", port)));
+    }
+
+    #[test]
+    fn test_chunk_size_bounds() {
+        let config = quick_config(16);
+        // Defaults keep the 64MB floor
+        let clamped = config.validate_and_clamp(ChunkSizeBounds::default()).unwrap();
+        assert_eq!(clamped.size_mb, Some(64));
+        // A lowered floor lets a nearly full device use a small chunk
+        let low = ChunkSizeBounds {
+            min_mb: 8,
+            max_mb: 512,
+        };
+        assert_eq!(config.validate_and_clamp(low).unwrap().size_mb, Some(16));
+        assert_eq!(quick_config(4).validate_and_clamp(low).unwrap().size_mb, Some(8));
+        assert_eq!(quick_config(4096).validate_and_clamp(low).unwrap().size_mb, Some(512));
+        // The 1GB default chunk is clamped like any other
+        let no_size = WipeConfig {
+            size_mb: None,
+            ..config
+        };
+        assert_eq!(no_size.validate_and_clamp(low).unwrap().size_mb, Some(512));
+
+        assert_eq!(low.validate(), Ok(low));
+        let absurd = |min_mb, max_mb| ChunkSizeBounds { min_mb, max_mb }.validate().is_err();
+        assert!(absurd(0, 64));
+        assert!(absurd(128, 64));
+        assert!(absurd(64, 1 << 20));
+        let settings: AppSettings = serde_json::from_str("{}").unwrap();
+        assert_eq!(settings.quick_chunk_bounds, ChunkSizeBounds::default());
+
+        // Whatever the widest bounds clamp to, the script's own check accepts
+        let script = Path::new(env!("CARGO_MANIFEST_DIR")).join("scripts/quick_wipe.sh");
+        let source = fs::read_to_string(script).unwrap();
+        let check = Regex::new(
+            r#"CHUNK_SIZE_MB" -lt (\d+) \] \|\| \[ "\$CHUNK_SIZE_MB" -gt (\d+)"#,
+        )
+        .unwrap();
+        let caps = check.captures(&source).unwrap();
+        let script_min: u32 = caps[1].parse().unwrap();
+        let script_max: u32 = caps[2].parse().unwrap();
+        let widest = ChunkSizeBounds::LIMITS;
+        for size_mb in [0, 1, 64, 65536, 1 << 20] {
+            let clamped = quick_config(size_mb).validate_and_clamp(widest).unwrap();
+            let size = clamped.size_mb.unwrap();
+            assert!(script_min <= size && size <= script_max, "{size}MB rejected");
+        }
+    }
+
+    #[test]
+    fn test_wipe_config_coherence() {
+        let chunk = ChunkSizeBounds::default();
+        let base = WipeConfig {
+            mode: "full".to_string(),
+            passes: 3,
+            size_mb: None,
+            double_reset: false,
+            expect_serial: None,
+            expect_model: None,
+            max_duration_secs: 0,
+            keep_temp_files: false,
+            pattern: None,
+            emulator_test: false,
+            skip_factory_reset: false,
+            clear_app_data: false,
+        };
+        let with = |mode: &str, pattern: Option<&str>, size_mb: Option<u32>| WipeConfig {
+            mode: mode.to_string(),
+            pattern: pattern.map(|p| p.to_string()),
+            size_mb,
+            ..base.clone()
+        };
+
+        // Accepted: the pattern is normalized, and passes clamped to what the scripts take
+        let random = with("full", Some("Random"), None).validate_and_clamp(chunk).unwrap();
+        assert_eq!(random.pattern.as_deref(), Some("random"));
+        let many = WipeConfig {
+            passes: 35,
+            ..with("full", None, None)
+        };
+        assert_eq!(many.validate_and_clamp(chunk).unwrap().passes, 20);
+        // Quick mode gets its default chunk size
+        let quick = with("quick", None, None).validate_and_clamp(chunk).unwrap();
+        assert_eq!((quick.passes, quick.size_mb), (3, Some(1024)));
+        // A chunk size means nothing in full mode and is dropped
+        let full = with("full", Some("random"), Some(2048)).validate_and_clamp(chunk).unwrap();
+        assert_eq!(full.size_mb, None);
+
+        // Rejected
+        let err = with("full", Some("gutmann"), None).validate_and_clamp(chunk).unwrap_err();
+        assert!(err.contains("Unknown wipe pattern 'gutmann'"));
+        let err = with("full", Some("dod"), None).validate_and_clamp(chunk).unwrap_err();
+        assert!(err.contains("Unknown wipe pattern 'dod'"));
+        let err = with("secure", None, None).validate_and_clamp(chunk).unwrap_err();
+        assert!(err.starts_with("Invalid wipe mode"));
+    }
+
+    #[test]
+    fn test_emulator_test_mode() {
+        let config = WipeConfig {
+            mode: "full".to_string(),
+            passes: 7,
+            pattern: Some("dod".to_string()),
+            double_reset: true,
+            clear_app_data: true,
+            emulator_test: true,
+            ..quick_config(2048)
+        };
+        let clamped = config.validate_and_clamp(ChunkSizeBounds::default()).unwrap();
+        assert_eq!(clamped.mode, "quick");
+        assert_eq!((clamped.passes, clamped.size_mb), (1, Some(EMULATOR_TEST_SIZE_MB)));
+        assert_eq!(clamped.pattern.as_deref(), Some("random"));
+        assert!(!clamped.double_reset);
+        assert!(!clamped.clear_app_data);
+        assert!(clamped.skip_factory_reset);
+
+        assert!(is_emulator_id("emulator-5554"));
+        assert!(!is_emulator_id("emulator-"));
+        assert!(!is_emulator_id("emulator-5554x"));
+        assert!(!is_emulator_id("RF123456"));
+        assert!(check_emulator_target("emulator-5554", "1\n").is_ok());
+        assert!(check_emulator_target("emulator-5554", "").unwrap_err().contains("qemu"));
+        assert!(check_emulator_target("RF123456", "1").unwrap_err().contains("only runs"));
+    }
+
+    /// End-to-end run against a real emulator. Ignored by default; run it with
+    /// `--ignored` once an emulator is booted, and it fails if none is connected.
+    #[test]
+    #[ignore = "needs a booted Android emulator"]
+    fn test_emulator_end_to_end() {
+        let output = Command::new("adb").arg("devices").output().expect("adb isn't available");
+        let listed = parse_adb_devices(&String::from_utf8_lossy(&output.stdout));
+        let (device_id, _) = listed
+            .into_iter()
+            .find(|(id, _)| is_emulator_id(id))
+            .expect("no emulator is connected");
+
+        let fixture = WipeFixture::new("emulator_end_to_end");
+        let ctx = fixture.ctx(VerifyFailurePolicy::default());
+        let recorder = Arc::new(EventRecorder::default());
+        let locks = DeviceLocks::new(fixture.dir.join("locks"));
+        let config = WipeConfig { emulator_test: true, ..quick_config(1024) };
+        run_wipe_with(
+            &ctx,
+            recorder.clone(),
+            &locks,
+            &ScriptCapabilityCache::new(),
+            &device_id,
+            &config,
+        )
+        .unwrap();
+
+        let events = recorder.sequence();
+        assert!(events.iter().any(|e| e.starts_with("wipe-progress")));
+        assert!(events.iter().any(|e| e == "wipe-complete"));
+        let sessions = fixture.sessions.list().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].size_mb, Some(EMULATOR_TEST_SIZE_MB));
+        assert!(sessions[0].factory_reset_skipped);
+        assert_eq!(build_certificate(&sessions[0]).result, "success");
+    }
+
+    #[test]
+    fn test_command_trace() {
+        // A tracer of its own, so the process-wide one other tests' commands go through
+        // is left alone
+        let tracer = CommandTracer::new();
+        let traces = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = traces.clone();
+        tracer.set_sink(Box::new(move |trace| sink.lock().unwrap().push(trace)));
+
+        // Off by default: nothing is reported
+        tracer.trace(Command::new("adb").args(["-s", "emulator-5554", "shell", "df"]));
+        assert!(traces.lock().unwrap().is_empty());
+
+        tracer.set_enabled(true);
+        tracer.trace(
+            Command::new("adb")
+                .args(["-s", "emulator-5554", "shell", "settings", "put", "global"])
+                .arg("auth_token=abc123")
+                .env("ADB_VENDOR_KEYS", "/home/user/.android"),
+        );
+
+        let traces = traces.lock().unwrap();
+        assert_eq!(traces.len(), 1);
+        assert_eq!(traces[0].program, "adb");
+        assert_eq!(
+            traces[0].args,
+            ["-s", "emulator-5554", "shell", "settings", "put", "global", "auth_token=***"]
+        );
+        assert_eq!(sanitize_trace_arg("--password=hunter2"), "--password=***");
+        assert_eq!(sanitize_trace_arg("/sdcard/wipe_temp"), "/sdcard/wipe_temp");
+    }
+
+    #[test]
+    fn test_resolve_device_id() {
+        let listed = vec![
+            ("adb-35171FDH2000AB-XkPz1q._adb-tls-connect._tcp".to_string(), "device".to_string()),
+            ("RF123456".to_string(), "device".to_string()),
+        ];
+        let serial_of = |id: &str| match id {
+            "RF123456" => "RF123456".to_string(),
+            _ => "35171FDH2000AB".to_string(),
+        };
+
+        // Still listed: unchanged
+        assert_eq!(resolve_device_id("RF123456", None, &listed, serial_of).unwrap(), "RF123456");
+
+        // Stale wireless id resolves to the device's new transport id by serial
+        let stale = "adb-35171FDH2000AB-a8Bc2d._adb-tls-connect._tcp";
+        assert_eq!(resolve_device_id(stale, None, &listed, serial_of).unwrap(), listed[0].0);
+
+        // ip:port ids carry no serial unless one is supplied
+        let err = resolve_device_id("192.168.1.20:5555", None, &listed, serial_of).unwrap_err();
+        assert!(err.contains("no longer connected"));
+        let resolved =
+            resolve_device_id("192.168.1.20:5555", Some("RF123456"), &listed, serial_of).unwrap();
+        assert_eq!(resolved, "RF123456");
+
+        let err = resolve_device_id("ZY22GONE", None, &listed, serial_of).unwrap_err();
+        assert!(err.contains("serial ZY22GONE"));
+    }
+
+    #[test]
+    fn test_is_ab_device() {
+        assert!(is_ab_device("_a\n", ""));
+        assert!(is_ab_device("_b", "true"));
+        assert!(is_ab_device("", "true\n"));
+        // Non-A/B: both props empty (unset), or ab_update explicitly false
+        assert!(!is_ab_device("", ""));
+        assert!(!is_ab_device("\n", "false"));
+        assert!(!is_ab_device("_", ""));
+    }
+
+    #[test]
+    fn test_firmware_props() {
+        let output = "[gsm.version.baseband]: [S918USQS4CXK1,S918USQS4CXK1]\n\
+                      [ro.bootloader]: [S918USQS4CXK1]\n\
+                      [ro.boot.slot_suffix]: []\n\
+                      [ro.product.model]: [SM-S918U]\n\
+                      [persist.sys.locale]: [en-US]\n\
+                      not a property line\n";
+        let props = parse_getprop(output);
+        assert_eq!(props.len(), 5);
+        assert_eq!(props["ro.product.model"], "SM-S918U");
+        assert_eq!(props["ro.boot.slot_suffix"], "");
+
+        // The same version for each modem is reported once
+        let baseband = firmware_version(&props["gsm.version.baseband"]);
+        assert_eq!(baseband.as_deref(), Some("S918USQS4CXK1"));
+        assert_eq!(firmware_version(&props["ro.bootloader"]).as_deref(), Some("S918USQS4CXK1"));
+        assert_eq!(firmware_version("M1,M2").as_deref(), Some("M1, M2"));
+        // Missing on Wi-Fi-only devices and emulators
+        assert_eq!(firmware_version(""), None);
+        assert_eq!(firmware_version("unknown"), None);
+
+        let mut session = sample_session();
+        session.device.bootloader = Some("S918USQS4CXK1".to_string());
+        let cert = build_certificate(&session);
+        let rows = certificate_rows(&cert);
+        let firmware = rows.iter().find(|(label, _)| *label == "Firmware").unwrap();
+        assert_eq!(firmware.1, "Bootloader S918USQS4CXK1");
+        let json = serde_json::to_value(&cert).unwrap();
+        assert_eq!(json["device_bootloader"], "S918USQS4CXK1");
+        assert!(json.get("device_baseband").is_none());
+    }
+
+    #[test]
+    fn test_parse_usb_speed() {
+        assert_eq!(parse_usb_speed("480\n").as_deref(), Some("USB 2.0 High Speed (480 Mbps)"));
+        assert_eq!(parse_usb_speed("5000").as_deref(), Some("USB 3.0 SuperSpeed (5 Gbps)"));
+        assert_eq!(parse_usb_speed("super-speed").as_deref(), Some("USB 3.0 SuperSpeed (5 Gbps)"));
+        assert_eq!(parse_usb_speed("1.5").as_deref(), Some("USB 1.0 Low Speed (1.5 Mbps)"));
+        assert_eq!(parse_usb_speed("2500").as_deref(), Some("USB (2500 Mbps)"));
+        assert!(parse_usb_speed("UNKNOWN").is_none());
+        assert!(parse_usb_speed("").is_none());
+
+        let root = test_dir("usb-sysfs");
+        for (port, serial, speed) in [("1-1", "ZY22OTHER", "5000"), ("1-2", "RF123456", "480")] {
+            fs::create_dir_all(root.join(port)).unwrap();
+            fs::write(root.join(port).join("serial"), format!("{}\n", serial)).unwrap();
+            fs::write(root.join(port).join("speed"), speed).unwrap();
+        }
+        assert_eq!(
+            find_usb_speed(&root, "RF123456").as_deref(),
+            Some("USB 2.0 High Speed (480 Mbps)")
+        );
+        assert!(find_usb_speed(&root, "NOTPLUGGED").is_none());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_check_expected_identity() {
+        let device = DeviceInfo {
+            id: "192.168.1.20:5555".to_string(),
+            model: "Pixel 8".to_string(),
+            brand: "google".to_string(),
+            android_version: "15".to_string(),
+            usb_speed: None,
+            ab_device: false,
+            bootloader: None,
+            baseband: None,
+        };
+        let config: WipeConfig = serde_json::from_str(
+            r#"{"mode": "full", "passes": 1, "size_mb": null, "double_reset": false}"#,
+        )
+        .unwrap();
+        // No expectations: always proceeds
+        assert!(check_expected_identity(&config, &device, "35171FDH2000AB").is_ok());
+
+        let mut expected = config.clone();
+        expected.expect_serial = Some(" 35171fdh2000ab ".to_string());
+        expected.expect_model = Some("pixel 8".to_string());
+        assert!(check_expected_identity(&expected, &device, "35171FDH2000AB").is_ok());
+
+        let err = check_expected_identity(&expected, &device, "OTHER123").unwrap_err();
+        assert!(err.starts_with("Device mismatch: expected serial 35171fdh2000ab"));
+
+        expected.expect_serial = None;
+        expected.expect_model = Some("SM-S928U".to_string());
+        let err = check_expected_identity(&expected, &device, "35171FDH2000AB").unwrap_err();
+        assert!(err.contains("expected model SM-S928U"));
+        assert!(err.contains("is a Pixel 8"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wipe_timer_aborts_long_run() {
+        // Simulated wipe that would otherwise run for a minute
+        let mut child = Command::new("sleep").arg("60").spawn().unwrap();
+        let pid = child.id();
+        let started = std::time::Instant::now();
+
+        let timer = WipeTimer::start(Duration::from_millis(200), move || {
+            let _ = Command::new("kill").arg(pid.to_string()).status();
+        });
+        let status = child.wait().unwrap();
+
+        assert!(timer.finish());
+        assert!(!status.success());
+        assert!(started.elapsed() < Duration::from_secs(30));
+
+        // Finishing before the limit never fires
+        let timer = WipeTimer::start(Duration::from_secs(60), || {});
+        assert!(!timer.finish());
+    }
+
+    #[test]
+    fn test_progress_queue_keeps_latest_under_flood() {
+        let progress = |pass: u32, percent: f32, phase: &str| WipeProgress {
+            pass,
+            total_passes: 3,
+            percent,
+            bytes_written: 0,
+            message: String::new(),
+            phase: phase.to_string(),
+            rate: None,
+            estimated: false,
+        };
+        let queue = Arc::new(ProgressQueue::new(4));
+        let producer = queue.clone();
+
+        // Flood the queue far faster than anyone consumes it
+        let handle = std::thread::spawn(move || {
+            for i in 0..10_000 {
+                producer.push(progress(1, i as f32 / 100.0, "writing"));
+                if i == 5_000 {
+                    producer.push(progress(1, 33.3, "complete"));
+                }
+            }
+            producer.push(progress(3, 100.0, "writing"));
+            producer.close();
+        });
+        handle.join().unwrap();
+
+        let mut received = Vec::new();
+        while let QueueItem::Progress(p) = queue.next(Duration::from_secs(1)) {
+            received.push(p);
+        }
+
+        assert!(received.len() <= 4);
+        assert_eq!(received.last().unwrap().percent, 100.0);
+        // Milestones are never dropped in favour of intermediate updates
+        assert!(received.iter().any(|p| p.phase == "complete"));
+    }
+
+    #[test]
+    fn test_progress_mode_falls_back_to_indeterminate() {
+        let start = Instant::now();
+        let mut mode = ProgressModeTracker::new(start, Duration::from_secs(30));
 
-    let output = Command::new("adb")
-        .args([
-            "-s",
-            &device_id,
-            "shell",
-            "settings",
-            "put",
-            "global",
-            "adb_enabled",
-            "0",
-        ])
-        .output()
-        .map_err(|e| format!("Failed to revoke ADB: {}", e))?;
+        // Within the grace period nothing changes
+        assert_eq!(mode.on_idle(start + Duration::from_secs(10)), None);
+        // No progress lines by the end of it: switch to indeterminate, once
+        assert_eq!(mode.on_idle(start + Duration::from_secs(31)), Some("indeterminate"));
+        assert_eq!(mode.on_idle(start + Duration::from_secs(60)), None);
+        // Progress finally shows up: back to determinate
+        assert_eq!(mode.on_progress(), Some("determinate"));
+        assert_eq!(mode.on_progress(), None);
+        assert_eq!(mode.on_idle(start + Duration::from_secs(600)), None);
 
-    if output.status.success() {
-        Ok("ADB debugging disabled on device. You may need to re-enable it for future use.".to_string())
-    } else {
-        Err("Failed to disable ADB debugging. Device may require root access.".to_string())
+        // An idle queue reports Idle rather than blocking forever
+        let queue = ProgressQueue::new(4);
+        assert!(matches!(queue.next(Duration::from_millis(10)), QueueItem::Idle));
+        queue.close();
+        assert!(matches!(queue.next(Duration::from_millis(10)), QueueItem::Closed));
     }
-}
 
-/// Clean up any temporary wipe files on device
-#[tauri::command]
-async fn cleanup_wipe_files(device_id: String) -> Result<String, String> {
-    let device_id = sanitize_device_id(&device_id)?;
+    #[test]
+    fn test_device_lock_contention() {
+        let dir = test_dir("device-locks");
+        let locks = DeviceLocks::new(dir.clone());
 
-    let output = Command::new("adb")
-        .args([
-            "-s",
-            &device_id,
-            "shell",
-            "rm",
-            "-rf",
-            "/sdcard/wipe_temp",
-            "/sdcard/secure_wipe_*",
-        ])
-        .output()
-        .map_err(|e| format!("Failed to cleanup: {}", e))?;
+        let guard = locks.acquire("192.168.1.1:5555").unwrap();
+        let err = locks.acquire("192.168.1.1:5555").err().unwrap();
+        assert!(err.contains("in use by another session"));
 
-    if output.status.success() {
-        Ok("Temporary wipe files cleaned up.".to_string())
-    } else {
-        // Not critical if cleanup fails
-        Ok("Cleanup attempted. Some files may remain.".to_string())
+        // Other devices are unaffected
+        assert!(locks.acquire("emulator-5554").is_ok());
+
+        // The lock file names its owner from the moment it exists
+        let held = fs::read_to_string(locks.lock_path("192.168.1.1:5555")).unwrap();
+        assert_eq!(held, std::process::id().to_string());
+        // Ids that differ only in ':' vs '_' get their own locks
+        assert_ne!(locks.lock_path("192.168.1.1:5555"), locks.lock_path("192.168.1.1_5555"));
+        assert!(locks.acquire("192.168.1.1_5555").is_ok());
+
+        // Released on drop
+        drop(guard);
+        assert!(locks.acquire("192.168.1.1:5555").is_ok());
+        // Only lock files are left behind while held, no pid scratch files
+        let tmp = fs::read_dir(&dir).unwrap().flatten();
+        assert!(!tmp.into_iter().any(|e| e.path().extension().is_some_and(|x| x == "tmp")));
+
+        fs::remove_dir_all(dir).unwrap();
     }
-}
 
-// ============================================================================
-// App Setup
-// ============================================================================
+    #[test]
+    fn test_device_lock_stale_cleanup() {
+        let dir = test_dir("device-locks-stale");
+        let locks = DeviceLocks::new(dir.clone());
 
-/// Cleanup any running wipe processes and temp files
-fn cleanup_on_exit(state: &WipeState) {
-    // Get the device ID if a wipe was in progress
-    let device_id = {
-        let dev_lock = state.device_id.lock().unwrap();
-        dev_lock.clone()
-    };
+        // Lock left behind by a process that no longer exists
+        fs::write(locks.lock_path("RF123456"), "999999999").unwrap();
+        // Lock held by this (live) process
+        fs::write(locks.lock_path("emulator-5554"), std::process::id().to_string()).unwrap();
 
-    // Kill any running wipe scripts
-    let _ = Command::new("pkill")
-        .arg("-f")
-        .arg("wipe.sh")
-        .output();
+        assert_eq!(locks.clean_stale(), 1);
+        assert!(!locks.lock_path("RF123456").exists());
+        assert!(locks.lock_path("emulator-5554").exists());
 
-    // If we have a device ID, clean up device-side processes and files
-    if let Some(device_id) = device_id {
-        // Kill dd process on device
-        let _ = Command::new("adb")
-            .arg("-s")
-            .arg(&device_id)
-            .arg("shell")
-            .arg("pkill -f 'dd if=/dev/urandom'")
-            .output();
+        // A stale lock is also reclaimed on acquire
+        fs::write(locks.lock_path("RF123456"), "999999999").unwrap();
+        assert!(locks.acquire("RF123456").is_ok());
 
-        // Clean up temp files
-        let _ = Command::new("adb")
-            .arg("-s")
-            .arg(&device_id)
-            .arg("shell")
-            .arg("rm -rf /sdcard/wipe_temp/")
-            .output();
+        fs::remove_dir_all(dir).unwrap();
     }
-}
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_shell::init())
-        .manage(WipeState {
-            device_id: Mutex::new(None),
-        })
-        .invoke_handler(tauri::generate_handler![
-            check_adb_status,
-            check_adb,
-            get_storage_info,
-            run_wipe,
-            abort_wipe,
-            run_factory_reset,
-            check_device_connected,
-            get_instructions,
-            revoke_adb,
-            cleanup_wipe_files,
-        ])
-        .on_window_event(|window, event| {
-            if let tauri::WindowEvent::CloseRequested { .. } = event {
-                // Cleanup when window is closed
-                if let Some(state) = window.try_state::<WipeState>() {
-                    cleanup_on_exit(&state);
-                }
-            }
-        })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
-}
+    fn sample_session() -> WipeSession {
+        WipeSession {
+            session_id: "1765548202000-RF123456".to_string(),
+            device: DeviceInfo {
+                id: "RF123456".to_string(),
+                model: "SM-S928U".to_string(),
+                brand: "samsung".to_string(),
+                android_version: "16".to_string(),
+                usb_speed: None,
+                ab_device: true,
+                bootloader: None,
+                baseband: None,
+            },
+            serial: "RF123456".to_string(),
+            mode: "quick".to_string(),
+            passes: 3,
+            size_mb: Some(1024),
+            started_at: 1765548202,
+            finished_at: 1765549102,
+            success: true,
+            coverage: Some(estimate_coverage(4096 * 1024 * 1024, 1024 * 1024 * 1024)),
+            verification: None,
+            operator: None,
+            free_space_coverage: Some(free_space_coverage(1 << 30, 1 << 30, true)),
+            warnings: Vec::new(),
+            storage_snapshots: Vec::new(),
+            factory_reset_skipped: false,
+            passes_completed: Some(3),
+            app_version: Some(APP_VERSION.to_string()),
+            script_version: Some("2.2.0".to_string()),
+            phase_durations: Vec::new(),
+            batch_id: None,
+        }
+    }
 
-// ============================================================================
-// Tests
-// ============================================================================
+    #[test]
+    fn test_format_utc_timestamp() {
+        assert_eq!(format_utc_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc_timestamp(1765548202), "2025-12-12 14:03:22 UTC");
+        // Leap day
+        assert_eq!(format_utc_timestamp(1709164800), "2024-02-29 00:00:00 UTC");
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_sanitize_session_id() {
+        assert!(sanitize_session_id("1765548202000-RF123456").is_ok());
+        assert!(sanitize_session_id(&new_session_id("192.168.1.1:5555")).is_ok());
+        assert!(sanitize_session_id("../../etc/passwd").is_err());
+        assert!(sanitize_session_id("").is_err());
+    }
 
     #[test]
-    fn test_sanitize_device_id_valid() {
-        assert!(sanitize_device_id("emulator-5554").is_ok());
-        assert!(sanitize_device_id("192.168.1.1:5555").is_ok());
-        assert!(sanitize_device_id("RFXXXXXXXX").is_ok());
-        assert!(sanitize_device_id("device_123").is_ok());
+    fn test_export_wipe_history() {
+        let dir = test_dir("history");
+        let store = SessionStore::new(dir.join("sessions"));
+        assert!(store.list().unwrap().is_empty());
+
+        let mut failed_run = sample_session();
+        failed_run.session_id = "1765640000000-ZY22".to_string();
+        failed_run.started_at = 1765640000;
+        failed_run.device.model = "Pixel 8, \"Pro\"".to_string();
+        failed_run.mode = "full".to_string();
+        failed_run.size_mb = None;
+        failed_run.success = false;
+        store.save(&failed_run).unwrap();
+        store.save(&sample_session()).unwrap();
+        fs::write(dir.join("sessions").join("broken.json"), "{").unwrap();
+
+        let entries: Vec<HistoryEntry> = store.list().unwrap().iter().map(history_entry).collect();
+        assert_eq!(entries.len(), 2);
+
+        let csv = history_to_csv(&entries);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "session_id,started,finished,brand,model,serial,method,result");
+        assert_eq!(
+            lines[1],
+            "1765548202000-RF123456,2025-12-12 14:03:22 UTC,2025-12-12 14:18:22 UTC,samsung,\
+             SM-S928U,RF123456,Quick overwrite: 3 passes x 1024MB of random data,completed"
+        );
+        // Oldest first; separators and quotes in a field are escaped
+        assert!(lines[2].contains(",\"Pixel 8, \"\"Pro\"\"\","));
+        assert!(lines[2].ends_with(",failed"));
+
+        let json: Vec<HistoryEntry> = serde_json::from_str(&history_to_json(&entries).unwrap())
+            .unwrap();
+        assert_eq!(json[1].model, "Pixel 8, \"Pro\"");
+        assert_eq!(json[0].result, "completed");
+        fs::remove_dir_all(dir).unwrap();
     }
 
     #[test]
-    fn test_sanitize_device_id_invalid() {
-        assert!(sanitize_device_id("").is_err());
-        assert!(sanitize_device_id("device; rm -rf /").is_err());
-        assert!(sanitize_device_id("$(whoami)").is_err());
-        assert!(sanitize_device_id(&"a".repeat(100)).is_err());
+    fn test_build_certificate() {
+        let cert = build_certificate(&sample_session());
+        assert_eq!(cert.device_model, "SM-S928U");
+        assert_eq!(cert.operator, None);
+        assert_eq!(cert.result, "success");
+        assert!(cert.nist_level.contains("Clear"));
+        assert_eq!(cert.method, "Quick overwrite: 3 passes x 1024MB of random data");
+        assert_eq!(cert.coverage.as_ref().unwrap().unreachable_bytes, 3072 * 1024 * 1024);
+
+        let mut failed = sample_session();
+        failed.success = false;
+        assert!(build_certificate(&failed).nist_level.starts_with("None"));
     }
 
     #[test]
-    fn test_parse_adb_devices() {
-        let output = "List of devices attached\n\
-                      emulator-5554\tdevice\n\
-                      192.168.1.1:5555\tdevice\n\
-                      RF123456\toffline\n";
+    fn test_operator_in_certificate() {
+        let operator = validate_operator(&OperatorInfo {
+            name: "  J. Rivera (IT-0042) ".to_string(),
+            case_reference: Some("DISPOSAL-2026-118".to_string()),
+        })
+        .unwrap();
+        assert_eq!(operator.name, "J. Rivera (IT-0042)");
 
-        let devices = parse_adb_devices(output);
-        assert_eq!(devices.len(), 2);
-        assert_eq!(devices[0].0, "emulator-5554");
-        assert_eq!(devices[1].0, "192.168.1.1:5555");
+        let mut session = sample_session();
+        session.operator = Some(operator.clone());
+        let cert = build_certificate(&session);
+        assert_eq!(cert.operator.as_deref(), Some("J. Rivera (IT-0042)"));
+        assert_eq!(cert.case_reference.as_deref(), Some("DISPOSAL-2026-118"));
+        let payload = serde_json::to_value(&cert).unwrap();
+        assert_eq!(payload["operator"], "J. Rivera (IT-0042)");
+        assert_eq!(payload["case_reference"], "DISPOSAL-2026-118");
+        assert_eq!(
+            describe_operator(Some(&operator)),
+            " operator=\"J. Rivera (IT-0042)\" case=\"DISPOSAL-2026-118\""
+        );
+
+        let reject = |name: &str, case: Option<&str>| {
+            validate_operator(&OperatorInfo {
+                name: name.to_string(),
+                case_reference: case.map(str::to_string),
+            })
+            .is_err()
+        };
+        assert!(reject("   ", None));
+        assert!(reject(&"x".repeat(OPERATOR_FIELD_MAX_CHARS + 1), None));
+        assert!(reject("J. Rivera\nresult=success", None));
+        assert!(reject("J. Rivera", Some("case\r\n42")));
+        let blank_case = OperatorInfo {
+            name: "J. Rivera".to_string(),
+            case_reference: Some(" ".to_string()),
+        };
+        assert_eq!(validate_operator(&blank_case).unwrap().case_reference, None);
     }
 
     #[test]
-    fn test_parse_adb_devices_empty() {
-        let output = "List of devices attached\n\n";
-        let devices = parse_adb_devices(output);
-        assert!(devices.is_empty());
+    fn test_find_certificates_by_serial() {
+        let dir = test_dir("find-certificates");
+        let sessions = SessionStore::new(dir.join("sessions"));
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+
+        let mut second = sample_session();
+        second.session_id = "1765640000000-RF123456".to_string();
+        second.started_at = 1765640000;
+        second.finished_at = 1765640900;
+        let mut other = sample_session();
+        other.session_id = "1765600000000-ZY22".to_string();
+        other.serial = "ZY22".to_string();
+        sessions.save(&second).unwrap();
+        sessions.save(&sample_session()).unwrap();
+        sessions.save(&other).unwrap();
+
+        let generation = ArtifactGeneration::default().begin();
+        let found = find_certificates_with(&sessions, &key, &generation, "rf123456").unwrap();
+        let ids: Vec<&str> = found.iter().map(|cert| cert.session_id.as_str()).collect();
+        assert_eq!(ids, ["1765548202000-RF123456", "1765640000000-RF123456"]);
+        assert_eq!(found[1].started_at, 1765640000);
+        assert_eq!(found[1].completed_at, 1765640900);
+        assert!(found.iter().all(|cert| cert.signature.is_some()));
+
+        assert!(find_certificates_with(&sessions, &key, &generation, "UNKNOWN")
+            .unwrap()
+            .is_empty());
+        assert!(find_certificates_with(&sessions, &key, &generation, "../x").is_err());
+        fs::remove_dir_all(dir).unwrap();
     }
 
     #[test]
-    fn test_parse_df_output() {
-        // Real Samsung S24 output format (1K-blocks, not MB)
-        let output = "Filesystem     1K-blocks    Used Available Use% Mounted on\n\
-                      /dev/fuse      483563724 3229496 480203156   1% /storage/emulated\n";
+    fn test_export_batch_report() {
+        let dir = test_dir("batch-report");
+        let batches = BatchStore::new(dir.join("batches"));
+        let result = |device_id: &str, success, skipped, message: &str| BatchDeviceResult {
+            device_id: device_id.to_string(),
+            success,
+            skipped,
+            cancelled: false,
+            message: message.to_string(),
+        };
+        let report = BatchReport {
+            batch_id: "batch-1765548200000".to_string(),
+            finished_at: 1765549000,
+            results: vec![
+                result("RF123456", true, false, "Wipe completed"),
+                result("ZY22", false, false, "Device disconnected, \"quick\" mode"),
+                result("ZY33", false, true, "Not attempted"),
+            ],
+            succeeded: 1,
+            failed: 2,
+            cancelled: 0,
+        };
+        let dir_audit = AuditLog::new(dir.join("logs"), DEFAULT_AUDIT_LOG_MAX_BYTES);
+        let events = EventRecorder::default();
+        record_batch_report(&dir_audit, &events, &batches, &report);
+        assert!(events.sequence().is_empty());
+        let report = batches.load("batch-1765548200000").unwrap();
 
-        let info = parse_df_output(output).unwrap();
-        // Values are converted from KB to MB (divided by 1024)
-        assert_eq!(info.total_mb, 483563724 / 1024);  // ~472230 MB
-        assert_eq!(info.used_mb, 3229496 / 1024);     // ~3153 MB
-        assert_eq!(info.available_mb, 480203156 / 1024); // ~468948 MB
-        assert_eq!(info.percent_used, 1);
+        // A report that can't be saved is still audited, and the failure reported
+        fs::write(dir.join("not-a-dir"), "").unwrap();
+        let unwritable = BatchStore::new(dir.join("not-a-dir"));
+        record_batch_report(&dir_audit, &events, &unwritable, &report);
+        assert_eq!(events.sequence(), ["batch-report-not-saved"]);
+        let logged: Vec<String> =
+            read_all_audit_entries(&dir_audit).into_iter().map(|e| e.event).collect();
+        assert_eq!(logged, ["batch-completed", "batch-completed", "batch-report-not-saved"]);
+
+        let mut session = sample_session();
+        session.batch_id = Some(report.batch_id.clone());
+        let mut unrelated = sample_session();
+        unrelated.session_id = "1765400000000-RF123456".to_string();
+        let entries = batch_report_entries(&report, &[unrelated, session]);
+        assert_eq!(entries[0].result, "succeeded");
+        assert_eq!(entries[0].certificate.as_deref(), Some("1765548202000-RF123456"));
+        assert_eq!(entries[0].serial, "RF123456");
+        assert_eq!(entries[1].result, "failed");
+        assert_eq!(entries[1].certificate, None);
+        assert_eq!(entries[2].result, "skipped");
+
+        let csv = batch_report_to_csv(&entries);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1]
+            .starts_with("RF123456,SM-S928U,RF123456,succeeded,1765548202000-RF123456,"));
+        assert!(lines[2].ends_with(",\"Device disconnected, \"\"quick\"\" mode\""));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&batch_report_to_json(&report, &entries).unwrap()).unwrap();
+        assert_eq!(json["batch_id"], "batch-1765548200000");
+        assert_eq!(json["failed"], 2);
+        assert_eq!(json["devices"][0]["certificate"], "1765548202000-RF123456");
+
+        let generation = ArtifactGeneration::default().begin();
+        let pdf = render_batch_report_pdf(&report, &entries, &generation).unwrap();
+        assert!(pdf.starts_with(b"%PDF-"));
+        let many: Vec<BatchReportEntry> = (0..PDF_BATCH_DEVICES_PER_PAGE + 1)
+            .map(|_| entries[1].clone())
+            .collect();
+        let pdf = render_batch_report_pdf(&report, &many, &generation).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf).to_string();
+        assert!(pdf.contains("/Count 2"));
+
+        assert!(batches.load("batch-unknown").is_err());
+        fs::remove_dir_all(dir).unwrap();
     }
 
     #[test]
-    fn test_parse_df_output_invalid() {
-        let output = "Error: device not found\n";
-        assert!(parse_df_output(output).is_err());
+    fn test_cancelled_generation_stops() {
+        let dir = test_dir("cancel-generation");
+        let sessions = SessionStore::new(dir.join("sessions"));
+        sessions.save(&sample_session()).unwrap();
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let report = BatchReport {
+            batch_id: "batch-1".to_string(),
+            finished_at: 0,
+            results: Vec::new(),
+            succeeded: 0,
+            failed: 0,
+            cancelled: 0,
+        };
+
+        let generation = ArtifactGeneration::default();
+        let first = generation.begin();
+        let second = generation.begin();
+        assert_eq!(generation.cancel(), 2);
+        // Starting another generation doesn't clear the cancel for the ones running
+        let next = generation.begin();
+        let found = find_certificates_with(&sessions, &key, &first, "RF123456");
+        assert_eq!(found.unwrap_err(), GENERATION_CANCELLED);
+        let pdf = render_batch_report_pdf(&report, &[], &second);
+        assert_eq!(pdf.unwrap_err(), GENERATION_CANCELLED);
+        // The render loop itself checks, not only the command before it starts
+        let cert = build_certificate(&sample_session());
+        assert_eq!(render_certificate_pdf(&cert, &first).unwrap_err(), GENERATION_CANCELLED);
+
+        let events = EventRecorder::default();
+        assert!(finish_generation(&events, "batch-report", second.check()).is_err());
+        assert!(finish_generation(&events, "batch-report", Err::<(), _>("x".into())).is_err());
+        let recorded = events.0.lock().unwrap().clone();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].0, "generation-cancelled");
+        assert_eq!(recorded[0].1["artifact"], "batch-report");
+
+        // The generation started after the cancel runs to completion
+        let found = find_certificates_with(&sessions, &key, &next, "RF123456");
+        assert_eq!(found.unwrap().len(), 1);
+        drop((first, second, next));
+        assert_eq!(generation.cancel(), 0);
+        fs::remove_dir_all(dir).unwrap();
     }
 
     #[test]
-    fn test_strip_ansi() {
-        let with_ansi = "\x1b[0;32mPass 2 complete\x1b[0m";
-        assert_eq!(strip_ansi(with_ansi), "Pass 2 complete");
+    fn test_render_certificate_pdf() {
+        let dir = test_dir("certificate-pdf");
+        let sessions = SessionStore::new(dir.join("sessions"));
+        sessions.save(&sample_session()).unwrap();
 
-        let no_ansi = "Pass 2 complete";
-        assert_eq!(strip_ansi(no_ansi), "Pass 2 complete");
+        let cert = build_certificate(&sessions.load("1765548202000-RF123456").unwrap());
+        let pdf = render_certificate_pdf(&cert, &ArtifactGeneration::default().begin()).unwrap();
+        let path = dir.join("certificate.pdf");
+        fs::write(&path, &pdf).unwrap();
+
+        assert!(fs::metadata(&path).unwrap().len() > 0);
+        assert!(pdf.starts_with(b"%PDF-"));
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("Certificate of Data Sanitization"));
+        assert!(text.contains("SM-S928U"));
+
+        fs::remove_dir_all(dir).unwrap();
     }
 
     #[test]
-    fn test_parse_progress_line() {
-        // Test "Pass N complete" with ANSI codes
-        let line = "\x1b[0;32mPass 2 complete\x1b[0m";
-        let progress = parse_progress_line(line, 3).unwrap();
-        assert_eq!(progress.pass, 2);
-        assert_eq!(progress.total_passes, 3);
-        assert!((progress.percent - 66.67).abs() < 1.0);
+    fn test_certificate_sign_verify_round_trip() {
+        let dir = test_dir("certificate-verify");
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let other_key = SigningKey::from_bytes(&[9u8; 32]);
+
+        // Generate and sign
+        let mut cert = build_certificate(&sample_session());
+        sign_certificate(&mut cert, &key).unwrap();
+        let path = dir.join("certificate.json");
+        fs::write(&path, serde_json::to_string_pretty(&cert).unwrap()).unwrap();
+
+        let result = verify_certificate_file(&path, Some(&key.verifying_key())).unwrap();
+        assert!(result.valid);
+        assert!(result.known_key);
+        assert_eq!(result.key_id, Some(signing_key_id(&key.verifying_key())));
+
+        // Valid signature from a different installation's key
+        let result = verify_certificate_file(&path, Some(&other_key.verifying_key())).unwrap();
+        assert!(result.valid);
+        assert!(!result.known_key);
+
+        // Tamper with a field
+        let mut tampered = cert.clone();
+        tampered.device_serial = "RF999999".to_string();
+        fs::write(&path, serde_json::to_string(&tampered).unwrap()).unwrap();
+        let result = verify_certificate_file(&path, Some(&key.verifying_key())).unwrap();
+        assert!(!result.valid);
+        assert!(result.reason.unwrap().contains("tampered"));
+
+        // Swap in another key's public key without re-signing
+        let mut swapped = cert.clone();
+        swapped.public_key = Some(hex::encode(other_key.verifying_key().to_bytes()));
+        swapped.key_id = Some(signing_key_id(&other_key.verifying_key()));
+        assert!(!verify_certificate_signature(&swapped, None).valid);
+
+        // Malformed and unsigned files are reported, not errors
+        fs::write(&path, "{ not json").unwrap();
+        assert!(!verify_certificate_file(&path, None).unwrap().valid);
+        let unsigned = build_certificate(&sample_session());
+        assert!(!verify_certificate_signature(&unsigned, None).valid);
+
+        // Missing file is an error
+        assert!(verify_certificate_file(&dir.join("missing.json"), None).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
     }
 
     #[test]
-    fn test_parse_progress_line_final() {
-        // Test "Passes completed: N" format (final summary)
-        let line = "\x1b[0;32mPasses completed: 3\x1b[0m";
-        let progress = parse_progress_line(line, 3).unwrap();
-        assert_eq!(progress.pass, 3);
-        assert_eq!(progress.total_passes, 3);
-        assert_eq!(progress.percent, 100.0);
-        assert_eq!(progress.phase, "complete");
+    fn test_signing_key_persisted() {
+        let dir = test_dir("signing-key");
+        let first = load_or_create_signing_key(&dir).unwrap();
+        let second = load_or_create_signing_key(&dir).unwrap();
+        assert_eq!(first.to_bytes(), second.to_bytes());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join(SIGNING_KEY_FILE)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_dir_all(dir).unwrap();
     }
 
     #[test]
-    fn test_parse_progress_line_no_match() {
-        let line = "Starting wipe operation...";
-        assert!(parse_progress_line(line, 3).is_none());
+    fn test_validate_export_path() {
+        let dir = std::env::temp_dir();
+        assert!(validate_export_path(dir.join("cert.pdf").to_str().unwrap(), "pdf").is_ok());
+        assert!(validate_export_path(dir.join("cert.txt").to_str().unwrap(), "pdf").is_err());
+        assert!(validate_export_path("cert.pdf", "pdf").is_err());
+        let missing = dir.join("missing/cert.pdf");
+        assert!(validate_export_path(missing.to_str().unwrap(), "pdf").is_err());
     }
 
     #[test]
-    fn test_parse_progress_line_full_wipe_format() {
-        // Test "PROGRESS: Pass N - XMB / YMB (Z%)" format from full_wipe.sh
-        let line = "\x1b[1;33mPROGRESS: Pass 1 - 256MB / 50000MB (0%)\x1b[0m";
-        let progress = parse_progress_line(line, 3).unwrap();
-        assert_eq!(progress.pass, 1);
-        assert_eq!(progress.total_passes, 3);
-        // Pass 1 at 0% with 3 passes = ((0 + 0) / 3) * 100 = 0%
-        assert!(progress.percent < 1.0);
-        assert_eq!(progress.phase, "writing");
+    fn test_build_factory_reset_result() {
+        let result = build_factory_reset_result(
+            "android.settings.BACKUP_AND_RESET_SETTINGS",
+            "Backup & Reset",
+            false,
+            true,
+            "samsung".to_string(),
+            "Galaxy S24 Ultra".to_string(),
+            Vec::new(),
+        );
+        assert_eq!(result.intent, "android.settings.BACKUP_AND_RESET_SETTINGS");
+        assert_eq!(result.screen, "Backup & Reset");
+        assert!(!result.fallback);
+        assert_eq!(result.phase, "final");
+        // Steps match what get_instructions returns for the same device
+        assert_eq!(
+            result.instructions,
+            get_instructions("samsung".to_string(), "Galaxy S24 Ultra".to_string())
+        );
+        assert!(result.summary.contains("Backup & Reset"));
+        assert!(result.summary.contains("final reset"));
 
-        // Test mid-pass progress
-        let line2 = "PROGRESS: Pass 2 - 25000MB / 50000MB (50%)";
-        let progress2 = parse_progress_line(line2, 3).unwrap();
-        assert_eq!(progress2.pass, 2);
-        // Pass 2 at 50% with 3 passes = ((1 + 0.5) / 3) * 100 = 50%
-        assert!((progress2.percent - 50.0).abs() < 1.0);
+        let fallback = build_factory_reset_result(
+            "com.android.settings/.Settings",
+            "Settings",
+            true,
+            false,
+            "google".to_string(),
+            "Pixel 8".to_string(),
+            vec![BlockedIntent {
+                intent: "android.settings.MASTER_CLEAR".to_string(),
+                blocked_reason: "Permission Denial".to_string(),
+            }],
+        );
+        assert!(fallback.fallback);
+        assert_eq!(fallback.phase, "initial");
+        assert!(!fallback.instructions.is_empty());
+        assert!(fallback.summary.contains("blocks the automated reset"));
+        assert!(!result.summary.contains("blocks the automated reset"));
+
+        // Serializes with the field names the frontend reads
+        let json = serde_json::to_value(&fallback).unwrap();
+        assert_eq!(json["phase"], "initial");
+        assert!(json["instructions"].is_array());
     }
 
     #[test]
-    fn test_wipe_config_validation() {
-        let config = WipeConfig {
-            mode: "quick".to_string(),
-            passes: 25, // Over limit
-            size_mb: Some(50), // Under limit
-            double_reset: false,
+    fn test_interruptible_sleep() {
+        let cancel = AtomicBool::new(false);
+        assert!(interruptible_sleep(Duration::from_millis(10), &cancel));
+
+        cancel.store(true, Ordering::SeqCst);
+        let started = Instant::now();
+        assert!(!interruptible_sleep(Duration::from_secs(60), &cancel));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // Cancelling a device's waits ends a poll loop in progress on it, and only that one
+        let offline = || FakeRunner(vec![("get-state", ok("offline\n"))]);
+        let waiting = std::thread::spawn(move || {
+            let started = Instant::now();
+            let back = wait_for_adbd(&offline(), "WAIT0001", MAX_POLL_TIMEOUT, MAX_POLL_TIMEOUT);
+            (back, started.elapsed())
+        });
+        let other = DEVICE_WAITS.register("WAIT0002");
+        while DEVICE_WAITS.cancel("WAIT0001") == 0 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let (back, elapsed) = waiting.join().unwrap();
+        assert!(!back);
+        assert!(elapsed < Duration::from_secs(5));
+        assert!(!other.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_poll_loops_stop_at_attempt_cap() {
+        // A device that never settles, polled with no interval and an hour to wait: every
+        // loop has to end at MAX_POLL_ATTEMPTS rather than spin for the hour
+        let hour = Duration::from_secs(3600);
+        let online = "List of devices attached\nRF123456\tdevice\n";
+        let stuck = || {
+            RecordingRunner::new(vec![
+                ("get-state", ok("offline\n")),
+                ("adb devices", ok(online)),
+                ("sys.boot_completed", ok("1\n")),
+                ("user_setup_complete", ok("1\n")),
+                ("reboot -p", ok("")),
+                ("resolve-activity", ok("com.android.settings/.MasterClear\n")),
+                ("dumpsys activity", ok("  mResumedActivity: ActivityRecord{1 u0 a.b/.C t3}\n")),
+            ])
         };
+        let recorder = EventRecorder::default();
 
-        // Passes should clamp to 20
-        assert_eq!(config.passes.clamp(1, 20), 20);
+        let runner = stuck();
+        assert!(!wait_for_adbd(&runner, "RF123456", hour, Duration::ZERO));
+        assert_eq!(runner.count("get-state"), MAX_POLL_ATTEMPTS as usize);
 
-        // Size should clamp to minimum 64
-        assert_eq!(config.size_mb.unwrap().clamp(64, 10240), 64);
+        let runner = stuck();
+        let check =
+            poll_reset_started_with(&runner, &recorder, "RF123456", hour, Duration::ZERO).unwrap();
+        assert!(check.message.starts_with("Timed out"));
+        assert_eq!(runner.count("adb devices"), MAX_POLL_ATTEMPTS as usize);
+
+        let runner = stuck();
+        let intent = "android.settings.MASTER_CLEAR";
+        let blocked = check_intent_opened(&runner, "RF123456", intent, hour, Duration::ZERO);
+        assert!(blocked.unwrap().contains("nothing opened"));
+        assert_eq!(runner.count("dumpsys activity"), MAX_POLL_ATTEMPTS as usize);
+
+        let runner = stuck();
+        let result = power_off_with(&runner, &recorder, "RF123456", hour, Duration::ZERO);
+        assert!(result.unwrap_err().starts_with("Timed out"));
+        assert_eq!(runner.count("adb devices"), MAX_POLL_ATTEMPTS as usize);
+    }
+
+    #[test]
+    fn test_reset_intent_foreground_check() {
+        let dumpsys = |component: &str| {
+            ok(&format!(
+                "ACTIVITY MANAGER ACTIVITIES (dumpsys activity activities)\n\
+                 Display #0 (activities from top to bottom):\n\
+                 \x20 mResumedActivity: ActivityRecord{{8f1c2d4 u0 {} t412}}\n",
+                component
+            ))
+        };
+        let launcher = "com.sec.android.app.launcher/.activities.LauncherActivity";
+        assert_eq!(
+            parse_resumed_activity(&dumpsys(launcher).stdout).as_deref(),
+            Some(launcher)
+        );
+        let newer = "  topResumedActivity=ActivityRecord{1 u0 a.b/.C t3}";
+        assert_eq!(parse_resumed_activity(newer).as_deref(), Some("a.b/.C"));
+        assert_eq!(parse_resumed_activity("Permission Denial"), None);
+
+        let master_clear = "com.android.settings/.Settings$FactoryResetActivity";
+        assert!(is_expected_activity(
+            "com.android.settings/com.android.settings.Settings$FactoryResetActivity",
+            Some(master_clear)
+        ));
+        assert!(is_expected_activity("com.android.settings/.SubSettings", Some(master_clear)));
+        assert!(!is_expected_activity(launcher, Some(master_clear)));
+        assert!(is_expected_activity("com.android.settings/.SubSettings", None));
+
+        // MASTER_CLEAR is accepted but the launcher stays on top: fall through to the next
+        let ignored = FakeRunner(vec![
+            ("getprop", ok("")),
+            ("resolve-activity", ok(master_clear)),
+            ("am start", ok("Starting: Intent { act=android.settings.MASTER_CLEAR }")),
+            ("dumpsys activity", dumpsys(launcher)),
+        ]);
+        let result =
+            open_factory_reset_checked(&ignored, "RF123456", false, Duration::ZERO, Duration::ZERO)
+                .unwrap();
+        assert!(result.fallback);
+        assert_eq!(result.blocked.len(), RESET_INTENTS.len());
+        assert!(result.blocked[0].blocked_reason.contains("nothing opened"));
+
+        let opened = FakeRunner(vec![
+            ("getprop", ok("")),
+            ("resolve-activity", ok(master_clear)),
+            ("am start", ok("Starting: Intent { act=android.settings.MASTER_CLEAR }")),
+            ("dumpsys activity", dumpsys(master_clear)),
+        ]);
+        let result =
+            open_factory_reset_checked(&opened, "RF123456", false, Duration::ZERO, Duration::ZERO)
+                .unwrap();
+        assert_eq!(result.intent, "android.settings.MASTER_CLEAR");
+        assert!(result.blocked.is_empty());
+    }
+
+    #[test]
+    fn test_intent_blocked_reason() {
+        let denied = "Starting: Intent { act=android.settings.MASTER_CLEAR }\n\
+                      java.lang.SecurityException: Permission Denial: starting Intent { \
+                      act=android.settings.MASTER_CLEAR } from null (pid=1234, uid=2000) \
+                      requires android.permission.MASTER_CLEAR";
+        assert_eq!(intent_blocked_reason("", denied).as_deref(), Some("Permission Denial"));
+        assert_eq!(
+            intent_blocked_reason("", "Exception occurred: java.lang.SecurityException").as_deref(),
+            Some("SecurityException")
+        );
+        let unresolved = "Starting: Intent { act=android.settings.PRIVACY_SETTINGS }\n\
+                          Error: Activity not started, unable to resolve Intent { \
+                          act=android.settings.PRIVACY_SETTINGS flg=0x10000000 }";
+        assert_eq!(
+            intent_blocked_reason(unresolved, "").as_deref(),
+            Some("Intent not available on this device")
+        );
+        assert_eq!(
+            intent_blocked_reason("Error: Activity class does not exist.", "").as_deref(),
+            Some("Error: Activity class does not exist.")
+        );
+        assert_eq!(
+            intent_blocked_reason("Starting: Intent { act=android.settings.MASTER_CLEAR }", ""),
+            None
+        );
     }
 
     #[test]
@@ -980,6 +12630,74 @@ mod tests {
         assert!(instructions.iter().any(|s| s.contains("may vary")));
     }
 
+    #[test]
+    fn test_supported_brands_match_instructions() {
+        let generic = get_instructions("Unknown".to_string(), String::new());
+        let brands = get_supported_brands();
+        assert_eq!(brands.len(), SUPPORTED_BRANDS.len());
+        for brand in &brands {
+            assert_ne!(get_instructions(brand.clone(), String::new()), generic, "{}", brand);
+        }
+        // Brands without their own arm fall through to the generic steps
+        for brand in ["xiaomi", "sony", "oppo", "huawei"] {
+            assert!(!brands.iter().any(|b| b == brand));
+            assert_eq!(get_instructions(brand.to_string(), String::new()), generic);
+        }
+    }
+
+    #[test]
+    fn test_audit_log_rotation_preserves_entries() {
+        let dir = test_dir("audit-rotation");
+        let log = AuditLog::new(dir.clone(), MIN_AUDIT_LOG_MAX_BYTES);
+
+        // Each entry is ~100 bytes, so 100 entries cross the 4KB limit several times
+        for i in 0..100 {
+            audit(&log, "wipe-started", Some("emulator-5554"), format!("entry {}", i));
+        }
+
+        let archives = log.list_archives().unwrap();
+        assert!(archives.len() >= 2);
+        assert!(archives.iter().all(|a| a.size_bytes <= MIN_AUDIT_LOG_MAX_BYTES));
+
+        // Every entry survives, in order, across the rotation boundaries
+        let entries = read_all_audit_entries(&log);
+        assert_eq!(entries.len(), 100);
+        for (i, entry) in entries.iter().enumerate() {
+            assert_eq!(entry.details, format!("entry {}", i));
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_audit_log_manual_rotate() {
+        let dir = test_dir("audit-manual");
+        let log = AuditLog::new(dir.clone(), DEFAULT_AUDIT_LOG_MAX_BYTES);
+
+        // Nothing to rotate yet
+        assert!(log.rotate().unwrap().is_none());
+
+        audit(&log, "wipe-started", Some("RF123456"), "before".to_string());
+        let archive = log.rotate().unwrap().unwrap();
+        assert!(archive.exists());
+        assert!(!log.path().exists());
+
+        audit(&log, "wipe-completed", Some("RF123456"), "after".to_string());
+        let entries = read_all_audit_entries(&log);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].details, "before");
+        assert_eq!(entries[1].details, "after");
+        assert_eq!(log.list_archives().unwrap().len(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_archive_sort_key() {
+        assert!(archive_sort_key("audit-100.log") < archive_sort_key("audit-100-1.log"));
+        assert!(archive_sort_key("audit-100-2.log") < archive_sort_key("audit-101.log"));
+    }
+
     #[test]
     fn test_get_instructions_case_insensitive() {
         let instructions1 = get_instructions("SAMSUNG".to_string(), "galaxy s24".to_string());
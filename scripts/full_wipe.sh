@@ -4,11 +4,17 @@
 # Dynamic storage detection, multi-pass full storage overwrite
 # Includes logging, progress tracking, and desktop notification
 #
-# Version: 2.2.0
+# Version: 2.3.0
 # Repository: https://github.com/OnlyParams/android-secure-wipe
 #
 # CHANGELOG:
 # ----------
+# v2.3.0 (2026-10-16)
+#   - --help now lists --raw, so the desktop app can detect it
+#   - Added --keep-files flag to leave the last pass's data for inspection
+#   - Reports "Removing temporary files..." while the wipe directory is removed
+#   - Desktop notifications escape quotes and backslashes in the device model
+#
 # v2.2.0 (2024-12-11)
 #   - Added input validation for --passes (must be 1-20)
 #   - Rejects non-numeric values with clear error messages
@@ -53,7 +59,7 @@
 set -euo pipefail
 
 # Script version
-VERSION="2.2.0"
+VERSION="2.3.0"
 
 # Colors for output
 RED='\033[0;31m'
@@ -71,6 +77,7 @@ FILL_PERCENT=95    # Fill to 95% to avoid running out of space
 DRY_RUN=false
 AUTO_YES=false
 RAW_OUTPUT=false   # Raw mode for Tauri - no pipe buffering
+KEEP_FILES=false   # Leave the last pass's data on the device
 MIN_SPACE_MB=100   # Minimum required space in MB
 DEVICE=""          # Must be specified via -d flag
 
@@ -97,6 +104,10 @@ while [[ $# -gt 0 ]]; do
             RAW_OUTPUT=true
             shift
             ;;
+        --keep-files)
+            KEEP_FILES=true
+            shift
+            ;;
         --version|-v)
             echo "full_wipe.sh version $VERSION"
             exit 0
@@ -111,6 +122,8 @@ while [[ $# -gt 0 ]]; do
             echo "  -p, --passes N    Number of overwrite passes (default: 3)"
             echo "  --dry-run         Show what would be done without writing"
             echo "  --yes, -y         Skip confirmation prompt (for automation)"
+            echo "  --raw             Plain unbuffered output (used by the desktop app)"
+            echo "  --keep-files      Leave the last pass's data in $WIPE_DIR for inspection"
             echo "  --version         Show version number"
             echo "  --help            Show this help message"
             echo ""
@@ -176,14 +189,19 @@ notify() {
         notify-send "$title" "$message" 2>/dev/null || true
     fi
 
-    # macOS (osascript)
+    # macOS (osascript) - the model name may contain quotes or backslashes
     if command -v osascript &> /dev/null; then
-        osascript -e "display notification \"$message\" with title \"$title\"" 2>/dev/null || true
+        local as_message as_title
+        as_message=$(printf '%s' "$message" | sed 's/[\\"]/\\&/g')
+        as_title=$(printf '%s' "$title" | sed 's/[\\"]/\\&/g')
+        osascript -e "display notification \"$as_message\" with title \"$as_title\"" 2>/dev/null || true
     fi
 
     # Windows (PowerShell) - only works in certain terminals
     if command -v powershell.exe &> /dev/null; then
-        powershell.exe -Command "[System.Reflection.Assembly]::LoadWithPartialName('System.Windows.Forms'); [System.Windows.Forms.MessageBox]::Show('$message','$title')" 2>/dev/null || true
+        local ps_message="${message//\'/\'\'}"
+        local ps_title="${title//\'/\'\'}"
+        powershell.exe -Command "[System.Reflection.Assembly]::LoadWithPartialName('System.Windows.Forms'); [System.Windows.Forms.MessageBox]::Show('$ps_message','$ps_title')" 2>/dev/null || true
     fi
 }
 
@@ -387,6 +405,7 @@ WIPE_DIR=\"$WIPE_DIR\"
 TARGET_MB=$TARGET_MB
 PASSES=$PASSES
 CHUNK_MB=64  # Larger chunks = fewer files = less overhead
+KEEP_FILES=$KEEP_FILES
 
 mkdir -p \"\$WIPE_DIR\"
 
@@ -433,16 +452,23 @@ for pass in \$(seq 1 \$PASSES); do
     sync
     sleep 1
 
-    echo \"Cleaning up pass \$pass...\"
-    rm -rf \"\$PASS_DIR\"
-    sync
-    sleep 1
+    if [ \"\$KEEP_FILES\" = true ] && [ \$pass -eq \$PASSES ]; then
+        echo \"Keeping pass \$pass data in \$PASS_DIR\"
+    else
+        echo \"Cleaning up pass \$pass...\"
+        rm -rf \"\$PASS_DIR\"
+        sync
+        sleep 1
+    fi
 
     echo \"PASS_COMPLETE: Pass \$pass done - wrote \${written}MB\"
 done
 
 # Final cleanup
-rm -rf \"\$WIPE_DIR\"
+if [ \"\$KEEP_FILES\" != true ]; then
+    echo \"Removing temporary files...\"
+    rm -rf \"\$WIPE_DIR\"
+fi
 sync
 
 echo \"WIPE_COMPLETE: All \$PASSES passes finished\"
@@ -476,7 +502,7 @@ else
                 echo ""
                 log "${GREEN}${line#WIPE_COMPLETE: }${NC}"
                 ;;
-            "Syncing"*|"Cleaning"*)
+            "Syncing"*|"Cleaning"*|"Removing"*)
                 echo ""
                 echo -e "${YELLOW}  $line${NC}"
                 ;;